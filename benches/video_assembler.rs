@@ -0,0 +1,58 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use tello::VideoFrameAssembler;
+
+/// synthetic stream of frames with 8 packets, like a 4 Mbit/s stream of the drone
+fn packets() -> Vec<Vec<u8>> {
+    let mut packets = Vec::new();
+    for frame_id in 0..30u8 {
+        for sqn in 0..8u8 {
+            let sqn = if sqn == 7 { 0x80 | sqn } else { sqn };
+            let mut packet = vec![frame_id, sqn];
+            packet.extend_from_slice(&[0x42; 1458]);
+            packets.push(packet);
+        }
+    }
+    packets
+}
+
+/// the frame collection as it was done before: allocate and append every packet
+fn collect_with_append(packets: &[Vec<u8>]) -> usize {
+    let mut frames = 0;
+    let mut frame_buffer: Vec<u8> = Vec::new();
+    for packet in packets {
+        if packet[1] == 0 {
+            frame_buffer = packet[2..].to_owned();
+        } else {
+            let mut data = packet[2..].to_owned();
+            frame_buffer.append(&mut data);
+        }
+        if packet[1] >= 120 {
+            frames += black_box(&frame_buffer).len();
+        }
+    }
+    frames
+}
+
+fn collect_with_assembler(assembler: &mut VideoFrameAssembler, packets: &[Vec<u8>]) -> usize {
+    let mut frames = 0;
+    for packet in packets {
+        if let Some((_, frame)) = assembler.push(packet) {
+            frames += black_box(frame).len();
+        }
+    }
+    frames
+}
+
+fn video_benchmark(c: &mut Criterion) {
+    let packets = packets();
+    c.bench_function("video frames with append", |b| {
+        b.iter(|| collect_with_append(black_box(&packets)))
+    });
+    let mut assembler = VideoFrameAssembler::default();
+    c.bench_function("video frames with assembler", |b| {
+        b.iter(|| collect_with_assembler(&mut assembler, black_box(&packets)))
+    });
+}
+
+criterion_group!(benches, video_benchmark);
+criterion_main!(benches);
@@ -1,11 +1,11 @@
 use futures::executor::block_on;
-use std::{string::String, thread::sleep, time::Duration};
-use tello::Drone;
+use std::{string::String, time::Duration};
+use tello::{Drone, Mission};
 
 fn main() -> Result<(), String> {
     block_on(async {
         let mut drone = Drone::new("192.168.10.1:8889").command_mode();
-        let state = drone.state_receiver().unwrap();
+        let state = drone.state_receiver()?.unwrap();
         drone.enable().await?;
 
         match state.recv_timeout(Duration::from_secs(5)) {
@@ -16,18 +16,16 @@ fn main() -> Result<(), String> {
             _ => println!("No state package received"),
         }
 
-        println!("take_off {:?}", drone.take_off().await);
-        sleep(Duration::from_secs(7));
-
+        let mut mission = Mission::new().take_off();
         for _ in 0..6 {
-            println!("forward {:?}", drone.forward(30).await);
-            sleep(Duration::from_secs(5));
-            println!("cw {:?}", drone.cw(60).await);
-            sleep(Duration::from_secs(4));
+            mission = mission.forward(30).cw(60);
         }
+        let mission = mission.land();
 
-        println!("land {:?}", drone.land().await);
-        sleep(Duration::from_secs(3));
+        for report in drone.run_mission(&mission).await {
+            println!("{:?} {:?}", report.step, report.result);
+        }
+        println!("POS {:?}", drone.odometry);
         Ok(())
     })
 }
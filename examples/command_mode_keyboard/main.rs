@@ -5,14 +5,15 @@ use std::thread::sleep;
 use std::time::Duration;
 use std::{io, thread};
 
-use tello::Drone;
+use tello::{Drone, Flip};
 
 fn main() -> Result<(), String> {
     block_on(async {
         let mut drone = Drone::new("192.168.10.1:8889").command_mode();
-        let state = drone.state_receiver().unwrap();
+        let state = drone.state_receiver()?.unwrap();
 
         let stdin_channel = create_stdin_channel();
+        drone.set_retries(2);
         let _failed_sometimes_but_works = drone.enable().await;
         'mainLoop: loop {
             match stdin_channel.try_recv() {
@@ -33,7 +34,21 @@ fn main() -> Result<(), String> {
                         "right" => drone.right(commands[1].parse().unwrap_or(0)).await,
                         "cw" => drone.cw(commands[1].parse().unwrap_or(0)).await,
                         "ccw" => drone.ccw(commands[1].parse().unwrap_or(0)).await,
-                        //"home" => drone.go_home(0, 0, 0, 20),
+                        "flip" => match commands.get(1) {
+                            Some(&"l") => drone.flip(Flip::Left).await,
+                            Some(&"r") => drone.flip(Flip::Right).await,
+                            Some(&"f") => drone.flip(Flip::Forward).await,
+                            Some(&"b") => drone.flip(Flip::Back).await,
+                            _ => {
+                                println!("usage: flip l|r|f|b");
+                                Ok(())
+                            }
+                        },
+                        "battery?" => drone
+                            .get_battery()
+                            .await
+                            .map(|bat| println!("battery {}%", bat)),
+                        "home" => drone.go_home(20).await,
                         _ => Ok(()),
                     };
                     if send.is_err() {
@@ -6,7 +6,7 @@ use tokio_stream::wrappers::WatchStream;
 async fn main() -> Result<(), String> {
     let mut drone = Drone::new("192.168.10.1:8889").command_mode();
     drone.enable().await?;
-    let state = WatchStream::new(drone.state_receiver().unwrap());
+    let state = WatchStream::new(drone.state_receiver()?.unwrap());
 
     let path = async {
         println!("take off {:?}", drone.take_off().await);
@@ -0,0 +1,73 @@
+use sdl2::event::Event;
+use sdl2::keyboard::Keycode;
+use std::string::String;
+use std::time::Duration;
+use tello::{DecodedMessage, DecodingPoller, Drone};
+
+#[path = "../fly/video_renderer.rs"]
+mod video_renderer;
+use video_renderer::VideoRenderer;
+
+const WINDOW_WIDTH: u32 = 960;
+const WINDOW_HEIGHT: u32 = 720;
+
+/// renders the decoded video of the drone into the SDL canvas.
+/// Requires the `decode` feature: `cargo run --example decoded_video --features decode`
+fn main() -> Result<(), String> {
+    let mut drone = Drone::new("192.168.10.1:8889");
+    let mut poller = DecodingPoller::new().map_err(|e| e.to_string())?;
+
+    let sdl_context = sdl2::init()?;
+    let window = sdl_context
+        .video()?
+        .window("TELLO video", WINDOW_WIDTH, WINDOW_HEIGHT)
+        .position_centered()
+        .build()
+        .expect("could not initialize video subsystem");
+    let mut canvas = window
+        .into_canvas()
+        .build()
+        .expect("could not make a canvas");
+    let texture_creator = canvas.texture_creator();
+    let mut video = VideoRenderer::new(&texture_creator);
+
+    drone.connect(11111);
+    let mut event_pump = sdl_context.event_pump()?;
+    'running: loop {
+        for event in event_pump.poll_iter() {
+            match event {
+                Event::Quit { .. }
+                | Event::KeyDown {
+                    keycode: Some(Keycode::Escape),
+                    ..
+                } => break 'running,
+                Event::KeyDown {
+                    keycode: Some(Keycode::V),
+                    ..
+                } => drone.start_video()?,
+                Event::KeyDown {
+                    keycode: Some(Keycode::K),
+                    ..
+                } => drone.take_off()?,
+                Event::KeyDown {
+                    keycode: Some(Keycode::L),
+                    ..
+                } => drone.land()?,
+                _ => {}
+            }
+        }
+
+        while let Some(msg) = poller.poll(&mut drone) {
+            if let DecodedMessage::Frame(_, frame) = msg {
+                video.update(frame.width, frame.height, &frame.rgb)?;
+            }
+        }
+
+        canvas.clear();
+        video.render(&mut canvas)?;
+        canvas.present();
+        std::thread::sleep(Duration::from_millis(10));
+    }
+    println!("skipped frames: {}", poller.skipped_frames());
+    Ok(())
+}
@@ -7,7 +7,10 @@ use std::string::String;
 use std::time::Duration;
 
 use std::ops::Deref;
-use tello::{Drone, Flip, Message, Package, PackageData, RCState, ResponseMsg};
+use tello::{Drone, Flip, GestureEvent, Message, Package, PackageData, RCState};
+
+mod video_renderer;
+use video_renderer::VideoRenderer;
 
 // extern crate glib;
 #[derive(Debug)]
@@ -57,6 +60,12 @@ fn main() -> Result<(), String> {
         .unwrap();
     let stats_target = Rect::new(50, WINDOW_HEIGHT as i32 - 40, WINDOW_WIDTH - 100, 40);
 
+    // the video is shown with the `decode` feature: `cargo run --example fly --features decode`
+    #[cfg_attr(not(feature = "decode"), allow(unused_mut))]
+    let mut video = VideoRenderer::new(&texture_creator);
+    #[cfg(feature = "decode")]
+    let mut decoder = tello::H264Decoder::new().map_err(|e| e.to_string())?;
+
     let mut event_pump = sdl_context.event_pump()?;
 
     let mut land = false;
@@ -67,11 +76,13 @@ fn main() -> Result<(), String> {
     'running: loop {
         canvas.set_draw_color(Color::RGB(80, 64, 255 - 80));
         canvas.clear();
+        // the video first, the overlays are drawn on top of it
+        video.render(&mut canvas)?;
         canvas.copy(&key_texture, None, Some(keys_target))?;
         canvas.copy(&control_texture, None, Some(control_target))?;
 
         if let Some(data) = drone.drone_meta.get_flight_data() {
-            let d = format!("{:?}", data);
+            let d = format!("{:?} sensors: {}", data, data.sensor_health());
             let surface_stats = font.render(d.deref()).blended(Color::RGB(0, 0, 0)).unwrap();
             let texture_stats = texture_creator
                 .create_texture_from_surface(&surface_stats)
@@ -111,7 +122,9 @@ fn main() -> Result<(), String> {
                     keycode: Some(Keycode::P),
                     ..
                 } => {
-                    drone.throw_and_go().unwrap();
+                    if let Err(e) = drone.throw_and_go() {
+                        println!("throw 'n go: {}", e);
+                    }
                 }
                 Event::KeyDown {
                     keycode: Some(Keycode::L),
@@ -134,8 +147,7 @@ fn main() -> Result<(), String> {
                         drone.start_video().unwrap();
                     } else {
                         video_on = false;
-                        // @TODO unknown command for stop_video
-                        drone.start_video().unwrap();
+                        drone.stop_video();
                     }
                 }
                 Event::KeyDown {
@@ -202,15 +214,27 @@ fn main() -> Result<(), String> {
             match msg {
                 Message::Data(Package {data: PackageData::FlightData(d), ..}) => {
                     println!("battery {}", d.battery_percentage);
+                    if let Some(countdown) = drone.drone_meta.throw_countdown() {
+                        println!("throw the drone within {:.1}s", countdown.as_secs_f32());
+                    }
                 }
+                Message::Gesture(GestureEvent::ThrowArmed) => println!("throw the drone now"),
+                Message::Gesture(event) => println!("{:?}", event),
                 Message::Data(d) /*if d.cmd != CommandIds::LogHeaderMsg*/ => {
                     println!("msg {:?}", d.clone());
                 }
+                #[cfg(feature = "decode")]
+                Message::Frame(_, d) => {
+                    for frame in decoder.decode(&d) {
+                        video.update(frame.width, frame.height, &frame.rgb)?;
+                    }
+                }
+                #[cfg(not(feature = "decode"))]
                 Message::Frame(frame_id, d)=> {
                     println!("frame {} {:?}", frame_id, &d[..15]);
                 }
-                Message::Response(ResponseMsg::Connected(_)) => {
-                    println!("connected");
+                Message::ConnectionChanged(state) => {
+                    println!("connection {:?}", state);
                 }
                 _ => ()
             }
@@ -0,0 +1,69 @@
+// the fly example updates the video only with the `decode` feature
+#![cfg_attr(not(feature = "decode"), allow(dead_code))]
+
+use sdl2::pixels::PixelFormatEnum;
+use sdl2::rect::Rect;
+use sdl2::render::{Texture, TextureCreator, WindowCanvas};
+use sdl2::video::WindowContext;
+
+/// shows the decoded RGB frames of the drone in a SDL canvas.
+///
+/// The texture follows the size of the frames, so a switch of the `VideoMode` (960x720 or
+/// 1280x720) is handled with the next frame.
+pub struct VideoRenderer<'a> {
+    texture_creator: &'a TextureCreator<WindowContext>,
+    texture: Option<(Texture<'a>, u32, u32)>,
+}
+
+impl<'a> VideoRenderer<'a> {
+    pub fn new(texture_creator: &'a TextureCreator<WindowContext>) -> Self {
+        Self {
+            texture_creator,
+            texture: None,
+        }
+    }
+
+    /// upload a RGB24 frame with `width * 3` bytes per row
+    pub fn update(&mut self, width: u32, height: u32, rgb: &[u8]) -> Result<(), String> {
+        if !matches!(self.texture, Some((_, w, h)) if (w, h) == (width, height)) {
+            let texture = self
+                .texture_creator
+                .create_texture_streaming(PixelFormatEnum::RGB24, width, height)
+                .map_err(|e| e.to_string())?;
+            self.texture = Some((texture, width, height));
+        }
+        if let Some((texture, ..)) = self.texture.as_mut() {
+            texture
+                .update(None, rgb, width as usize * 3)
+                .map_err(|e| e.to_string())?;
+        }
+        Ok(())
+    }
+
+    /// draw the last frame, centered in the canvas with the aspect ratio of the video
+    pub fn render(&self, canvas: &mut WindowCanvas) -> Result<(), String> {
+        if let Some((texture, width, height)) = &self.texture {
+            let target = fit((*width, *height), canvas.output_size()?);
+            canvas.copy(texture, None, Some(target))?;
+        }
+        Ok(())
+    }
+}
+
+/// largest rect with the aspect ratio of the video, centered in the window
+fn fit(video: (u32, u32), window: (u32, u32)) -> Rect {
+    let scale = f64::min(
+        window.0 as f64 / video.0 as f64,
+        window.1 as f64 / video.1 as f64,
+    );
+    let (w, h) = (
+        (video.0 as f64 * scale) as u32,
+        (video.1 as f64 * scale) as u32,
+    );
+    Rect::new(
+        ((window.0 - w) / 2) as i32,
+        ((window.1 - h) / 2) as i32,
+        w,
+        h,
+    )
+}
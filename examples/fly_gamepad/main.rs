@@ -7,7 +7,7 @@ use std::ops::Deref;
 use gilrs::{Gilrs, Axis, Event, Button, EventType};
 use std::net::UdpSocket;
 
-use tello::{Drone, Flip, Message, Package, PackageData, ResponseMsg};
+use tello::{Drone, Flip, Message, Package, PackageData};
 
 const WINDOW_WIDTH: u32 = 1280;
 const WINDOW_HEIGHT: u32 = 720;
@@ -71,7 +71,7 @@ fn main() -> Result<(), String> {
         }
 
         // map GamePad events to drone
-        while let Some(Event { event, .. }) = gilrs.next_event() {
+        while let Some(Event { id, event, .. }) = gilrs.next_event() {
             match event {
                 EventType::ButtonReleased(Button::Mode, _) => {
                     break 'running;
@@ -110,29 +110,15 @@ fn main() -> Result<(), String> {
                         drone.land().map_err(|_| "land failed")?;
                     }
                 }
-                // EventType::AxisChanged(Axis::LeftStickX, value, _) => {
-                //     drone.rc_state.go_left_right(value)
-                // }
-                // EventType::AxisChanged(Axis::LeftStickY, value, _) => {
-                //     drone.rc_state.go_forward_back(value)
-                // }
-                // EventType::AxisChanged(Axis::RightStickX, value, _) => {
-                //     drone.rc_state.turn(value)
-                // }
-                // EventType::AxisChanged(Axis::RightStickY, value, _) => {
-                //     drone.rc_state.go_up_down(value)
-                // }
-                EventType::AxisChanged(Axis::LeftStickX, value, _) => {
-                    drone.rc_state.turn(value)
-                }
-                EventType::AxisChanged(Axis::LeftStickY, value, _) => {
-                    drone.rc_state.go_up_down(value)
-                }
-                EventType::AxisChanged(Axis::RightStickX, value, _) => {
-                    drone.rc_state.go_left_right(value)
-                }
-                EventType::AxisChanged(Axis::RightStickY, value, _) => {
-                    drone.rc_state.go_forward_back(value)
+                // mode 2: the left stick turns and climbs, the right stick moves
+                EventType::AxisChanged(..) => {
+                    let pad = gilrs.gamepad(id);
+                    drone.rc_state.set_axes(
+                        pad.value(Axis::RightStickX),
+                        pad.value(Axis::RightStickY),
+                        pad.value(Axis::LeftStickY),
+                        pad.value(Axis::LeftStickX),
+                    );
                 }
                 EventType::ButtonPressed(Button::DPadDown, _) => {
                     drone.flip(Flip::Back).map_err(|_| "Flip failed")?;
@@ -164,8 +150,8 @@ fn main() -> Result<(), String> {
                     socket.send_to(&d, "127.0.0.1:11110").expect("couldn't send data");
                     println!("send frame {} {:?}", frame_id, &d[..15]);
                 }
-                Message::Response(ResponseMsg::Connected(_)) => {
-                    println!("connected");
+                Message::ConnectionChanged(state) => {
+                    println!("connection {:?}", state);
                 }
                 _ => ()
             }
@@ -0,0 +1,209 @@
+use crate::CommandIds;
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+/// default time to wait for the ack of a command, see `Drone::set_ack_timeout()`
+pub const DEFAULT_ACK_TIMEOUT: Duration = Duration::from_millis(500);
+/// number of the remembered acks, to drop the duplicates
+const ACK_HISTORY: usize = 16;
+
+/// Commands, the drone acknowledges with a package of the same command id and sequence number.
+///
+/// The acks of the video settings (`VideoEncoderRateCmd`, `ExposureCmd`) are not verified with
+/// the same sequence number, so they are not tracked and never time out.
+const ACKED_COMMANDS: &[CommandIds] = &[
+    CommandIds::TakeoffCmd,
+    CommandIds::LandCmd,
+    CommandIds::PalmLandCmd,
+    CommandIds::ThrowAndGoCmd,
+    CommandIds::BounceCmd,
+    CommandIds::TakePictureCommand,
+    CommandIds::AltLimitCmd,
+    CommandIds::AttLimitCmd,
+    CommandIds::LowBatThresholdCmd,
+];
+
+/// commands, that can be send again without a side effect, see `Drone::set_ack_retries()`
+const IDEMPOTENT_COMMANDS: &[CommandIds] = &[
+    CommandIds::TakeoffCmd,
+    CommandIds::LandCmd,
+    CommandIds::PalmLandCmd,
+    CommandIds::BounceCmd,
+    CommandIds::AltLimitCmd,
+    CommandIds::AttLimitCmd,
+    CommandIds::LowBatThresholdCmd,
+];
+
+/// a sent command, waiting for the ack of the drone, see `Drone::pending_commands()`
+#[derive(Debug, Clone, PartialEq)]
+pub struct PendingCommand {
+    pub cmd: CommandIds,
+    pub sq_nr: u16,
+    /// time of the first send
+    pub sent_at: Instant,
+    /// how often the command was send again
+    pub retries: u8,
+    last_sent: Instant,
+    data: Vec<u8>,
+}
+
+/// the received package in relation to the sent commands
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum AckMatch {
+    /// the first ack of a pending command
+    Ack,
+    /// another ack of an acknowledged command
+    Duplicate,
+    /// not an ack of a tracked command
+    Other,
+}
+
+/// Matches the acks of the drone with the sent commands.
+#[derive(Debug)]
+pub(crate) struct AckTracker {
+    pending: Vec<PendingCommand>,
+    acked: VecDeque<(CommandIds, u16)>,
+    pub timeout: Duration,
+    pub retries: u8,
+}
+
+impl Default for AckTracker {
+    fn default() -> Self {
+        Self {
+            pending: Vec::new(),
+            acked: VecDeque::new(),
+            timeout: DEFAULT_ACK_TIMEOUT,
+            retries: 0,
+        }
+    }
+}
+
+impl AckTracker {
+    /// track the sent package `data`, if the drone acknowledges the command. Commands with the
+    /// sequence number 0 can't be matched.
+    pub fn sent(&mut self, cmd: CommandIds, sq_nr: u16, data: &[u8], now: Instant) {
+        if sq_nr == 0 || !ACKED_COMMANDS.contains(&cmd) {
+            return;
+        }
+        self.pending.retain(|p| (p.cmd, p.sq_nr) != (cmd, sq_nr));
+        self.pending.push(PendingCommand {
+            cmd,
+            sq_nr,
+            sent_at: now,
+            retries: 0,
+            last_sent: now,
+            data: data.to_vec(),
+        });
+    }
+
+    /// match a received package with the pending commands
+    pub fn received(&mut self, cmd: CommandIds, sq_nr: u16) -> AckMatch {
+        match self
+            .pending
+            .iter()
+            .position(|p| (p.cmd, p.sq_nr) == (cmd, sq_nr))
+        {
+            Some(index) => {
+                self.pending.remove(index);
+                if self.acked.len() >= ACK_HISTORY {
+                    self.acked.pop_front();
+                }
+                self.acked.push_back((cmd, sq_nr));
+                AckMatch::Ack
+            }
+            None if self.acked.contains(&(cmd, sq_nr)) => AckMatch::Duplicate,
+            None => AckMatch::Other,
+        }
+    }
+
+    /// The packages to send again and the commands, that timed out. A command is send again
+    /// with the same sequence number, so a late ack still matches.
+    pub fn expire(&mut self, now: Instant) -> (Vec<Vec<u8>>, Vec<(CommandIds, u16)>) {
+        let mut resend = Vec::new();
+        let mut timed_out = Vec::new();
+        let mut index = 0;
+        while index < self.pending.len() {
+            let pending = &mut self.pending[index];
+            if now.duration_since(pending.last_sent) < self.timeout {
+                index += 1;
+            } else if pending.retries < self.retries && IDEMPOTENT_COMMANDS.contains(&pending.cmd) {
+                pending.retries += 1;
+                pending.last_sent = now;
+                resend.push(pending.data.clone());
+                index += 1;
+            } else {
+                let pending = self.pending.remove(index);
+                timed_out.push((pending.cmd, pending.sq_nr));
+            }
+        }
+        (resend, timed_out)
+    }
+
+    pub fn pending(&self) -> &[PendingCommand] {
+        &self.pending
+    }
+}
+
+#[test]
+pub fn test_ack_tracker() {
+    let start = Instant::now();
+    let mut tracker = AckTracker::default();
+    tracker.sent(CommandIds::TakeoffCmd, 7, &[1], start);
+    tracker.sent(CommandIds::LandCmd, 8, &[2], start);
+    // not acknowledged by the drone, or not matchable
+    tracker.sent(CommandIds::StickCmd, 9, &[3], start);
+    tracker.sent(CommandIds::FlipCmd, 0, &[4], start);
+    tracker.sent(CommandIds::ExposureCmd, 10, &[5], start);
+    assert_eq!(tracker.pending().len(), 2);
+
+    assert_eq!(tracker.received(CommandIds::TakeoffCmd, 6), AckMatch::Other);
+    assert_eq!(tracker.received(CommandIds::TakeoffCmd, 7), AckMatch::Ack);
+    assert_eq!(
+        tracker.received(CommandIds::TakeoffCmd, 7),
+        AckMatch::Duplicate
+    );
+    assert_eq!(tracker.received(CommandIds::FlightMsg, 7), AckMatch::Other);
+    assert_eq!(tracker.pending()[0].cmd, CommandIds::LandCmd);
+
+    let later = start + DEFAULT_ACK_TIMEOUT;
+    assert_eq!(
+        tracker.expire(later - Duration::from_millis(1)),
+        (vec![], vec![])
+    );
+    assert_eq!(
+        tracker.expire(later),
+        (vec![], vec![(CommandIds::LandCmd, 8)])
+    );
+    assert!(tracker.pending().is_empty());
+}
+
+#[test]
+pub fn test_ack_tracker_retries() {
+    let start = Instant::now();
+    let timeout = DEFAULT_ACK_TIMEOUT;
+    let mut tracker = AckTracker {
+        retries: 2,
+        ..AckTracker::default()
+    };
+    tracker.sent(CommandIds::LandCmd, 8, &[2], start);
+    // a flip or a picture is not send again
+    tracker.sent(CommandIds::TakePictureCommand, 9, &[3], start);
+
+    assert_eq!(
+        tracker.expire(start + timeout),
+        (vec![vec![2]], vec![(CommandIds::TakePictureCommand, 9)])
+    );
+    assert_eq!(tracker.expire(start + timeout * 2), (vec![vec![2]], vec![]));
+    assert_eq!(tracker.pending()[0].retries, 2);
+    // a late ack of the first send still matches
+    assert_eq!(tracker.received(CommandIds::LandCmd, 8), AckMatch::Ack);
+    assert_eq!(tracker.expire(start + timeout * 3), (vec![], vec![]));
+
+    tracker.sent(CommandIds::TakeoffCmd, 10, &[1], start);
+    tracker.expire(start + timeout);
+    tracker.expire(start + timeout * 2);
+    assert_eq!(
+        tracker.expire(start + timeout * 3),
+        (vec![], vec![(CommandIds::TakeoffCmd, 10)])
+    );
+}
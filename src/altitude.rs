@@ -0,0 +1,204 @@
+use crate::{CommandError, CommandMode, CommandModeState};
+use std::time::{Duration, Instant};
+
+#[cfg(not(feature = "tokio_async"))]
+type StateReceiver = std::sync::mpsc::Receiver<CommandModeState>;
+#[cfg(feature = "tokio_async")]
+type StateReceiver = tokio::sync::watch::Receiver<Option<CommandModeState>>;
+
+/// `hold_altitude()` fails, when no state is received within this time
+const STATE_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// shortest interval (s) for the derivative of the height
+const MIN_DERIVATIVE_DT: f32 = 0.02;
+
+/// PID controller for the height, the output is the up/down stick of the `rc` command.
+///
+/// The default gains are a starting point for the Tello indoors, tune them with
+/// `with_gains()`. The derivative is taken from the measured height, so a new target does
+/// not kick the throttle.
+///
+/// # Examples
+/// ```no_run
+/// # use tello::{AltitudeController, CommandError, CommandMode, CommandModeState};
+/// # use std::time::Duration;
+/// # async fn hold(command_mode: &CommandMode, state: &CommandModeState) -> Result<(), CommandError> {
+/// let mut controller = AltitudeController::new(120).with_gains(0.8, 0.1, 0.2);
+/// let up_down = controller.update(state.tof, Duration::from_millis(100));
+/// command_mode.rc(0, 0, up_down, 0).await?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct AltitudeController {
+    target: f32,
+    kp: f32,
+    ki: f32,
+    kd: f32,
+    max_output: f32,
+    integral: f32,
+    last_height: Option<f32>,
+}
+
+impl AltitudeController {
+    /// controller for the target height in cm
+    pub fn new(target_cm: i16) -> Self {
+        Self {
+            target: target_cm as f32,
+            kp: 1.0,
+            ki: 0.1,
+            kd: 0.1,
+            max_output: 60.0,
+            integral: 0.0,
+            last_height: None,
+        }
+    }
+    /// proportional (stick per cm), integral (stick per cm*s) and derivative (stick per cm/s)
+    /// gain
+    pub fn with_gains(mut self, kp: f32, ki: f32, kd: f32) -> Self {
+        self.kp = kp;
+        self.ki = ki;
+        self.kd = kd;
+        self
+    }
+    /// limit of the up/down stick (1 - 100), default 60
+    pub fn with_max_output(mut self, max_output: u8) -> Self {
+        self.max_output = max_output.clamp(1, 100) as f32;
+        self
+    }
+    pub fn target(&self) -> i16 {
+        self.target as i16
+    }
+    /// change the target height in cm. The integral is kept.
+    pub fn set_target(&mut self, target_cm: i16) {
+        self.target = target_cm as f32;
+    }
+    /// forget the integral and the last height, e.g. after a motion command
+    pub fn reset(&mut self) {
+        self.integral = 0.0;
+        self.last_height = None;
+    }
+    /// the up/down stick (-100 - 100) for the measured height in cm, `dt` after the last
+    /// update.
+    pub fn update(&mut self, height_cm: i16, dt: Duration) -> i8 {
+        let height = height_cm as f32;
+        let dt = dt.as_secs_f32();
+        let error = self.target - height;
+
+        let derivative = match self.last_height {
+            // states that arrive in a burst would spike the derivative
+            Some(last) => (height - last) / dt.max(MIN_DERIVATIVE_DT),
+            None => 0.0,
+        };
+        self.last_height = Some(height);
+
+        let integral = self.integral + error * dt;
+        let output = self.kp * error + self.ki * integral - self.kd * derivative;
+        // anti windup: the integral is frozen, while the stick is saturated
+        if output.abs() <= self.max_output || output.signum() != error.signum() {
+            self.integral = integral;
+        }
+        output.clamp(-self.max_output, self.max_output).round() as i8
+    }
+}
+
+/// the height of the next state, measured with the time of flight sensor
+#[cfg(feature = "tokio_async")]
+async fn next_height(state: &mut StateReceiver) -> Option<i16> {
+    tokio::time::timeout(STATE_TIMEOUT, state.changed())
+        .await
+        .ok()?
+        .ok()?;
+    let height = state.borrow().as_ref().map(|s| s.tof);
+    height
+}
+
+/// the height of the latest state, measured with the time of flight sensor
+#[cfg(not(feature = "tokio_async"))]
+async fn next_height(state: &mut StateReceiver) -> Option<i16> {
+    let mut latest = state.recv_timeout(STATE_TIMEOUT).ok()?;
+    // skip the queued states, they are outdated
+    while let Ok(next) = state.try_recv() {
+        latest = next;
+    }
+    Some(latest.tof)
+}
+
+impl CommandMode {
+    /// Hold the target height of the `controller` for the `duration`, with the up/down stick of
+    /// the `rc` command. The height is the `tof` of the `state` (see `state_receiver()`), an
+    /// `rc` command is send for every received state.
+    ///
+    /// The sticks are released at the end. Fails with `CommandError::Timeout`, if no state is
+    /// received for a second.
+    ///
+    /// # Examples
+    /// ```no_run
+    /// # use tello::{AltitudeController, CommandError, CommandMode};
+    /// # use std::time::Duration;
+    /// # async fn hold(mut command_mode: CommandMode) -> Result<(), CommandError> {
+    /// let mut state = command_mode.state_receiver()?.unwrap();
+    /// command_mode.enable().await?;
+    /// command_mode.take_off().await?;
+    /// let mut controller = AltitudeController::new(150);
+    /// command_mode
+    ///     .hold_altitude(&mut state, &mut controller, Duration::from_secs(10))
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn hold_altitude(
+        &self,
+        state: &mut StateReceiver,
+        controller: &mut AltitudeController,
+        duration: Duration,
+    ) -> Result<(), CommandError> {
+        let start = Instant::now();
+        let mut last_update = start;
+        let result = loop {
+            if start.elapsed() >= duration {
+                break Ok(());
+            }
+            let height = match next_height(state).await {
+                Some(height) => height,
+                None => break Err(CommandError::Timeout),
+            };
+            let now = Instant::now();
+            let up_down = controller.update(height, now - last_update);
+            last_update = now;
+            if let Err(e) = self.rc(0, 0, up_down, 0).await {
+                break Err(e);
+            }
+        };
+        self.rc(0, 0, 0, 0).await?;
+        result
+    }
+}
+
+#[test]
+pub fn test_altitude_controller() {
+    let step = Duration::from_millis(100);
+    let mut controller = AltitudeController::new(100).with_gains(1.0, 0.0, 0.0);
+    assert_eq!(controller.update(100, step), 0);
+    assert_eq!(controller.update(80, step), 20);
+    assert_eq!(controller.update(130, step), -30);
+    // limited to the max output
+    assert_eq!(controller.update(0, step), 60);
+    assert_eq!(controller.with_max_output(100).update(0, step), 100);
+
+    // the derivative damps the motion, but not a new target
+    let mut controller = AltitudeController::new(100).with_gains(0.0, 0.0, 0.5);
+    assert_eq!(controller.update(50, step), 0);
+    assert_eq!(controller.update(60, step), -50);
+    controller.set_target(200);
+    assert_eq!(controller.update(60, step), 0);
+
+    // the integral removes the steady state error, but stops while the stick is saturated
+    let mut controller = AltitudeController::new(100).with_gains(0.0, 1.0, 0.0);
+    assert_eq!(controller.update(90, Duration::from_secs(1)), 10);
+    assert_eq!(controller.update(90, Duration::from_secs(1)), 20);
+    assert_eq!(controller.update(0, Duration::from_secs(10)), 60);
+    assert_eq!(controller.update(100, Duration::from_secs(10)), 20);
+    controller.reset();
+    assert_eq!(controller.update(100, Duration::from_secs(1)), 0);
+}
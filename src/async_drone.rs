@@ -0,0 +1,941 @@
+use crate::drone_state::FlightData;
+use crate::odometry::Odometry;
+use crate::video::{frame_queue, set_recv_buffer, FrameSink, VideoFrameStream, VideoStats};
+use crate::{
+    in_sky, keep_received, lock_mut, on_ground, CommandIds, ConnectionState, Drone, DroneError,
+    DroneMeta, Flip, Message, PalmLandState, PendingCommand, RCState, RejectedCommand, Result,
+    UdpCommand, VideoBitrate, VideoMode,
+};
+use socket2::SockRef;
+use std::{
+    collections::{HashMap, VecDeque},
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+    time::{Duration, Instant},
+};
+use tokio::{
+    net::UdpSocket,
+    sync::{mpsc, oneshot},
+    time::{interval, Interval, MissedTickBehavior},
+};
+use tokio_stream::Stream;
+
+/// Async variant of the `Drone`, created with `Drone::into_async()`.
+///
+/// The sockets are `tokio::net::UdpSocket`s and the stick commands and key-frame requests are
+/// send by `next_message()`, so it has to be awaited in a loop (e.g. in `tokio::select!`)
+/// instead of calling `poll()`.
+///
+/// The state of the inner `Drone` is read with the accessors, e.g. `drone_meta()`. The sticks
+/// are changed with `rc_state_mut()` or `set_rc()` and send in the stick interval.
+///
+/// # Example
+///
+/// ```no_run
+/// # use tello::{Drone, DroneError, Message};
+/// # async fn fly() -> Result<(), DroneError> {
+/// let mut drone = Drone::new("192.168.10.1:8889").into_async()?;
+/// drone.connect(11111).await?;
+/// loop {
+///     match drone.next_message().await {
+///         Message::Frame(_, data) => { /* decode */ }
+///         _ => (),
+///     }
+/// }
+/// # }
+/// ```
+pub struct AsyncDrone {
+    drone: Drone,
+    socket: UdpSocket,
+    video_socket: Option<UdpSocket>,
+    sticks: Interval,
+    stick_interval: Duration,
+    keyframes: Interval,
+    keyframe_period: Duration,
+    /// receives the frames instead of `next_message()`, see `video_frames()`
+    frame_sink: Option<FrameSink>,
+    /// commands of the `DroneHandle`s, see `spawn()`
+    commands: Option<mpsc::UnboundedReceiver<HandleCommand>>,
+}
+
+/// a command of a `DroneHandle` and the channel for its result
+type HandleCommand = (
+    Box<dyn FnOnce(&mut Drone) -> Result + Send>,
+    oneshot::Sender<Result>,
+);
+
+fn ticker(period: Duration) -> Interval {
+    let mut ticker = interval(period);
+    ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    ticker
+}
+
+fn into_tokio(socket: std::net::UdpSocket) -> std::result::Result<UdpSocket, DroneError> {
+    socket
+        .set_nonblocking(true)
+        .map_err(|_| DroneError::Network)?;
+    UdpSocket::from_std(socket).map_err(|_| DroneError::Network)
+}
+
+/// receive the next command of the handles, or never if the drone is not spawned
+async fn recv_command(
+    commands: &mut Option<mpsc::UnboundedReceiver<HandleCommand>>,
+) -> Option<HandleCommand> {
+    match commands {
+        Some(commands) => commands.recv().await,
+        None => std::future::pending().await,
+    }
+}
+
+/// receive from the video socket, or never if there is none
+async fn recv_video(socket: &Option<UdpSocket>, buf: &mut [u8]) -> std::io::Result<usize> {
+    match socket {
+        Some(socket) => socket.recv(buf).await,
+        None => std::future::pending().await,
+    }
+}
+
+impl AsyncDrone {
+    /// has to be called in a tokio runtime
+    pub(crate) fn new(drone: Drone) -> std::result::Result<AsyncDrone, DroneError> {
+        let socket = drone.socket.try_clone().map_err(|_| DroneError::Network)?;
+        let mut drone = drone;
+        let video_socket = drone.video_socket.take().map(into_tokio).transpose()?;
+        *lock_mut(&mut drone.outbox) = Some(Vec::new());
+
+        let stick_interval = drone.stick_interval;
+        let keyframe_period = drone.keyframe_check_period();
+        Ok(AsyncDrone {
+            socket: into_tokio(socket)?,
+            video_socket,
+            sticks: ticker(stick_interval),
+            stick_interval,
+            keyframes: ticker(keyframe_period),
+            keyframe_period,
+            frame_sink: None,
+            commands: None,
+            drone,
+        })
+    }
+
+    /// send the packages, collected by the inner `Drone`
+    async fn flush(&mut self) -> Result {
+        let packages = lock_mut(&mut self.drone.outbox)
+            .as_mut()
+            .map(std::mem::take)
+            .unwrap_or_default();
+        for data in packages {
+            self.socket
+                .send(&data)
+                .await
+                .map_err(|_| DroneError::Network)?;
+        }
+        Ok(())
+    }
+
+    /// run a command of the inner `Drone` and send it
+    async fn command(&mut self, command: impl FnOnce(&mut Drone) -> Result) -> Result {
+        command(&mut self.drone)?;
+        self.flush().await
+    }
+
+    /// take over the video socket, bound by `Drone::connect()`
+    fn adopt_video_socket(&mut self) -> Result {
+        if let Some(socket) = self.drone.video_socket.take() {
+            self.video_socket = Some(into_tokio(socket)?);
+        }
+        Ok(())
+    }
+
+    /// drop the video packets, received while the video was stopped
+    fn drop_pending_video(&mut self) {
+        if let Some(socket) = &self.video_socket {
+            let mut read_buf = [0; 1460];
+            while socket.try_recv(&mut read_buf).is_ok() {}
+        }
+    }
+
+    /// apply interval changes of the inner `Drone`
+    fn update_tickers(&mut self) {
+        if self.stick_interval != self.drone.stick_interval {
+            self.stick_interval = self.drone.stick_interval;
+            self.sticks = ticker(self.stick_interval);
+        }
+        if self.keyframe_period != self.drone.keyframe_check_period() {
+            self.keyframe_period = self.drone.keyframe_check_period();
+            self.keyframes = ticker(self.keyframe_period);
+        }
+    }
+
+    /// see `Drone::try_connect()`. Binds the video socket on `video_port`.
+    pub async fn connect(&mut self, video_port: u16) -> Result {
+        let connected = self.drone.try_connect(video_port);
+        // the video start of a failed connect is send anyway
+        self.flush().await?;
+        connected.map_err(|_| DroneError::Network)?;
+        self.adopt_video_socket()
+    }
+
+    /// see `Drone::set_video_recv_buffer()`, applied to the tokio video socket
+    pub fn set_video_recv_buffer(&mut self, bytes: usize) -> std::io::Result<Option<usize>> {
+        self.drone.video.recv_buffer = bytes;
+        if let Some(socket) = &self.video_socket {
+            self.drone.video.recv_buffer_size =
+                Some(set_recv_buffer(SockRef::from(socket), bytes)?);
+        }
+        Ok(self.drone.video.recv_buffer_size)
+    }
+
+    /// wait for the next message of the drone, like `Drone::poll()`.
+    ///
+    /// While waiting, the stick commands and key-frame requests are send in their intervals
+    /// and the log and date time requests are replied.
+    pub async fn next_message(&mut self) -> Message {
+        loop {
+            if let Some(msg) = self.next_event().await {
+                return msg;
+            }
+        }
+    }
+
+    /// the next message, or `None` once all `DroneHandle`s are dropped. The commands of the
+    /// handles are run while waiting.
+    async fn next_event(&mut self) -> Option<Message> {
+        let mut read_buf = [0; 1440];
+        let mut video_buf = [0; 1460];
+        loop {
+            if let Some(msg) = self.drone.pending.pop_front() {
+                return Some(msg);
+            }
+            self.update_tickers();
+            let _ = self.flush().await;
+
+            let video_enabled = self.drone.video.enabled;
+            tokio::select! {
+                received = self.socket.recv(&mut read_buf) => {
+                    // errors are ignored, like in `tick_receive()`
+                    if let Ok(received) = received {
+                        let msg = self.drone.handle_packet(read_buf[..received].to_vec());
+                        self.drone.reply_date_time();
+                        if let Some(msg) = msg {
+                            let _ = self.flush().await;
+                            return Some(msg);
+                        }
+                    }
+                }
+                // read while the video is stopped as well, the packets are dropped
+                received = recv_video(&self.video_socket, &mut video_buf) => {
+                    if let Ok(received) = received {
+                        match (&self.frame_sink, self.drone.handle_video_packet(&video_buf[..received])) {
+                            (Some(sink), Some(Message::Frame(_, data))) => {
+                                sink.push(self.drone.video.assembler.extended_frame_id(), data)
+                            }
+                            (Some(sink), Some(Message::FrameTs { id, data, .. })) => sink.push(id, data),
+                            (_, Some(msg)) => return Some(msg),
+                            (_, None) => (),
+                        }
+                    }
+                }
+                command = recv_command(&mut self.commands) => match command {
+                    Some((command, reply)) => {
+                        let result = command(&mut self.drone);
+                        // e.g. `connect()` binds a new video socket
+                        let adopted = self.adopt_video_socket();
+                        let flushed = self.flush().await;
+                        let _ = reply.send(result.and(adopted).and(flushed));
+                    }
+                    None => {
+                        self.commands = None;
+                        return None;
+                    }
+                },
+                _ = self.sticks.tick() => {
+                    let now = Instant::now();
+                    self.drone.send_rc_state(now);
+                    self.drone.tick_acks(now);
+                    self.drone.tick_connection(now);
+                    if self.drone.auto_reconnect {
+                        self.drone.ensure_connected();
+                    }
+                }
+                _ = self.keyframes.tick(), if video_enabled => {
+                    self.drone.tick_key_frame(Instant::now());
+                }
+            }
+        }
+    }
+
+    /// Stream of the video frames `(frame_id, data)`. At most `capacity` frames are buffered,
+    /// the oldest frame is dropped when the consumer is slow.
+    ///
+    /// The frames are still received by `next_message()`, but not returned by it anymore.
+    /// So `next_message()` has to be awaited in another task or in a `tokio::select!`. A
+    /// second call replaces the stream, the previous one ends.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use tello::{AsyncDrone, DroneError};
+    /// # use tokio_stream::StreamExt;
+    /// # async fn fly(mut drone: AsyncDrone) -> Result<(), DroneError> {
+    /// let mut frames = drone.video_frames(8);
+    /// drone.start_video().await?;
+    /// tokio::spawn(async move {
+    ///     loop {
+    ///         drone.next_message().await;
+    ///     }
+    /// });
+    /// while let Some((frame_id, data)) = frames.next().await {
+    ///     // decode
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn video_frames(&mut self, capacity: usize) -> VideoFrameStream {
+        let (sink, queue) = frame_queue(capacity);
+        self.frame_sink = Some(sink);
+        VideoFrameStream::new(queue)
+    }
+
+    /// Run the drone in its own tokio task, instead of awaiting `next_message()` in a loop.
+    ///
+    /// Returns a `DroneHandle` to send the commands, the received messages and the stream of
+    /// the video frames (see `video_frames()`). At most `capacity` messages and frames are
+    /// buffered. A slow consumer loses the newest messages and the oldest frames, so the stick
+    /// commands keep their interval. The task ends, when all handles are dropped.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use tello::{Drone, DroneError};
+    /// # async fn fly() -> Result<(), DroneError> {
+    /// let (drone, mut messages, mut frames) = Drone::new("192.168.10.1:8889")
+    ///     .into_async()?
+    ///     .spawn(8);
+    /// drone.connect(11111).await?;
+    /// drone.take_off().await?;
+    /// while let Some(msg) = messages.recv().await {
+    ///     println!("{:?}", msg);
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn spawn(
+        mut self,
+        capacity: usize,
+    ) -> (DroneHandle, mpsc::Receiver<Message>, VideoFrameStream) {
+        let (commands, receiver) = mpsc::unbounded_channel();
+        self.commands = Some(receiver);
+        let frames = self.video_frames(capacity);
+        let (messages, receiver) = mpsc::channel(capacity.max(1));
+        tokio::spawn(async move {
+            while let Some(msg) = self.next_event().await {
+                let _ = messages.try_send(msg);
+            }
+        });
+        (DroneHandle { commands }, receiver, frames)
+    }
+
+    /// stream of the messages, see `next_message()`
+    pub fn into_stream(self) -> MessageStream {
+        MessageStream {
+            next: next_owned(self),
+        }
+    }
+
+    pub async fn send(&mut self, command: UdpCommand) -> Result {
+        self.command(|d| d.send(command)).await
+    }
+    pub async fn take_off(&mut self) -> Result {
+        self.command(|d| d.take_off()).await
+    }
+    pub async fn throw_and_go(&mut self) -> Result {
+        self.command(|d| d.throw_and_go()).await
+    }
+    /// see `Drone::emergency()`, **the drone falls down**
+    pub async fn emergency(&mut self) -> Result {
+        self.command(|d| d.emergency()).await
+    }
+    pub async fn land(&mut self) -> Result {
+        self.command(|d| d.land()).await
+    }
+    pub async fn stop_land(&mut self) -> Result {
+        self.command(|d| d.stop_land()).await
+    }
+    /// see `Drone::take_off_and_wait()`, the messages received while waiting are returned by
+    /// the next `next_message()` calls
+    pub async fn take_off_and_wait(&mut self, timeout: Duration) -> Result {
+        self.drone.check_take_off()?;
+        let since = self.drone.status_counter;
+        self.take_off().await?;
+        self.wait_for_flight(since, timeout, in_sky).await
+    }
+    /// see `Drone::land_and_wait()`
+    pub async fn land_and_wait(&mut self, timeout: Duration) -> Result {
+        let since = self.drone.status_counter;
+        self.land().await?;
+        self.wait_for_flight(since, timeout, on_ground).await
+    }
+    async fn wait_for_flight(
+        &mut self,
+        since: u32,
+        timeout: Duration,
+        reached: fn(&FlightData) -> bool,
+    ) -> Result {
+        let deadline = tokio::time::Instant::now() + timeout;
+        let mut received = VecDeque::new();
+        let result = loop {
+            if self.drone.flight_reached(since, reached) {
+                break Ok(());
+            }
+            match tokio::time::timeout_at(deadline, self.next_message()).await {
+                Ok(msg) => keep_received(&mut received, msg),
+                Err(_) => break Err(DroneError::Timeout),
+            }
+        };
+        received.append(&mut self.drone.pending);
+        self.drone.pending = received;
+        result
+    }
+    /// see `Drone::hover()`
+    pub async fn hover(&mut self) -> Result {
+        self.command(|d| d.hover()).await
+    }
+    pub async fn palm_land(&mut self) -> Result {
+        self.command(|d| d.palm_land()).await
+    }
+    pub async fn flip(&mut self, direction: Flip) -> Result {
+        self.command(|d| d.flip(direction)).await
+    }
+    pub async fn bounce(&mut self) -> Result {
+        self.command(|d| d.bounce()).await
+    }
+    pub async fn bounce_stop(&mut self) -> Result {
+        self.command(|d| d.bounce_stop()).await
+    }
+    pub async fn take_picture(&mut self) -> Result {
+        self.command(|d| d.take_picture()).await
+    }
+    pub async fn start_video(&mut self) -> Result {
+        if !self.drone.video.enabled {
+            self.drop_pending_video();
+        }
+        self.command(|d| d.start_video()).await
+    }
+    /// see `Drone::stop_video()`
+    pub async fn stop_video(&mut self) -> Result {
+        self.command(|d| {
+            d.stop_video();
+            Ok(())
+        })
+        .await
+    }
+    pub async fn set_video_mode(&mut self, mode: VideoMode) -> Result {
+        self.command(|d| d.set_video_mode(mode)).await
+    }
+    pub async fn set_video_bitrate(&mut self, rate: u8) -> Result {
+        self.command(|d| d.set_video_bitrate(rate)).await
+    }
+    pub async fn set_exposure(&mut self, level: u8) -> Result {
+        self.command(|d| d.set_exposure(level)).await
+    }
+    pub async fn set_alt_limit(&mut self, limit: u8) -> Result {
+        self.command(|d| d.set_alt_limit(limit)).await
+    }
+    pub async fn set_att_angle(&mut self, degrees: f32) -> Result {
+        self.command(|d| d.set_att_angle(degrees)).await
+    }
+    pub async fn set_battery_threshold(&mut self, threshold: u8) -> Result {
+        self.command(|d| d.set_battery_threshold(threshold)).await
+    }
+    /// see `Drone::set_video_bitrate_preset()`
+    pub async fn set_video_bitrate_preset(&mut self, bitrate: VideoBitrate) -> Result {
+        self.command(|d| d.set_video_bitrate_preset(bitrate)).await
+    }
+    pub async fn request_key_frame(&mut self) -> Result {
+        self.command(|d| d.request_key_frame()).await
+    }
+    /// see `Drone::set_rc()`
+    pub async fn set_rc(
+        &mut self,
+        left_right: f32,
+        forward_back: f32,
+        up_down: f32,
+        turn: f32,
+    ) -> Result {
+        self.command(|d| d.set_rc(left_right, forward_back, up_down, turn))
+            .await
+    }
+    pub async fn get_version(&mut self) -> Result {
+        self.command(|d| d.get_version()).await
+    }
+    pub async fn get_alt_limit(&mut self) -> Result {
+        self.command(|d| d.get_alt_limit()).await
+    }
+    pub async fn get_att_angle(&mut self) -> Result {
+        self.command(|d| d.get_att_angle()).await
+    }
+    pub async fn get_battery_threshold(&mut self) -> Result {
+        self.command(|d| d.get_battery_threshold()).await
+    }
+    pub async fn get_region(&mut self) -> Result {
+        self.command(|d| d.get_region()).await
+    }
+}
+
+/// Read-only state and the settings of the inner `Drone`
+impl AsyncDrone {
+    /// current meta data from the drone, see `Drone::drone_meta`
+    pub fn drone_meta(&self) -> &DroneMeta {
+        &self.drone.drone_meta
+    }
+    /// see `Drone::odometry()`
+    pub fn odometry(&self) -> &Odometry {
+        self.drone.odometry()
+    }
+    /// see `Drone::reset_odometry()`
+    pub fn reset_odometry(&mut self) {
+        self.drone.reset_odometry()
+    }
+    /// the sticks, send in the stick interval
+    pub fn rc_state(&self) -> &RCState {
+        &self.drone.rc_state
+    }
+    /// change the sticks, e.g. `drone.rc_state_mut().go_up()`. They are send with the next
+    /// stick interval, `set_rc()` sends them immediately with `set_immediate_stick(true)`.
+    pub fn rc_state_mut(&mut self) -> &mut RCState {
+        &mut self.drone.rc_state
+    }
+    /// see `Drone::connection_state()`
+    pub fn connection_state(&self) -> ConnectionState {
+        self.drone.connection_state()
+    }
+    /// see `Drone::video_stats()`
+    pub fn video_stats(&self) -> VideoStats {
+        self.drone.video_stats()
+    }
+    /// see `Drone::link_quality()`
+    pub fn link_quality(&self) -> Option<u8> {
+        self.drone.link_quality()
+    }
+    /// see `Drone::palm_land_state()`
+    pub fn palm_land_state(&self) -> PalmLandState {
+        self.drone.palm_land_state()
+    }
+    /// see `Drone::pending_commands()`
+    pub fn pending_commands(&self) -> Vec<PendingCommand> {
+        self.drone.pending_commands()
+    }
+    /// see `Drone::last_rejected_command()`
+    pub fn last_rejected_command(&self) -> Option<RejectedCommand> {
+        self.drone.last_rejected_command()
+    }
+    /// see `Drone::packet_counts()`
+    pub fn packet_counts(&self) -> &HashMap<CommandIds, u64> {
+        self.drone.packet_counts()
+    }
+
+    /// see `Drone::set_stick_interval()`, the stick timer is restarted with the interval
+    pub fn set_stick_interval(&mut self, interval: Duration) -> Result {
+        self.drone.set_stick_interval(interval)?;
+        self.update_tickers();
+        Ok(())
+    }
+    /// see `Drone::set_stick_coalescing()`
+    pub fn set_stick_coalescing(&mut self, enabled: bool) {
+        self.drone.set_stick_coalescing(enabled)
+    }
+    /// see `Drone::set_immediate_stick()`
+    pub fn set_immediate_stick(&mut self, enabled: bool) {
+        self.drone.set_immediate_stick(enabled)
+    }
+    /// see `Drone::set_keyframe_interval()`
+    pub fn set_keyframe_interval(&mut self, interval: Option<Duration>) -> Result {
+        self.drone.set_keyframe_interval(interval)?;
+        self.update_tickers();
+        Ok(())
+    }
+    /// see `Drone::set_keyframe_window()`
+    pub fn set_keyframe_window(&mut self, window: Duration) -> Result {
+        self.drone.set_keyframe_window(window)?;
+        self.update_tickers();
+        Ok(())
+    }
+    /// see `Drone::set_frame_timestamps()`
+    pub fn set_frame_timestamps(&mut self, enabled: bool) {
+        self.drone.set_frame_timestamps(enabled)
+    }
+    /// see `Drone::set_ack_timeout()`
+    pub fn set_ack_timeout(&mut self, timeout: Duration) {
+        self.drone.set_ack_timeout(timeout)
+    }
+    /// see `Drone::set_ack_retries()`
+    pub fn set_ack_retries(&mut self, retries: u8) {
+        self.drone.set_ack_retries(retries)
+    }
+    /// see `Drone::set_reconnect_timeout()`
+    pub fn set_reconnect_timeout(&mut self, timeout: Duration) {
+        self.drone.set_reconnect_timeout(timeout)
+    }
+    /// see `Drone::set_auto_reconnect()`
+    pub fn set_auto_reconnect(&mut self, enabled: bool) {
+        self.drone.set_auto_reconnect(enabled)
+    }
+}
+
+/// Sends the commands to an `AsyncDrone`, that runs in its own task (see
+/// `AsyncDrone::spawn()`).
+///
+/// The handle can be cloned and moved to other tasks. The commands fail with
+/// `DroneError::Network`, when the task has ended.
+#[derive(Debug, Clone)]
+pub struct DroneHandle {
+    commands: mpsc::UnboundedSender<HandleCommand>,
+}
+
+impl DroneHandle {
+    /// run `command` on the `Drone` of the task and send the packages
+    async fn run(&self, command: impl FnOnce(&mut Drone) -> Result + Send + 'static) -> Result {
+        let (reply, result) = oneshot::channel();
+        self.commands
+            .send((Box::new(command), reply))
+            .map_err(|_| DroneError::Network)?;
+        result.await.map_err(|_| DroneError::Network)?
+    }
+
+    /// run `query` on the `Drone` of the task and return its value
+    async fn query<T: Send + 'static>(
+        &self,
+        query: impl FnOnce(&mut Drone) -> std::result::Result<T, DroneError> + Send + 'static,
+    ) -> std::result::Result<T, DroneError> {
+        let (reply, value) = oneshot::channel();
+        self.run(move |d| {
+            let _ = reply.send(query(d)?);
+            Ok(())
+        })
+        .await?;
+        value.await.map_err(|_| DroneError::Network)
+    }
+
+    /// see `Drone::try_connect()`
+    pub async fn connect(&self, video_port: u16) -> Result {
+        self.run(move |d| {
+            d.try_connect(video_port)
+                .map(|_| ())
+                .map_err(|_| DroneError::Network)
+        })
+        .await
+    }
+    pub async fn take_off(&self) -> Result {
+        self.run(|d| d.take_off()).await
+    }
+    /// see `Drone::throw_and_go()`
+    pub async fn throw_and_go(&self) -> Result {
+        self.run(|d| d.throw_and_go()).await
+    }
+    pub async fn land(&self) -> Result {
+        self.run(|d| d.land()).await
+    }
+    pub async fn stop_land(&self) -> Result {
+        self.run(|d| d.stop_land()).await
+    }
+    /// See `Drone::take_off_and_wait()`. The flight data of the task is checked every 20ms,
+    /// the messages are still send to the receiver of `spawn()`.
+    pub async fn take_off_and_wait(&self, timeout: Duration) -> Result {
+        let since = self
+            .query(|d| {
+                d.check_take_off()?;
+                d.take_off()?;
+                Ok(d.status_counter)
+            })
+            .await?;
+        self.wait_for_flight(since, timeout, in_sky).await
+    }
+    /// see `Drone::land_and_wait()` and `take_off_and_wait()`
+    pub async fn land_and_wait(&self, timeout: Duration) -> Result {
+        let since = self
+            .query(|d| {
+                d.land()?;
+                Ok(d.status_counter)
+            })
+            .await?;
+        self.wait_for_flight(since, timeout, on_ground).await
+    }
+    async fn wait_for_flight(
+        &self,
+        since: u32,
+        timeout: Duration,
+        reached: fn(&FlightData) -> bool,
+    ) -> Result {
+        let start = Instant::now();
+        while !self
+            .query(move |d| Ok(d.flight_reached(since, reached)))
+            .await?
+        {
+            if start.elapsed() >= timeout {
+                return Err(DroneError::Timeout);
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        Ok(())
+    }
+    /// see `Drone::emergency()`, **the drone falls down**
+    pub async fn emergency(&self) -> Result {
+        self.run(|d| d.emergency()).await
+    }
+    /// see `Drone::hover()`
+    pub async fn hover(&self) -> Result {
+        self.run(|d| d.hover()).await
+    }
+    /// Set the axes like `RCState::set_axes()`, they are send with the next stick command of
+    /// the task.
+    pub async fn set_rc(
+        &self,
+        left_right: f32,
+        forward_back: f32,
+        up_down: f32,
+        turn: f32,
+    ) -> Result {
+        self.run(move |d| {
+            d.rc_state.set_axes(left_right, forward_back, up_down, turn);
+            Ok(())
+        })
+        .await
+    }
+    /// see `RCState::set_sport_mode()`
+    pub async fn set_sport_mode(&self, enabled: bool) -> Result {
+        self.run(move |d| {
+            d.rc_state.set_sport_mode(enabled);
+            Ok(())
+        })
+        .await
+    }
+    pub async fn palm_land(&self) -> Result {
+        self.run(|d| d.palm_land()).await
+    }
+    pub async fn flip(&self, direction: Flip) -> Result {
+        self.run(move |d| d.flip(direction)).await
+    }
+    pub async fn bounce(&self) -> Result {
+        self.run(|d| d.bounce()).await
+    }
+    pub async fn bounce_stop(&self) -> Result {
+        self.run(|d| d.bounce_stop()).await
+    }
+    pub async fn take_picture(&self) -> Result {
+        self.run(|d| d.take_picture()).await
+    }
+    pub async fn start_video(&self) -> Result {
+        self.run(|d| d.start_video()).await
+    }
+    pub async fn set_alt_limit(&self, limit: u8) -> Result {
+        self.run(move |d| d.set_alt_limit(limit)).await
+    }
+    /// see `Drone::set_att_angle()`
+    pub async fn set_att_angle(&self, degrees: f32) -> Result {
+        self.run(move |d| d.set_att_angle(degrees)).await
+    }
+    pub async fn set_battery_threshold(&self, threshold: u8) -> Result {
+        self.run(move |d| d.set_battery_threshold(threshold)).await
+    }
+    pub async fn set_video_mode(&self, mode: VideoMode) -> Result {
+        self.run(move |d| d.set_video_mode(mode)).await
+    }
+    pub async fn set_video_bitrate(&self, rate: u8) -> Result {
+        self.run(move |d| d.set_video_bitrate(rate)).await
+    }
+    /// see `Drone::set_video_bitrate_preset()`
+    pub async fn set_video_bitrate_preset(&self, bitrate: VideoBitrate) -> Result {
+        self.run(move |d| d.set_video_bitrate_preset(bitrate)).await
+    }
+    pub async fn set_exposure(&self, level: u8) -> Result {
+        self.run(move |d| d.set_exposure(level)).await
+    }
+    pub async fn request_key_frame(&self) -> Result {
+        self.run(|d| d.request_key_frame()).await
+    }
+    pub async fn get_version(&self) -> Result {
+        self.run(|d| d.get_version()).await
+    }
+    pub async fn get_alt_limit(&self) -> Result {
+        self.run(|d| d.get_alt_limit()).await
+    }
+    pub async fn get_att_angle(&self) -> Result {
+        self.run(|d| d.get_att_angle()).await
+    }
+    pub async fn get_battery_threshold(&self) -> Result {
+        self.run(|d| d.get_battery_threshold()).await
+    }
+    pub async fn get_region(&self) -> Result {
+        self.run(|d| d.get_region()).await
+    }
+    /// see `Drone::stop_video()`
+    pub async fn stop_video(&self) -> Result {
+        self.run(|d| {
+            d.stop_video();
+            Ok(())
+        })
+        .await
+    }
+}
+
+type NextMessage = Pin<Box<dyn Future<Output = (Message, AsyncDrone)> + Send>>;
+
+fn next_owned(mut drone: AsyncDrone) -> NextMessage {
+    Box::pin(async move {
+        let msg = drone.next_message().await;
+        (msg, drone)
+    })
+}
+
+/// `Stream` of the messages of an `AsyncDrone`, see `AsyncDrone::into_stream()`
+pub struct MessageStream {
+    next: NextMessage,
+}
+
+impl Stream for MessageStream {
+    type Item = Message;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Message>> {
+        match self.next.as_mut().poll(cx) {
+            Poll::Ready((msg, drone)) => {
+                self.next = next_owned(drone);
+                Poll::Ready(Some(msg))
+            }
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+#[cfg(test)]
+use crate::{free_port, PackageTypes, DRONE_PORT};
+
+// the guard only serializes the tests on the port 8889, each test has its own runtime
+#[allow(clippy::await_holding_lock)]
+#[tokio::test]
+pub async fn test_async_drone() {
+    let _port = DRONE_PORT.lock().unwrap_or_else(|e| e.into_inner());
+    let fake_drone = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+    fake_drone
+        .set_read_timeout(Some(Duration::from_millis(500)))
+        .unwrap();
+    let mut drone = Drone::new(&fake_drone.local_addr().unwrap().to_string())
+        .into_async()
+        .unwrap();
+    drone.set_stick_interval(Duration::from_millis(20)).unwrap();
+
+    drone.take_off().await.unwrap();
+    let mut buf = [0; 1440];
+    let received = fake_drone.recv(&mut buf).unwrap();
+    assert_eq!(buf[5], u16::from(CommandIds::TakeoffCmd) as u8);
+    assert_eq!(received, 11);
+
+    // a taken video port is an error, not a panic
+    let taken = std::net::UdpSocket::bind("0.0.0.0:0").unwrap();
+    let video_port = taken.local_addr().unwrap().port();
+    assert_eq!(drone.connect(video_port).await, Err(DroneError::Network));
+    fake_drone.recv(&mut buf).unwrap();
+    assert_eq!(buf[5], u16::from(CommandIds::VideoStartCmd) as u8);
+    assert!(matches!(
+        drone.next_message().await,
+        Message::ConnectionChanged(ConnectionState::Connecting)
+    ));
+
+    let mut packet = UdpCommand::new(CommandIds::WifiMsg, PackageTypes::X50);
+    packet.write(&[90, 0]);
+    fake_drone
+        .send_to(&Into::<Vec<u8>>::into(packet), "127.0.0.1:8889")
+        .unwrap();
+    match drone.next_message().await {
+        Message::Data(package) => assert_eq!(package.cmd, CommandIds::WifiMsg),
+        msg => panic!("unexpected {:?}", msg),
+    }
+    assert_eq!(drone.drone_meta().get_wifi_info().unwrap().strength(), 90);
+    assert_eq!(drone.packet_counts()[&CommandIds::WifiMsg], 1);
+    drone.rc_state_mut().go_up();
+    assert_eq!(drone.rc_state_mut().get_stick_parameter().0, 1.0);
+
+    // the stick commands are send while waiting for a message
+    let mut stream = drone.into_stream();
+    let waiting = tokio::time::timeout(Duration::from_millis(100), async {
+        use tokio_stream::StreamExt;
+        stream.next().await
+    });
+    assert!(waiting.await.is_err());
+    let received = fake_drone.recv(&mut buf).unwrap();
+    assert_eq!(buf[5], u16::from(CommandIds::StickCmd) as u8);
+    assert_eq!(received, 22);
+}
+
+#[allow(clippy::await_holding_lock)]
+#[tokio::test]
+pub async fn test_spawned_drone() {
+    let _port = DRONE_PORT.lock().unwrap_or_else(|e| e.into_inner());
+    let fake_drone = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+    fake_drone
+        .set_read_timeout(Some(Duration::from_millis(500)))
+        .unwrap();
+    let (drone, mut messages, mut frames) =
+        Drone::new(&fake_drone.local_addr().unwrap().to_string())
+            .into_async()
+            .unwrap()
+            .spawn(4);
+
+    drone.take_off().await.unwrap();
+    let mut buf = [0; 1440];
+    fake_drone.recv(&mut buf).unwrap();
+    assert_eq!(buf[5], u16::from(CommandIds::TakeoffCmd) as u8);
+
+    let mut packet = UdpCommand::new(CommandIds::WifiMsg, PackageTypes::X50);
+    packet.write(&[90, 0]);
+    fake_drone
+        .send_to(&Into::<Vec<u8>>::into(packet), "127.0.0.1:8889")
+        .unwrap();
+    let timeout = Duration::from_secs(1);
+    match tokio::time::timeout(timeout, messages.recv()).await {
+        Ok(Some(Message::Data(package))) => assert_eq!(package.cmd, CommandIds::WifiMsg),
+        msg => panic!("unexpected {:?}", msg),
+    }
+
+    // the settings and queries are send by the task
+    drone.set_exposure(1).await.unwrap();
+    fake_drone.recv(&mut buf).unwrap();
+    assert_eq!(buf[5], u16::from(CommandIds::ExposureCmd) as u8);
+    drone.get_version().await.unwrap();
+    fake_drone.recv(&mut buf).unwrap();
+    assert_eq!(buf[5], u16::from(CommandIds::VersionMsg) as u8);
+
+    // a taken video port is an error, not a panic of the task
+    let taken = std::net::UdpSocket::bind("0.0.0.0:0").unwrap();
+    let video_port = taken.local_addr().unwrap().port();
+    assert_eq!(drone.connect(video_port).await, Err(DroneError::Network));
+    fake_drone.recv(&mut buf).unwrap();
+    assert_eq!(buf[5], u16::from(CommandIds::VideoStartCmd) as u8);
+
+    // the video socket of the connect is used by the task
+    let video_port = free_port();
+    drone.connect(video_port).await.unwrap();
+    let video = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+    for packet in &[[1u8, 0, 5, 6], [1, 128, 7, 8]] {
+        video.send_to(packet, ("127.0.0.1", video_port)).unwrap();
+    }
+    let frame = tokio::time::timeout(timeout, async {
+        use tokio_stream::StreamExt;
+        frames.next().await
+    });
+    assert_eq!(frame.await.unwrap(), Some((1, vec![5, 6, 7, 8].into())));
+
+    // the task ends with the last handle
+    let second = drone.clone();
+    drop(drone);
+    assert_eq!(second.land().await, Ok(()));
+    drop(second);
+    loop {
+        match tokio::time::timeout(timeout, messages.recv()).await {
+            Ok(Some(_)) => (),
+            Ok(None) => break,
+            Err(_) => panic!("the task is still running"),
+        }
+    }
+}
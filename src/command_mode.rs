@@ -1,14 +1,18 @@
-use std::{convert::TryFrom, net::SocketAddr, string::FromUtf8Error, time::Duration};
+use std::{
+    collections::HashMap,
+    convert::TryFrom,
+    net::SocketAddr,
+    string::FromUtf8Error,
+    time::{Duration, Instant},
+};
 
 #[cfg(not(feature = "tokio_async"))]
 use std::net::UdpSocket;
 
-#[cfg(not(feature = "tokio_async"))]
-use std::time::Instant;
 #[cfg(feature = "tokio_async")]
 use tokio::net::UdpSocket;
 #[cfg(feature = "tokio_async")]
-use tokio::time::{sleep, timeout};
+use tokio::time::{sleep, timeout_at};
 
 #[cfg(not(feature = "tokio_async"))]
 use std::sync::mpsc;
@@ -20,126 +24,504 @@ type StateReceiver<T> = mpsc::Receiver<T>;
 #[cfg(feature = "tokio_async")]
 type StateReceiver<T> = watch::Receiver<Option<T>>;
 
+#[cfg(not(feature = "tokio_async"))]
+type CommandSocket = UdpSocket;
+#[cfg(feature = "tokio_async")]
+type CommandSocket = std::sync::Arc<UdpSocket>;
+
+/// only one command is waiting for its response, the other commands have to wait in the queue.
+#[cfg(not(feature = "tokio_async"))]
+type CommandQueue = std::sync::Arc<std::sync::Mutex<()>>;
+#[cfg(feature = "tokio_async")]
+type CommandQueue = std::sync::Arc<tokio::sync::Mutex<()>>;
+
+/// thread or task of the state and video receivers
+#[cfg(not(feature = "tokio_async"))]
+type Worker = std::thread::JoinHandle<()>;
+#[cfg(feature = "tokio_async")]
+type Worker = tokio::task::JoinHandle<()>;
+
+use crate::mission::{Mission, MissionStep, StepReport, StepResult};
 use crate::odometry::Odometry;
+#[cfg(not(feature = "tokio_async"))]
+use crate::video::VideoFrameIter;
+#[cfg(feature = "tokio_async")]
+use crate::video::VideoFrameStream;
+use crate::video::{
+    frame_queue, set_recv_buffer, SdkFrameAssembler, VideoChunk, DEFAULT_VIDEO_RECV_BUFFER,
+};
+use crate::Flip;
+use crate::RCState;
+use crate::{lock, lock_mut};
+use socket2::SockRef;
+use std::sync::atomic::{AtomicBool, AtomicU8, Ordering};
+use std::sync::{Arc, Mutex, Weak};
 
 /// Command mode for your tello drone. to leave the command mode, you have to reboot the drone.
 ///
 /// The CommandMode provides following information to you:
 ///
-/// -   `state_receiver(): Option<Receiver<CommandModeState>>`: parsed incoming state packages from the drone. You will take the ownership, you could do this only once.
-/// -   `video_receiver(): Option<Receiver<Vec<u8>>>`: Video frames (h264) from the drone. You will take the ownership, you could do this only once.
-/// -   `odometry: Odometry` odometer data for your movements.
+/// -   `state_receiver(): Result<Option<Receiver<CommandModeState>>, CommandError>`: parsed incoming state packages from the drone. You will take the ownership, you could do this only once.
+/// -   `video_receiver(): Result<Option<Receiver<VideoChunk>>, CommandError>`: Video frames (h264) from the drone. You will take the ownership, you could do this only once.
+/// -   `video_frames(capacity)` (tokio) / `video_frames_iter(capacity)` (sync): the same frames as `Stream` / blocking `Iterator` of `(frame_id, Bytes)`. At most `capacity` frames are buffered, the oldest frame is dropped (`dropped_frames()`) when you are too slow.
+/// -   `odometry: Odometry` odometer data for your movements. Flips don't change the position.
+///     A motion aborted with `stop()` is not added, so the odometry is only approximate after
+///     a stop.
+///     Motions without a confirmation are listed in `odometry.pending_moves()`.
+///
+/// The text protocol has no id to match a response with its command. Therefore only one
+/// command is send at a time, the next command waits in a queue until the response of the
+/// previous one is received or timed out.
+///
+/// The SDK has no command to take a picture. A still can be taken from the video stream, or
+/// with the binary protocol (`Drone::take_picture_to_file()`). The drone can't switch back
+/// from the command mode without a reboot, so a temporary switch is not possible.
 #[derive(Debug)]
 pub struct CommandMode {
     peer_addr: SocketAddr,
-    state_receiver: Option<StateReceiver<CommandModeState>>,
-    video_receiver: Option<mpsc::Receiver<Vec<u8>>>,
+    socket: CommandSocket,
+    queue: CommandQueue,
+    status_port: u16,
+    video_port: u16,
+    /// requested receive buffer of the video socket, see `with_video_recv_buffer()`
+    video_recv_buffer: usize,
+    /// the receivers are created on the first use, `None` after they are taken
+    state_receiver: Option<Option<StateReceiver<CommandModeState>>>,
+    /// `video_on()` binds the video receiver, while the CommandMode is shared
+    video: Mutex<VideoReceiver>,
+    command_timeout: Duration,
+    retries: u8,
+    /// speed of the last `speed()` in cm/s, the slowest speed until it is set
+    speed: AtomicU8,
+    /// set by `stop()`, the running motion is not added to the odometry
+    stopped: Arc<AtomicBool>,
+    /// send time of the `stop` commands, their responses are not returned to the waiting command
+    unanswered: Arc<Mutex<Vec<Instant>>>,
+    /// set by `StopHandle::abort()`, the running mission is aborted
+    aborted: Arc<AtomicBool>,
+    /// stops the receivers, when the CommandMode is dropped
+    shutdown: Arc<AtomicBool>,
+    workers: Mutex<Vec<Worker>>,
+    /// time of the last command, send to the drone
+    last_command: Arc<Mutex<Instant>>,
+    /// stops the running keep-alive loop
+    keepalive: Option<Arc<AtomicBool>>,
+    /// stops the running rc loop
+    rc_loop: Option<Arc<AtomicBool>>,
+    /// last yaw of the state receiver, used by the yaw fusion
+    last_yaw: Arc<Mutex<Option<i16>>>,
+    yaw_fusion: bool,
+    /// the props spin at idle after `motor_on()`
+    motors_idling: AtomicBool,
     pub odometry: Odometry,
 }
-#[derive(Default, Debug, Clone)]
+
+/// video receiver of the CommandMode, see `video_receiver()`
+#[derive(Debug, Default)]
+struct VideoReceiver {
+    /// created on the first use, `None` after it is taken
+    receiver: Option<Option<mpsc::Receiver<VideoChunk>>>,
+    /// effective receive buffer of the bound video socket
+    recv_buffer_size: Option<usize>,
+}
+
+/// Sends the `stop` command, while the `CommandMode` is borrowed by a running motion.
+///
+/// Create it with `CommandMode::stop_handle()` and move it to another task or thread.
+#[derive(Debug)]
+pub struct StopHandle {
+    peer_addr: SocketAddr,
+    socket: CommandSocket,
+    stopped: Arc<AtomicBool>,
+    unanswered: Arc<Mutex<Vec<Instant>>>,
+    aborted: Arc<AtomicBool>,
+}
+
+/// Error of a command in the command mode.
+///
+/// It converts into a `String` to use it with `?` in functions returning `Result<_, String>`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum CommandError {
+    /// no response from the drone within the command timeout (and all retries)
+    Timeout,
+    /// the drone answered with `error ...`
+    Drone(String),
+    /// the drone answered something unexpected
+    UnknownResponse(String),
+    /// the local socket could not be created or used
+    Socket(String),
+    /// the command is not supported in the command mode
+    Unsupported(String),
+    /// the parameter is out of the range, accepted by the drone. It was not send.
+    InvalidParameter(String),
+    /// the firmware of the drone does not know the command (`error Not support`)
+    UnsupportedFirmware(String),
+}
+
+impl std::fmt::Display for CommandError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CommandError::Timeout => write!(f, "timeout"),
+            CommandError::Drone(res) => write!(f, "{}", res),
+            CommandError::UnknownResponse(res) => write!(f, "Unknown response {:?}", res),
+            CommandError::Socket(e) => write!(f, "{}", e),
+            CommandError::Unsupported(cmd) => write!(f, "{} is not supported", cmd),
+            CommandError::InvalidParameter(e) => write!(f, "invalid parameter: {}", e),
+            CommandError::UnsupportedFirmware(cmd) => {
+                write!(f, "{} is not supported by the firmware", cmd)
+            }
+        }
+    }
+}
+
+impl std::error::Error for CommandError {}
+
+impl From<CommandError> for String {
+    fn from(e: CommandError) -> String {
+        e.to_string()
+    }
+}
+
+/// Flip directions of the SDK `flip` command
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FlipDir {
+    Left,
+    Right,
+    Forward,
+    Back,
+}
+
+impl FlipDir {
+    /// the single letter argument of the SDK `flip` command
+    fn sdk_arg(&self) -> &'static str {
+        match self {
+            FlipDir::Left => "l",
+            FlipDir::Right => "r",
+            FlipDir::Forward => "f",
+            FlipDir::Back => "b",
+        }
+    }
+}
+
+impl TryFrom<Flip> for FlipDir {
+    type Error = CommandError;
+    /// map the flip of the binary protocol. The SDK does not support the diagonal flips.
+    fn try_from(flip: Flip) -> Result<Self, CommandError> {
+        match flip {
+            Flip::Left => Ok(FlipDir::Left),
+            Flip::Right => Ok(FlipDir::Right),
+            Flip::Forward => Ok(FlipDir::Forward),
+            Flip::Back => Ok(FlipDir::Back),
+            _ => Err(CommandError::Unsupported(format!("flip {:?}", flip))),
+        }
+    }
+}
+
+/// Cameras used for the mission pad detection of the Tello EDU (`mdirection x`)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MissionPadDirection {
+    Downward = 0,
+    Forward = 1,
+    Both = 2,
+}
+
+/// colors of the LED matrix of the RoboMaster TT expansion, see `CommandMode::ext_display_text()`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MatrixColor {
+    Red,
+    Blue,
+    Purple,
+}
+
+/// scroll directions of `CommandMode::ext_display_text()`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ScrollDirection {
+    Left,
+    Right,
+    Up,
+    Down,
+}
+
+/// a curve through the point `p1` to the point `p2`, see `CommandMode::curve()`
+///
+/// - `p1`, `p2` (`x`, `y`, `z`) 0 or (-)20 - (-)500 cm
+/// - `speed` 10 - 60 centimeter per second
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Curve {
+    pub p1: (i32, i32, i32),
+    pub p2: (i32, i32, i32),
+    pub speed: u8,
+}
+
+impl Curve {
+    /// length of the path in cm, through the first to the second point
+    fn length(&self) -> f64 {
+        let (x1, y1, z1) = self.p1;
+        let (x2, y2, z2) = self.p2;
+        path_length(self.p1) + path_length((x2 - x1, y2 - y1, z2 - z1))
+    }
+}
+
+/// a jump between two mission pads of the Tello EDU, see `CommandMode::jump()`
+///
+/// - `x`, `y`, `z` 0 or (-)20 - (-)500 cm, relative to the mission pad `mid1`
+/// - `speed` 10 - 100 centimeter per second
+/// - `yaw` -360 - 360 degree, relative to the mission pad `mid2`
+/// - `mid1`, `mid2` 1 - 8
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Jump {
+    pub x: i32,
+    pub y: i32,
+    pub z: i32,
+    pub speed: u8,
+    pub yaw: i32,
+    pub mid1: u8,
+    pub mid2: u8,
+}
+
+/// `flip x` for the direction
+fn flip_command(dir: FlipDir) -> String {
+    format!("flip {}", dir.sdk_arg())
+}
+
+/// `rc a b c d` with the values clamped to -100 - 100
+fn rc_command(left_right: i8, forward_back: i8, up_down: i8, yaw: i8) -> String {
+    format!(
+        "rc {} {} {} {}",
+        left_right.clamp(-100, 100),
+        forward_back.clamp(-100, 100),
+        up_down.clamp(-100, 100),
+        yaw.clamp(-100, 100)
+    )
+}
+
+/// `rc` command with the current stick parameters of the RCState
+fn rc_command_from_state(rc_state: &mut RCState) -> String {
+    let (up_down, forward_back, left_right, yaw, _) = rc_state.get_stick_parameter();
+    let to_rc = |v: f32| (v.clamp(-1.0, 1.0) * 100.0) as i8;
+    rc_command(
+        to_rc(left_right),
+        to_rc(forward_back),
+        to_rc(up_down),
+        to_rc(yaw),
+    )
+}
+
+#[derive(Debug, Clone, PartialEq)]
 pub struct CommandModeState {
+    /// id of the detected mission pad (1 - 8), -1 if none is detected or the drone does not
+    /// report it. (Tello EDU only)
+    pub mid: i8, // -1
+    /// position relative to the mission pad in cm. (Tello EDU only)
+    pub x: i16, // 0
+    pub y: i16, // 0
+    pub z: i16, // 0
+    /// pitch, roll, yaw relative to the mission pad. (Tello EDU only)
+    pub mpry: (i16, i16, i16), // 0,0,0
     pub pitch: i16, // 0
-    pub roll: i16,  // 0
-    pub yaw: i16,   // -45
-    pub vgx: i16,   // 0
-    pub vgy: i16,   // 0
-    pub vgz: i16,   // 0
-    pub templ: i8,  // 69
-    pub temph: i8,  // 70
-    pub tof: i16,   // 10
-    pub h: i16,     // 0
-    pub bat: u8,    // 92
-    pub baro: f32,  // 548.55
-    pub time: f32,  // 0
-    pub agx: f32,   // -5.00
-    pub agy: f32,   // 0.00
-    pub agz: f32,   // -998.00
+    pub roll: i16, // 0
+    pub yaw: i16, // -45
+    /// speed in cm/s, see `speed_cms()`
+    pub vgx: i16, // 0
+    pub vgy: i16, // 0
+    pub vgz: i16, // 0
+    pub templ: i8, // 69
+    pub temph: i8, // 70
+    pub tof: i16, // 10
+    pub h: i16, // 0
+    pub bat: u8, // 92
+    pub baro: f32, // 548.55
+    pub time: f32, // 0
+    /// acceleration in 0.001 g, see `accel_mps2()`
+    pub agx: f32, // -5.00
+    pub agy: f32, // 0.00
+    pub agz: f32, // -998.00
+    /// the received state line, without the trailing `\r\n`
+    pub raw: String,
+    /// fields of the state line, that are not known (e.g. of a newer firmware)
+    pub extras: HashMap<String, String>,
+}
+
+impl Default for CommandModeState {
+    fn default() -> Self {
+        Self {
+            mid: -1,
+            x: 0,
+            y: 0,
+            z: 0,
+            mpry: (0, 0, 0),
+            pitch: 0,
+            roll: 0,
+            yaw: 0,
+            vgx: 0,
+            vgy: 0,
+            vgz: 0,
+            templ: 0,
+            temph: 0,
+            tof: 0,
+            h: 0,
+            bat: 0,
+            baro: 0.0,
+            time: 0.0,
+            agx: 0.0,
+            agy: 0.0,
+            agz: 0.0,
+            raw: String::new(),
+            extras: HashMap::new(),
+        }
+    }
 }
 
 impl TryFrom<&[u8; 150]> for CommandModeState {
     type Error = FromUtf8Error;
-    fn try_from(error: &[u8; 150]) -> Result<Self, FromUtf8Error> {
-        String::from_utf8(error.to_vec()).and_then(|str| {
-            Ok(str
-                .split(';')
-                .fold(CommandModeState::default(), |mut acc, v| {
-                    let param: Vec<&str> = v.split(':').collect();
-                    match (param.get(0).and_then(|v| Some(v.clone())), param.get(1)) {
-                        (Some("pitch"), Some(value)) => acc.pitch = value.parse().unwrap(),
-                        (Some("roll"), Some(value)) => acc.roll = value.parse().unwrap(),
-                        (Some("yaw"), Some(value)) => acc.yaw = value.parse().unwrap(),
-                        (Some("vgx"), Some(value)) => acc.vgx = value.parse().unwrap(),
-                        (Some("vgy"), Some(value)) => acc.vgy = value.parse().unwrap(),
-                        (Some("vgz"), Some(value)) => acc.vgz = value.parse().unwrap(),
-                        (Some("templ"), Some(value)) => acc.templ = value.parse().unwrap(),
-                        (Some("temph"), Some(value)) => acc.temph = value.parse().unwrap(),
-                        (Some("tof"), Some(value)) => acc.tof = value.parse().unwrap(),
-                        (Some("h"), Some(value)) => acc.h = value.parse().unwrap(),
-                        (Some("bat"), Some(value)) => acc.bat = value.parse().unwrap(),
-                        (Some("baro"), Some(value)) => acc.baro = value.parse().unwrap(),
-                        (Some("time"), Some(value)) => acc.time = value.parse().unwrap(),
-                        (Some("agx"), Some(value)) => acc.agx = value.parse().unwrap(),
-                        (Some("agy"), Some(value)) => acc.agy = value.parse().unwrap(),
-                        (Some("agz"), Some(value)) => acc.agz = value.parse().unwrap(),
-                        _ => (),
+    /// parse the whole buffer. Prefer the slice of the received length, otherwise stale
+    /// bytes of a previous (longer) packet are parsed as well.
+    fn try_from(data: &[u8; 150]) -> Result<Self, FromUtf8Error> {
+        CommandModeState::try_from(&data[..])
+    }
+}
+
+/// standard gravity in m/s²
+const STANDARD_GRAVITY: f32 = 9.80665;
+
+impl CommandModeState {
+    /// acceleration `(agx, agy, agz)` in m/s². The drone reports it in 0.001 g, the gravity is
+    /// included (about -9.8 on the z axis, when the drone rests on the ground).
+    pub fn accel_mps2(&self) -> (f32, f32, f32) {
+        let to_mps2 = |v: f32| v / 1000.0 * STANDARD_GRAVITY;
+        (to_mps2(self.agx), to_mps2(self.agy), to_mps2(self.agz))
+    }
+    /// magnitude of the acceleration in m/s², about 9.8 in the hover
+    pub fn total_accel(&self) -> f32 {
+        let (x, y, z) = self.accel_mps2();
+        (x * x + y * y + z * z).sqrt()
+    }
+    /// speed `(vgx, vgy, vgz)` in cm/s, as reported by the drone
+    pub fn speed_cms(&self) -> (i16, i16, i16) {
+        (self.vgx, self.vgy, self.vgz)
+    }
+}
+
+/// parse the `mpry:0,0,0` value of the state
+fn parse_mpry(value: &str) -> Option<(i16, i16, i16)> {
+    let mut values = value.split(',').map(|v| v.trim().parse().ok());
+    Some((values.next()??, values.next()??, values.next()??))
+}
+
+/// parse the value of a state field. A malformed value (e.g. of a truncated packet) keeps
+/// the current value.
+fn parse_field<T: std::str::FromStr>(field: &mut T, value: &str) {
+    if let Ok(value) = value.trim().parse() {
+        *field = value;
+    }
+}
+
+impl TryFrom<&[u8]> for CommandModeState {
+    type Error = FromUtf8Error;
+    /// parse the state string. Unknown or malformed fields are ignored.
+    fn try_from(data: &[u8]) -> Result<Self, FromUtf8Error> {
+        String::from_utf8(data.to_vec()).map(|str| {
+            let raw = str.trim_end_matches(|c: char| c == '\0' || c.is_whitespace());
+            let state = CommandModeState {
+                raw: raw.to_string(),
+                ..CommandModeState::default()
+            };
+            raw.split(';').fold(state, |mut acc, v| {
+                let mut param = v.splitn(2, ':');
+                match (
+                    param.next().unwrap_or_default(),
+                    param.next().unwrap_or_default(),
+                ) {
+                    ("mid", value) => parse_field(&mut acc.mid, value),
+                    ("x", value) => parse_field(&mut acc.x, value),
+                    ("y", value) => parse_field(&mut acc.y, value),
+                    ("z", value) => parse_field(&mut acc.z, value),
+                    ("pitch", value) => parse_field(&mut acc.pitch, value),
+                    ("roll", value) => parse_field(&mut acc.roll, value),
+                    ("yaw", value) => parse_field(&mut acc.yaw, value),
+                    ("vgx", value) => parse_field(&mut acc.vgx, value),
+                    ("vgy", value) => parse_field(&mut acc.vgy, value),
+                    ("vgz", value) => parse_field(&mut acc.vgz, value),
+                    ("templ", value) => parse_field(&mut acc.templ, value),
+                    ("temph", value) => parse_field(&mut acc.temph, value),
+                    ("tof", value) => parse_field(&mut acc.tof, value),
+                    ("h", value) => parse_field(&mut acc.h, value),
+                    ("bat", value) => parse_field(&mut acc.bat, value),
+                    ("baro", value) => parse_field(&mut acc.baro, value),
+                    ("time", value) => parse_field(&mut acc.time, value),
+                    ("agx", value) => parse_field(&mut acc.agx, value),
+                    ("agy", value) => parse_field(&mut acc.agy, value),
+                    ("agz", value) => parse_field(&mut acc.agz, value),
+                    ("mpry", value) => {
+                        if let Some(mpry) = parse_mpry(value) {
+                            acc.mpry = mpry
+                        }
                     }
-                    acc
-                }))
+                    ("", _) => (),
+                    (key, value) => {
+                        acc.extras.insert(key.to_string(), value.to_string());
+                    }
+                }
+                acc
+            })
         })
     }
 }
 
 #[cfg(not(feature = "tokio_async"))]
 impl CommandMode {
-    fn create_state_receiver() -> mpsc::Receiver<CommandModeState> {
+    fn create_state_receiver(
+        port: u16,
+        shutdown: Arc<AtomicBool>,
+        last_yaw: Arc<Mutex<Option<i16>>>,
+    ) -> Result<(mpsc::Receiver<CommandModeState>, Worker), CommandError> {
+        let state_socket = bind_receiver_socket(port)?;
         let (tx, state_receiver) = mpsc::channel::<CommandModeState>();
-        std::thread::spawn(move || {
-            let state_socket = UdpSocket::bind(&SocketAddr::from(([0, 0, 0, 0], 8890)))
-                .expect("couldn't bind to command address");
-            state_socket.set_nonblocking(true).unwrap();
-            'udpReceiverLoop: loop {
-                let mut buf = [0u8; 150];
+        let worker = std::thread::spawn(move || {
+            'udpReceiverLoop: while !shutdown.load(Ordering::SeqCst) {
+                let mut buf = [0u8; 256];
                 match state_socket.recv(&mut buf) {
-                    Ok(_) => {
-                        if let Ok(state) = CommandModeState::try_from(&buf) {
-                            tx.send(state).unwrap()
+                    Ok(len) => {
+                        if let Ok(state) = CommandModeState::try_from(&buf[..len]) {
+                            *lock(&last_yaw) = Some(state.yaw);
+                            if tx.send(state).is_err() {
+                                // receiver dropped
+                                break 'udpReceiverLoop;
+                            }
+                        } else {
+                            log::debug!(target: "tello::state", "invalid state of {} bytes", len);
                         }
                     }
                     Err(e) => {
                         if e.raw_os_error().unwrap_or(0) == 11 {
-                            std::thread::sleep(Duration::from_millis(500));
+                            std::thread::sleep(Duration::from_millis(100));
                         } else {
-                            println!("BOOM: {:?}", e.to_string());
+                            log::warn!(target: "tello::state", "state receiver stopped: {}", e);
                             break 'udpReceiverLoop;
                         }
                     }
                 }
             }
         });
-        state_receiver
+        Ok((state_receiver, worker))
     }
 
-    fn create_video_receiver(port: u16) -> mpsc::Receiver<Vec<u8>> {
-        let (video_sender, video_receiver) = mpsc::channel::<Vec<u8>>();
-        std::thread::spawn(move || {
-            let video_socket = UdpSocket::bind(&SocketAddr::from(([0, 0, 0, 0], port)))
-                .expect("couldn't bind to command address");
-            video_socket.set_nonblocking(true).unwrap();
-            let mut res_buffer = [0u8; 20000];
-            let mut ptr = 0;
+    fn create_video_receiver(
+        video_socket: std::net::UdpSocket,
+        shutdown: Arc<AtomicBool>,
+    ) -> Result<(mpsc::Receiver<VideoChunk>, Worker), CommandError> {
+        let (video_sender, video_receiver) = mpsc::channel::<VideoChunk>();
+        let worker = std::thread::spawn(move || {
+            let mut assembler = SdkFrameAssembler::default();
             let mut buf = [0u8; 1460];
-            loop {
+            while !shutdown.load(Ordering::SeqCst) {
                 match video_socket.recv(&mut buf) {
                     Ok(size) => {
-                        for v in 0..size {
-                            res_buffer[ptr] = buf[v];
-                            ptr += 1;
-                        }
-                        if size < 1460 {
-                            //println!("got frame: size {}", ptr);
-                            video_sender.send(res_buffer[0..ptr].to_owned()).unwrap();
-                            ptr = 0;
-                            res_buffer = [0u8; 20000];
+                        for chunk in assembler.push(&buf[..size]) {
+                            if video_sender.send(chunk).is_err() {
+                                // receiver dropped
+                                return;
+                            }
                         }
                     }
                     Err(_) => {
@@ -148,69 +530,160 @@ impl CommandMode {
                 }
             }
         });
-        video_receiver
+        Ok((video_receiver, worker))
+    }
+
+    /// stop the receiver threads and wait until the sockets are closed
+    fn shutdown_workers(&mut self) {
+        self.shutdown.store(true, Ordering::SeqCst);
+        for worker in lock_mut(&mut self.workers).drain(..) {
+            let _ = worker.join();
+        }
     }
 }
 #[cfg(feature = "tokio_async")]
 impl CommandMode {
-    fn create_state_receiver() -> StateReceiver<CommandModeState> {
+    fn create_state_receiver(
+        port: u16,
+        _shutdown: Arc<AtomicBool>,
+        last_yaw: Arc<Mutex<Option<i16>>>,
+    ) -> Result<(StateReceiver<CommandModeState>, Worker), CommandError> {
+        let state_socket = UdpSocket::from_std(bind_receiver_socket(port)?)
+            .map_err(|e| CommandError::Socket(format!("Failed to bind port {}: {}", port, e)))?;
         let (tx, state_receiver) = watch::channel::<Option<CommandModeState>>(None);
-        tokio::spawn(async move {
-            let state_socket = UdpSocket::bind(&SocketAddr::from(([0, 0, 0, 0], 8890)))
-                .await
-                .expect("couldn't bind to command address");
-
-            let mut buf = [0u8; 150];
-            while let Ok(_) = state_socket.recv_from(&mut buf).await {
-                // println!("{:?} bytes received from {:?}", len, addr);
-                if let Ok(data) = CommandModeState::try_from(&buf) {
-                    let _ = tx.send(Some(data));
+        let worker = tokio::spawn(async move {
+            let mut buf = [0u8; 256];
+            loop {
+                let len = match state_socket.recv_from(&mut buf).await {
+                    Ok((len, _)) => len,
+                    Err(e) => {
+                        log::warn!(target: "tello::state", "state receiver stopped: {}", e);
+                        return;
+                    }
+                };
+                if let Ok(data) = CommandModeState::try_from(&buf[..len]) {
+                    *lock(&last_yaw) = Some(data.yaw);
+                    if tx.send(Some(data)).is_err() {
+                        // receiver dropped
+                        return;
+                    }
+                } else {
+                    log::debug!(target: "tello::state", "invalid state of {} bytes", len);
                 }
             }
         });
-        state_receiver
+        Ok((state_receiver, worker))
     }
 
-    fn create_video_receiver(port: u16) -> mpsc::Receiver<Vec<u8>> {
-        let (video_sender, video_receiver) = mpsc::channel::<Vec<u8>>(50);
-        tokio::spawn(async move {
-            let video_socket = UdpSocket::bind(&SocketAddr::from(([0, 0, 0, 0], port)))
-                .await
-                .expect("couldn't bind to command address");
-
-            let mut res_buffer = [0u8; 20000];
-            let mut ptr = 0;
+    fn create_video_receiver(
+        video_socket: std::net::UdpSocket,
+        _shutdown: Arc<AtomicBool>,
+    ) -> Result<(mpsc::Receiver<VideoChunk>, Worker), CommandError> {
+        let video_socket = UdpSocket::from_std(video_socket)
+            .map_err(|e| CommandError::Socket(format!("Failed to use the video socket: {}", e)))?;
+        let (video_sender, video_receiver) = mpsc::channel::<VideoChunk>(50);
+        let worker = tokio::spawn(async move {
+            let mut assembler = SdkFrameAssembler::default();
             let mut buf = [0u8; 1460];
-            loop {
-                while let Ok((size, _)) = video_socket.recv_from(&mut buf).await {
-                    for v in 0..size {
-                        res_buffer[ptr] = buf[v];
-                        ptr += 1;
-                    }
-                    if size < 1460 {
-                        // println!("got frame: size {}", ptr);
-                        let _ = video_sender.send(res_buffer[0..ptr].to_owned());
-                        ptr = 0;
-                        res_buffer = [0u8; 20000];
+            while let Ok((size, _)) = video_socket.recv_from(&mut buf).await {
+                for chunk in assembler.push(&buf[..size]) {
+                    if video_sender.send(chunk).await.is_err() {
+                        // receiver dropped
+                        return;
                     }
                 }
             }
         });
-        video_receiver
+        Ok((video_receiver, worker))
+    }
+
+    /// abort the receiver tasks, the sockets are closed with the tasks
+    fn shutdown_workers(&mut self) {
+        self.shutdown.store(true, Ordering::SeqCst);
+        for worker in lock_mut(&mut self.workers).drain(..) {
+            worker.abort();
+        }
+    }
+}
+
+/// bind a non blocking socket to receive the state or the video of the drone
+fn bind_receiver_socket(port: u16) -> Result<std::net::UdpSocket, CommandError> {
+    std::net::UdpSocket::bind(SocketAddr::from(([0, 0, 0, 0], port)))
+        .and_then(|socket| socket.set_nonblocking(true).map(|_| socket))
+        .map_err(|e| CommandError::Socket(format!("Failed to bind port {}: {}", port, e)))
+}
+
+impl Drop for CommandMode {
+    fn drop(&mut self) {
+        self.shutdown_workers();
+    }
+}
+
+impl StopHandle {
+    /// abort the running `CommandMode::run_mission()`. The current motion is stopped and the
+    /// remaining steps are skipped.
+    pub async fn abort(&self) -> Result<(), CommandError> {
+        self.aborted.store(true, Ordering::SeqCst);
+        self.stop().await
+    }
+    /// stop the current motion and hover in place, see `CommandMode::stop()`.
+    pub async fn stop(&self) -> Result<(), CommandError> {
+        self.stopped.store(true, Ordering::SeqCst);
+        lock(&self.unanswered).push(Instant::now());
+        #[cfg(feature = "tokio_async")]
+        let res = self.socket.send_to(b"stop", self.peer_addr).await;
+        #[cfg(not(feature = "tokio_async"))]
+        let res = self.socket.send_to(b"stop", self.peer_addr);
+        res.map(|_| ())
+            .map_err(|e| CommandError::Socket(format!("Failed to send command to drone: {:?}", e)))
     }
 }
 
+/// default port of the state packages
+pub(crate) const STATUS_PORT: u16 = 8890;
+/// default port of the video stream
+pub(crate) const VIDEO_PORT: u16 = 11111;
+/// the keep-alive checks the time of the last command with this interval
+const KEEPALIVE_POLL: Duration = Duration::from_millis(100);
+/// default time to wait for a response, see `set_command_timeout()`
+const DEFAULT_COMMAND_TIMEOUT: Duration = Duration::from_secs(3);
+/// the drone responds to `takeoff` and `land`, when it is in the air or on the ground
+const TAKEOFF_TIME: Duration = Duration::from_secs(7);
+const FLIP_TIME: Duration = Duration::from_secs(2);
+/// turn rate in degree per second, assumed for the response of `cw` and `ccw`. The drone
+/// turns faster.
+const TURN_RATE: f64 = 30.0;
+
 impl From<SocketAddr> for CommandMode {
     /// Constructs a new CommandMode from a SocketAddr.
     ///
-    /// The state and the video frames receivers are spawned and provide those information
-    /// if the drone already sends them. Otherwise you have to `enable()` the drone fist.
+    /// The state and the video ports are bound on the first use of `state_receiver()`,
+    /// `video_receiver()` or `video_on()`. Otherwise you have to `enable()` the drone fist.
     fn from(peer_addr: SocketAddr) -> CommandMode {
         Self {
             peer_addr,
+            socket: Self::create_command_socket(),
+            queue: CommandQueue::default(),
+            status_port: STATUS_PORT,
+            video_port: VIDEO_PORT,
+            video_recv_buffer: DEFAULT_VIDEO_RECV_BUFFER,
+            command_timeout: DEFAULT_COMMAND_TIMEOUT,
+            speed: AtomicU8::new(SPEED_RANGE.0),
+            retries: 0,
+            stopped: Arc::new(AtomicBool::new(false)),
+            unanswered: Arc::new(Mutex::new(Vec::new())),
+            aborted: Arc::new(AtomicBool::new(false)),
+            shutdown: Arc::new(AtomicBool::new(false)),
+            workers: Mutex::new(Vec::new()),
+            last_command: Arc::new(Mutex::new(Instant::now())),
+            keepalive: None,
+            rc_loop: None,
+            last_yaw: Arc::new(Mutex::new(None)),
+            yaw_fusion: false,
+            motors_idling: AtomicBool::new(false),
             odometry: Odometry::default(),
-            state_receiver: Some(Self::create_state_receiver()),
-            video_receiver: Some(Self::create_video_receiver(11111)),
+            state_receiver: None,
+            video: Mutex::new(VideoReceiver::default()),
         }
     }
 }
@@ -223,337 +696,2240 @@ impl CommandMode {
     pub async fn new(ip: &str) -> Result<Self, std::io::Error> {
         Ok(Self::from(ip.parse::<SocketAddr>().unwrap()))
     }
+    /// receive the video stream on another port than 11111, e.g. for a second drone in the
+    /// station mode. The port is send to the drone with `enable()`.
+    ///
+    /// Call it before the video receiver is used.
+    pub fn with_video_port(mut self, port: u16) -> Self {
+        self.video_port = port;
+        self
+    }
+    /// request the receive buffer (SO_RCVBUF) of the video socket in bytes, default
+    /// `DEFAULT_VIDEO_RECV_BUFFER`. See `Drone::set_video_recv_buffer()`.
+    ///
+    /// Call it before the video receiver is used.
+    pub fn with_video_recv_buffer(mut self, bytes: usize) -> Self {
+        self.video_recv_buffer = bytes;
+        self
+    }
+    /// the effective receive buffer of the video socket, the OS may clamp the requested size.
+    /// `None` until the video receiver is used.
+    pub fn video_recv_buffer(&self) -> Option<usize> {
+        lock(&self.video).recv_buffer_size
+    }
+    /// receive the state packages on another port than 8890, e.g. for a second drone in the
+    /// station mode. The port is send to the drone with `enable()`.
+    ///
+    /// Call it before the state receiver is used.
+    pub fn with_status_port(mut self, port: u16) -> Self {
+        self.status_port = port;
+        self
+    }
     /// Take over the ownership of the state receiver. This method returns once the receiver and
-    /// returns `None` afterwards
+    /// returns `None` afterwards. The state port is bound with the first call, an error is
+    /// returned if the port is already in use.
     ///
     /// If you using `tokio_async` you will always get the last known value. otherwise, you will
     /// get a channel of the incoming data.
-    pub fn state_receiver(&mut self) -> Option<StateReceiver<CommandModeState>> {
-        let mut recv = None;
-        std::mem::swap(&mut recv, &mut self.state_receiver);
-        recv
+    pub fn state_receiver(
+        &mut self,
+    ) -> Result<Option<StateReceiver<CommandModeState>>, CommandError> {
+        if self.state_receiver.is_none() {
+            let (receiver, worker) = Self::create_state_receiver(
+                self.status_port,
+                self.shutdown.clone(),
+                self.last_yaw.clone(),
+            )?;
+            lock_mut(&mut self.workers).push(worker);
+            self.state_receiver = Some(Some(receiver));
+        }
+        Ok(self.state_receiver.as_mut().and_then(Option::take))
     }
 
     /// Take over the ownership of the video receiver. This method returns once the receiver and
-    /// returns `None` afterwards
-    pub fn video_receiver(&mut self) -> Option<mpsc::Receiver<Vec<u8>>> {
-        let mut recv = None;
-        std::mem::swap(&mut recv, &mut self.video_receiver);
-        recv
+    /// returns `None` afterwards. The video port is bound with the first call or `video_on()`,
+    /// an error is returned if the port is already in use.
+    pub fn video_receiver(&mut self) -> Result<Option<mpsc::Receiver<VideoChunk>>, CommandError> {
+        self.bind_video_receiver()?;
+        let video = lock_mut(&mut self.video);
+        Ok(video.receiver.as_mut().and_then(Option::take))
     }
-}
 
-#[cfg(feature = "tokio_async")]
-impl CommandMode {
-    async fn send_command(&self, command: Vec<u8>) -> Result<(), String> {
-        let peer = self.peer_addr.clone();
-        let l = tokio::spawn(async move {
-            let socket = UdpSocket::bind("0.0.0.0:8889")
-                .await
-                .map_err(|e| format!("can't create socket: {:?}", e))?;
+    fn bind_video_receiver(&self) -> Result<(), CommandError> {
+        let mut video = lock(&self.video);
+        if video.receiver.is_none() {
+            let socket = bind_receiver_socket(self.video_port)?;
+            // the OS default is kept, if the size is rejected
+            video.recv_buffer_size =
+                set_recv_buffer(SockRef::from(&socket), self.video_recv_buffer).ok();
+            let (receiver, worker) = Self::create_video_receiver(socket, self.shutdown.clone())?;
+            lock(&self.workers).push(worker);
+            video.receiver = Some(Some(receiver));
+        }
+        Ok(())
+    }
 
-            socket
-                .send_to(&command, peer)
-                .await
-                .map_err(|e| format!("Failed to send command to drone: {:?}", e))?;
-
-            let mut buf = [0u8; 64];
-            let res = timeout(Duration::new(30, 0), socket.recv(&mut buf)).await;
-
-            match res {
-                Err(_) => Err(format!("timeout")),
-                Ok(Err(e)) => {
-                    // 11 = Resource temporarily unavailable
-                    if let Some(11) = e.raw_os_error() {
-                        sleep(Duration::from_millis(300)).await;
-                        // println!("I should restart the thing !?");
-                        Err(format!("retry?"))
-                    } else {
-                        return Err(format!("socket error {:?}", e));
-                    }
-                }
-                Ok(Ok(bytes)) => {
-                    // println!("got data {}, {:?}", bytes, buf[..bytes].to_vec());
-                    return String::from_utf8(buf[..bytes].to_vec())
-                        .map_err(|_| format!("Failed to read data {:?}", buf))
-                        .and_then(|res| {
-                            if res.starts_with("ok") {
-                                // println!(
-                                //     "got OK for {:?}!",
-                                //     String::from_utf8(command.to_vec()).unwrap()
-                                // );
-                                Ok(())
-                            } else if res.starts_with("error") {
-                                Err(res)
-                            } else {
-                                Err("Unknown response".to_string())
-                            }
-                        });
-                }
-            }
-        });
-        l.await.unwrap()
+    /// Take over the video receiver as `futures::Stream` of the video frames. This method
+    /// returns once the stream and returns `None` afterwards (like `video_receiver()`)
+    #[cfg(feature = "tokio_async")]
+    pub fn video_stream(
+        &mut self,
+    ) -> Result<Option<tokio_stream::wrappers::ReceiverStream<VideoChunk>>, CommandError> {
+        Ok(self
+            .video_receiver()?
+            .map(tokio_stream::wrappers::ReceiverStream::new))
     }
-}
 
-#[cfg(not(feature = "tokio_async"))]
-impl CommandMode {
-    async fn send_command(&self, command: Vec<u8>) -> Result<(), String> {
-        let timeout = Instant::now();
-        async move {
-            let socket = UdpSocket::bind("0.0.0.0:8889")
-                .map_err(|e| format!("can't create socket: {:?}", e))?;
-            socket
-                .set_nonblocking(true)
-                .map_err(|e| format!("set to non-Blocking failed: {:?}", e))?;
-            {
-                // clear socket if something is left in there
-                let mut buf = [0u8; 4192];
-                let _ignore = socket.recv(&mut buf);
+    /// Take over the video receiver as `VideoFrameStream` of the complete frames. At most
+    /// `capacity` frames are buffered, the oldest frame is dropped when the consumer is slow.
+    /// This method returns once the stream and returns `None` afterwards (like `video_receiver()`)
+    #[cfg(feature = "tokio_async")]
+    pub fn video_frames(
+        &mut self,
+        capacity: usize,
+    ) -> Result<Option<VideoFrameStream>, CommandError> {
+        let mut receiver = match self.video_receiver()? {
+            Some(receiver) => receiver,
+            None => return Ok(None),
+        };
+        let (sink, queue) = frame_queue(capacity);
+        lock_mut(&mut self.workers).push(tokio::spawn(async move {
+            let mut frame_id = 0;
+            while let Some(chunk) = receiver.recv().await {
+                sink.push_chunk(&mut frame_id, chunk);
             }
-            socket
-                .send_to(&command, self.peer_addr)
-                .map_err(|e| format!("Failed to send command to drone: {:?}", e))?;
+        }));
+        Ok(Some(VideoFrameStream::new(queue)))
+    }
 
-            let mut buf = [0u8; 64];
-            loop {
-                let res = socket.recv(&mut buf);
-                match res {
-                    Err(e) => {
-                        // 11 = Resource temporarily unavailable
-                        if let Some(11) = e.raw_os_error() {
-                            if timeout.elapsed() > Duration::new(30, 0) {
-                                break Err("timeout".to_string());
-                            }
-                            std::thread::sleep(Duration::from_millis(300));
-                        } else {
-                            break Err(format!("socket error {:?}", e));
-                        }
-                    }
-                    Ok(bytes) => {
-                        break String::from_utf8(buf[..bytes].to_vec())
-                            .map_err(|_| format!("Failed to read data {:?}", buf))
-                            .and_then(|res| {
-                                if res.starts_with("ok") {
-                                    // println!(
-                                    //     "got OK for {:?}!",
-                                    //     String::from_utf8(command.to_vec()).unwrap()
-                                    // );
-                                    Ok(())
-                                } else if res.starts_with("error") {
-                                    Err(res)
-                                } else {
-                                    Err("Unknown response".to_string())
-                                }
-                            });
-                    }
-                }
+    /// Take over the video receiver as blocking `VideoFrameIter` of the complete frames. At most
+    /// `capacity` frames are buffered, the oldest frame is dropped when the consumer is slow.
+    /// This method returns once the iterator and returns `None` afterwards (like `video_receiver()`)
+    #[cfg(not(feature = "tokio_async"))]
+    pub fn video_frames_iter(
+        &mut self,
+        capacity: usize,
+    ) -> Result<Option<VideoFrameIter>, CommandError> {
+        let receiver = match self.video_receiver()? {
+            Some(receiver) => receiver,
+            None => return Ok(None),
+        };
+        let (sink, queue) = frame_queue(capacity);
+        lock_mut(&mut self.workers).push(std::thread::spawn(move || {
+            let mut frame_id = 0;
+            for chunk in receiver {
+                sink.push_chunk(&mut frame_id, chunk);
             }
-        }
-        .await
+        }));
+        Ok(Some(VideoFrameIter::new(queue)))
     }
 }
 
+#[cfg(feature = "tokio_async")]
 impl CommandMode {
-    /// enables the drone. This command should be the first one you send.
+    /// bind the socket for the commands to a free port. The drone responds to this port.
+    fn create_command_socket() -> CommandSocket {
+        let socket = std::net::UdpSocket::bind(SocketAddr::from(([0, 0, 0, 0], 0)))
+            .expect("couldn't bind to command address");
+        socket.set_nonblocking(true).unwrap();
+        let socket = UdpSocket::from_std(socket).expect("couldn't register command socket");
+        std::sync::Arc::new(socket)
+    }
+
+    /// send the command without waiting for a response. (e.g. `rc`, `stop`)
     ///
-    /// Note: There is no disable(). you have to power-cycle the drone to get it
-    /// back to the normal mode.
-    pub async fn enable(&self) -> Result<(), String> {
-        self.send_command("command".into()).await
+    /// The command is send immediately, even if another command is waiting for its response.
+    pub async fn send_command_nowait(&self, command: &str) -> Result<(), CommandError> {
+        log::trace!(target: "tello::command", "send {:?} without response", command);
+        *lock(&self.last_command) = Instant::now();
+        self.socket
+            .send_to(command.as_bytes(), self.peer_addr)
+            .await
+            .map(|_| ())
+            .map_err(|e| CommandError::Socket(format!("Failed to send command to drone: {:?}", e)))
     }
-    /// Emergency will stop the motors immediately without landing
-    pub async fn emergency(&self) -> Result<(), String> {
-        self.send_command("emergency".into()).await
+
+    /// wait before the next retry
+    async fn backoff(attempt: u8) {
+        sleep(Duration::from_millis(100 << attempt.min(5))).await
     }
-    /// starts the drone to 1 meter above the ground
-    pub async fn take_off(&mut self) -> Result<(), String> {
-        let r = self.send_command("takeoff".into()).await;
-        self.odometry.reset();
-        self.odometry.up(100);
-        r
+
+    async fn pause(duration: Duration) {
+        sleep(duration).await
     }
-    /// Land the drone
-    pub async fn land(&self) -> Result<(), String> {
-        self.send_command("land".into()).await
+
+    /// handle to `stop()` the drone from another task, while a motion is running.
+    pub fn stop_handle(&self) -> StopHandle {
+        StopHandle {
+            peer_addr: self.peer_addr,
+            socket: self.socket.clone(),
+            stopped: self.stopped.clone(),
+            unanswered: self.unanswered.clone(),
+            aborted: self.aborted.clone(),
+        }
     }
-    /// Enable the drone to send video frames to the 11111 port of the command sender IP
-    pub async fn video_on(&self) -> Result<(), String> {
-        self.send_command("streamon".into()).await
+
+    /// send the stick parameters of the `rc_state` every 50ms (20Hz) with the `rc` command.
+    ///
+    /// The loop stops with `stop_rc_loop()`, when the CommandMode is dropped, or when all other
+    /// references to the `rc_state` are dropped. A running loop is replaced.
+    pub fn start_rc_loop(&mut self, rc_state: &Arc<Mutex<RCState>>) -> Result<(), CommandError> {
+        self.stop_rc_loop();
+        let stop = Arc::new(AtomicBool::new(false));
+        let rc_loop = stop.clone();
+        let socket = self.socket.clone();
+        let peer = self.peer_addr;
+        let last_command = self.last_command.clone();
+        let rc_state: Weak<Mutex<RCState>> = Arc::downgrade(rc_state);
+        let worker = tokio::spawn(async move {
+            while !rc_loop.load(Ordering::SeqCst) {
+                let command = match rc_state.upgrade() {
+                    Some(rc_state) => rc_command_from_state(&mut lock(&rc_state)),
+                    None => break,
+                };
+                *lock(&last_command) = Instant::now();
+                let _ = socket.send_to(command.as_bytes(), peer).await;
+                sleep(Duration::from_millis(50)).await;
+            }
+        });
+        lock_mut(&mut self.workers).push(worker);
+        self.rc_loop = Some(stop);
+        Ok(())
     }
-    /// Disable the video stream
-    pub async fn video_off(&self) -> Result<(), String> {
-        self.send_command("streamoff".into()).await
+
+    /// send the command once and return the raw response of the drone
+    async fn send_request_once(
+        &self,
+        command: Vec<u8>,
+        command_timeout: Duration,
+    ) -> Result<String, CommandError> {
+        let peer = self.peer_addr;
+        let socket = self.socket.clone();
+        let queue = self.queue.clone();
+        let last_command = self.last_command.clone();
+        let unanswered = self.unanswered.clone();
+        let l = tokio::spawn(async move {
+            let _queue = queue.lock().await;
+            let replies = Replies {
+                last_command: &last_command,
+                unanswered: &unanswered,
+            };
+            exchange(&socket, peer, &command, command_timeout, replies).await
+        });
+        l.await.unwrap()
     }
-    /// move upwards for 20-500 cm
-    pub async fn up(&mut self, step: u32) -> Result<(), String> {
-        let step_norm = step.min(500).max(20);
-        let command = format!("up {}", step_norm);
-        self.send_command(command.into())
-            .await
-            .and_then(|_| Ok(self.odometry.up(step_norm)))
+
+    /// Send `battery?` to the drone, when no other command is send within the `interval`.
+    /// The drone lands by itself, if it does not receive a command for 15 seconds.
+    ///
+    /// The keep-alive waits in the command queue like any other command, it never takes
+    /// the response of another command and pauses while a command is waiting for its response.
+    pub fn start_keepalive(&mut self, interval: Duration) {
+        self.stop_keepalive();
+        let stop = Arc::new(AtomicBool::new(false));
+        let keepalive = stop.clone();
+        let peer = self.peer_addr;
+        let socket = self.socket.clone();
+        let queue = self.queue.clone();
+        let last_command = self.last_command.clone();
+        let unanswered = self.unanswered.clone();
+        let command_timeout = self.command_timeout;
+        let worker = tokio::spawn(async move {
+            while !keepalive.load(Ordering::SeqCst) {
+                sleep(interval.min(KEEPALIVE_POLL)).await;
+                let idle = lock(&last_command).elapsed();
+                if idle < interval {
+                    continue;
+                }
+                // a busy queue means, there is a command in flight
+                if let Ok(_queue) = queue.try_lock() {
+                    let replies = Replies {
+                        last_command: &last_command,
+                        unanswered: &unanswered,
+                    };
+                    let _ = exchange(&socket, peer, b"battery?", command_timeout, replies).await;
+                }
+            }
+        });
+        lock_mut(&mut self.workers).push(worker);
+        self.keepalive = Some(stop);
     }
-    /// move downwards for 20-500 cm (if possible)
-    pub async fn down(&mut self, step: u32) -> Result<(), String> {
-        let step_norm = step.min(500).max(20);
-        let command = format!("down {}", step_norm);
-        self.send_command(command.into())
-            .await
-            .and_then(|_| Ok(self.odometry.down(step_norm)))
+}
+
+/// send the command and wait for its response. The caller has to hold the command queue.
+#[cfg(feature = "tokio_async")]
+async fn exchange(
+    socket: &UdpSocket,
+    peer: SocketAddr,
+    command: &[u8],
+    command_timeout: Duration,
+    replies: Replies<'_>,
+) -> Result<String, CommandError> {
+    {
+        // clear socket if a late response is left in there
+        let mut buf = [0u8; 4192];
+        while socket.try_recv(&mut buf).is_ok() {
+            replies.take_unanswered(command_timeout);
+        }
     }
-    /// move to the left for 20-500 cm
-    pub async fn left(&mut self, step: u32) -> Result<(), String> {
-        let step_norm = step.min(500).max(20);
-        let command = format!("left {}", step_norm);
-        self.send_command(command.into())
-            .await
-            .and_then(|_| Ok(self.odometry.left(step_norm)))
+
+    *lock(replies.last_command) = Instant::now();
+    socket
+        .send_to(command, peer)
+        .await
+        .map_err(|e| CommandError::Socket(format!("Failed to send command to drone: {:?}", e)))?;
+
+    let deadline = tokio::time::Instant::now() + command_timeout;
+    let mut buf = [0u8; 64];
+    loop {
+        match timeout_at(deadline, socket.recv(&mut buf)).await {
+            Err(_) => break Err(CommandError::Timeout),
+            Ok(Err(e)) => break Err(CommandError::Socket(format!("socket error {:?}", e))),
+            Ok(Ok(_)) if replies.take_unanswered(command_timeout) => {
+                log::debug!(target: "tello::command", "skip the response of a stop");
+            }
+            Ok(Ok(bytes)) => {
+                break String::from_utf8(buf[..bytes].to_vec()).map_err(|_| {
+                    CommandError::UnknownResponse(format!("Failed to read data {:?}", buf))
+                })
+            }
+        }
     }
-    /// move to the right for 20-500 cm
-    pub async fn right(&mut self, step: u32) -> Result<(), String> {
-        let step_norm = step.min(500).max(20);
-        let command = format!("right {}", step_norm);
-        self.send_command(command.into())
-            .await
-            .and_then(|_| Ok(self.odometry.right(step_norm)))
+}
+
+#[cfg(not(feature = "tokio_async"))]
+impl CommandMode {
+    /// bind the socket for the commands to a free port. The drone responds to this port.
+    fn create_command_socket() -> CommandSocket {
+        let socket = UdpSocket::bind(SocketAddr::from(([0, 0, 0, 0], 0)))
+            .expect("couldn't bind to command address");
+        socket.set_nonblocking(true).unwrap();
+        socket
     }
-    /// move forwards for 20-200 cm
-    pub async fn forward(&mut self, step: u32) -> Result<(), String> {
-        let step_norm = step.min(500).max(20);
-        let command = format!("forward {}", step_norm);
-        self.send_command(command.into())
-            .await
-            .and_then(|_| Ok(self.odometry.forward(step_norm)))
+
+    /// send the command without waiting for a response. (e.g. `rc`, `stop`)
+    ///
+    /// The command is send immediately, even if another command is waiting for its response.
+    pub async fn send_command_nowait(&self, command: &str) -> Result<(), CommandError> {
+        log::trace!(target: "tello::command", "send {:?} without response", command);
+        *lock(&self.last_command) = Instant::now();
+        self.socket
+            .send_to(command.as_bytes(), self.peer_addr)
+            .map(|_| ())
+            .map_err(|e| CommandError::Socket(format!("Failed to send command to drone: {:?}", e)))
     }
-    /// move backwards for 20 - 500 cm
-    pub async fn back(&mut self, step: u32) -> Result<(), String> {
-        let step_norm = step.min(500).max(20);
-        let command = format!("back {}", step_norm);
-        self.send_command(command.into())
-            .await
-            .and_then(|_| Ok(self.odometry.back(step_norm)))
+
+    /// wait before the next retry
+    async fn backoff(attempt: u8) {
+        std::thread::sleep(Duration::from_millis(100 << attempt.min(5)))
     }
-    /// turn clockwise for 0 - 3600 degrees (10 times 360)
-    pub async fn cw(&mut self, step: u32) -> Result<(), String> {
-        let command = format!("cw {}", step);
-        let step_norm = step.min(3600).max(1);
-        self.send_command(command.into())
-            .await
-            .and_then(|_| Ok(self.odometry.cw(step_norm)))
+
+    async fn pause(duration: Duration) {
+        std::thread::sleep(duration)
     }
-    /// turn counter clockwise for 0 - 3600 degrees (10 times 360)
-    pub async fn ccw(&mut self, step: u32) -> Result<(), String> {
-        let step_norm = step.min(3600).max(1);
-        let command = format!("ccw {}", step);
-        self.send_command(command.into())
-            .await
-            .and_then(|_| Ok(self.odometry.ccw(step_norm)))
+
+    /// handle to `stop()` the drone from another thread, while a motion is running.
+    pub fn stop_handle(&self) -> StopHandle {
+        StopHandle {
+            peer_addr: self.peer_addr,
+            socket: self
+                .socket
+                .try_clone()
+                .expect("couldn't clone command socket"),
+            stopped: self.stopped.clone(),
+            unanswered: self.unanswered.clone(),
+            aborted: self.aborted.clone(),
+        }
     }
 
-    /// Go to a given position in the 3D space.
+    /// send the stick parameters of the `rc_state` every 50ms (20Hz) with the `rc` command.
     ///
-    /// - `x`, `y`, `z` 0 or (-)20 - (-)500 cm
-    /// - `speed` speed in centimeter per second
-    pub async fn go_to(&mut self, x: i32, y: i32, z: i32, speed: u8) -> Result<(), String> {
-        let x_dir = x > 0;
-        let y_dir = y > 0;
-        let z_dir = z > 0;
-
-        let mut x_norm = (x == 0).then(|| 0).unwrap_or(x.abs().min(500).max(20));
-        x_norm = x_dir.then(|| x_norm).unwrap_or(x_norm * -1);
-
-        let mut y_norm = (y == 0).then(|| 0).unwrap_or(y.abs().min(500).max(20));
-        y_norm = y_dir.then(|| y_norm).unwrap_or(y_norm * -1);
-
-        let mut z_norm = (z == 0).then(|| 0).unwrap_or(z.abs().min(500).max(20));
-        z_norm = z_dir.then(|| z_norm).unwrap_or(z_norm * -1);
-
-        let speed_norm = speed.min(100).max(10);
-        let command = format!("go {} {} {} {}", x_norm, y_norm, z_norm, speed_norm);
-        // println!("{}", command);
-        self.send_command(command.into()).await.and_then(|_| {
-            if x_dir {
-                self.odometry.forward(x_norm.abs() as u32);
-            } else {
-                self.odometry.back(x_norm.abs() as u32);
-            }
-            if y_dir {
-                self.odometry.right(y_norm.abs() as u32);
-            } else {
-                self.odometry.left(y_norm.abs() as u32);
-            }
-            if z_dir {
-                self.odometry.up(z_norm.abs() as u32);
-            } else {
-                self.odometry.down(z_norm.abs() as u32);
+    /// The loop stops with `stop_rc_loop()`, when the CommandMode is dropped, or when all other
+    /// references to the `rc_state` are dropped. A running loop is replaced.
+    pub fn start_rc_loop(&mut self, rc_state: &Arc<Mutex<RCState>>) -> Result<(), CommandError> {
+        self.stop_rc_loop();
+        let socket = self.socket.try_clone().map_err(|e| {
+            CommandError::Socket(format!("Failed to clone the command socket: {}", e))
+        })?;
+        let stop = Arc::new(AtomicBool::new(false));
+        let rc_loop = stop.clone();
+        let peer = self.peer_addr;
+        let last_command = self.last_command.clone();
+        let shutdown = self.shutdown.clone();
+        let rc_state: Weak<Mutex<RCState>> = Arc::downgrade(rc_state);
+        let worker = std::thread::spawn(move || {
+            while !rc_loop.load(Ordering::SeqCst) && !shutdown.load(Ordering::SeqCst) {
+                let command = match rc_state.upgrade() {
+                    Some(rc_state) => rc_command_from_state(&mut lock(&rc_state)),
+                    None => break,
+                };
+                *lock(&last_command) = Instant::now();
+                let _ = socket.send_to(command.as_bytes(), peer);
+                std::thread::sleep(Duration::from_millis(50));
             }
-            Ok(())
-        })
+        });
+        lock_mut(&mut self.workers).push(worker);
+        self.rc_loop = Some(stop);
+        Ok(())
     }
 
-    /// Moves in a curve parsing the first point to the second point in the shortest path.
+    /// send the command once and return the raw response of the drone
+    async fn send_request_once(
+        &self,
+        command: Vec<u8>,
+        command_timeout: Duration,
+    ) -> Result<String, CommandError> {
+        async move {
+            let _queue = self
+                .queue
+                .lock()
+                .map_err(|_| CommandError::Socket("command queue is poisoned".to_string()))?;
+            let replies = Replies {
+                last_command: &self.last_command,
+                unanswered: &self.unanswered,
+            };
+            exchange(
+                &self.socket,
+                self.peer_addr,
+                &command,
+                command_timeout,
+                replies,
+            )
+        }
+        .await
+    }
+
+    /// Send `battery?` to the drone, when no other command is send within the `interval`.
+    /// The drone lands by itself, if it does not receive a command for 15 seconds.
     ///
-    /// The radius could not be to large and the distance cold not exceed the 500 cm
-    /// the minimal distance to go is 0 or 20cm on `x`,`y`,`z`
-    pub async fn curve(
-        &mut self,
-        x1: i32,
-        y1: i32,
-        z1: i32,
-        x2: i32,
-        y2: i32,
-        z2: i32,
-        speed: u8,
-    ) -> Result<(), String> {
-        let x1_dir = x1 > 0;
-        let y1_dir = y1 > 0;
-        let z1_dir = z1 > 0;
-        let x2_dir = x2 > 0;
-        let y2_dir = y2 > 0;
-        let z2_dir = z2 > 0;
-
-        let mut x1_norm = (x1 == 0).then(|| 0).unwrap_or(x1.min(500).max(20));
-        x1_norm = x1_dir.then(|| x1_norm).unwrap_or(x1_norm * -1);
-        let mut y1_norm = (y1 == 0).then(|| 0).unwrap_or(y1.min(500).max(20));
-        y1_norm = y1_dir.then(|| y1_norm).unwrap_or(y1_norm * -1);
-        let mut z1_norm = (z1 == 0).then(|| 0).unwrap_or(z1.min(500).max(20));
-        z1_norm = z1_dir.then(|| z1_norm).unwrap_or(z1_norm * -1);
-        let mut x2_norm = (x2 == 0).then(|| 0).unwrap_or(x2.min(500).max(20));
-        x2_norm = x2_dir.then(|| x2_norm).unwrap_or(x2_norm * -1);
-        let mut y2_norm = (y2 == 0).then(|| 0).unwrap_or(y2.min(500).max(20));
-        y2_norm = y2_dir.then(|| y2_norm).unwrap_or(y2_norm * -1);
-        let mut z2_norm = (z2 == 0).then(|| 0).unwrap_or(z2.min(500).max(20));
-        z2_norm = z2_dir.then(|| z2_norm).unwrap_or(z2_norm * -1);
-        let speed_norm = speed.min(100).max(10);
-        let command = format!(
-            "curve {} {} {} {} {} {} {}",
-            x1_norm, y1_norm, z1_norm, x2_norm, y2_norm, z2_norm, speed_norm
-        );
-        self.send_command(command.into()).await.and_then(|_| {
-            if x2_dir {
-                self.odometry.forward(x2_norm.abs() as u32);
-            } else {
-                self.odometry.back(x2_norm.abs() as u32);
+    /// The keep-alive waits in the command queue like any other command, it never takes
+    /// the response of another command and pauses while a command is waiting for its response.
+    pub fn start_keepalive(&mut self, interval: Duration) {
+        self.stop_keepalive();
+        let stop = Arc::new(AtomicBool::new(false));
+        let keepalive = stop.clone();
+        let shutdown = self.shutdown.clone();
+        let peer = self.peer_addr;
+        let socket = self
+            .socket
+            .try_clone()
+            .expect("couldn't clone command socket");
+        let queue = self.queue.clone();
+        let last_command = self.last_command.clone();
+        let unanswered = self.unanswered.clone();
+        let command_timeout = self.command_timeout;
+        let worker = std::thread::spawn(move || {
+            while !keepalive.load(Ordering::SeqCst) && !shutdown.load(Ordering::SeqCst) {
+                std::thread::sleep(interval.min(KEEPALIVE_POLL));
+                if lock(&last_command).elapsed() < interval {
+                    continue;
+                }
+                // a busy queue means, there is a command in flight
+                if let Ok(_queue) = queue.try_lock() {
+                    let replies = Replies {
+                        last_command: &last_command,
+                        unanswered: &unanswered,
+                    };
+                    let _ = exchange(&socket, peer, b"battery?", command_timeout, replies);
+                }
             }
-            if y2_dir {
-                self.odometry.right(y2_norm.abs() as u32);
-            } else {
-                self.odometry.left(y2_norm.abs() as u32);
+        });
+        lock_mut(&mut self.workers).push(worker);
+        self.keepalive = Some(stop);
+    }
+}
+
+/// send the command and wait for its response. The caller has to hold the command queue.
+#[cfg(not(feature = "tokio_async"))]
+fn exchange(
+    socket: &UdpSocket,
+    peer: SocketAddr,
+    command: &[u8],
+    command_timeout: Duration,
+    replies: Replies<'_>,
+) -> Result<String, CommandError> {
+    let timeout = Instant::now();
+    {
+        // clear socket if a late response is left in there
+        let mut buf = [0u8; 4192];
+        while socket.recv(&mut buf).is_ok() {
+            replies.take_unanswered(command_timeout);
+        }
+    }
+    *lock(replies.last_command) = Instant::now();
+    socket
+        .send_to(command, peer)
+        .map_err(|e| CommandError::Socket(format!("Failed to send command to drone: {:?}", e)))?;
+
+    let mut buf = [0u8; 64];
+    loop {
+        let res = socket.recv(&mut buf);
+        match res {
+            Err(e) => {
+                // 11 = Resource temporarily unavailable
+                if let Some(11) = e.raw_os_error() {
+                    if timeout.elapsed() > command_timeout {
+                        break Err(CommandError::Timeout);
+                    }
+                    std::thread::sleep(Duration::from_millis(50));
+                } else {
+                    break Err(CommandError::Socket(format!("socket error {:?}", e)));
+                }
+            }
+            Ok(_) if replies.take_unanswered(command_timeout) => {
+                log::debug!(target: "tello::command", "skip the response of a stop");
+            }
+            Ok(bytes) => {
+                break String::from_utf8(buf[..bytes].to_vec()).map_err(|_| {
+                    CommandError::UnknownResponse(format!("Failed to read data {:?}", buf))
+                });
             }
-            if z2_dir {
-                self.odometry.up(z2_norm.abs() as u32);
+        }
+    }
+}
+
+/// bookkeeping of the responses, shared by the commands, the keep-alive and the `StopHandle`
+struct Replies<'a> {
+    /// time of the last command, send to the drone
+    last_command: &'a Mutex<Instant>,
+    /// send time of the `stop` commands without a response yet
+    unanswered: &'a Mutex<Vec<Instant>>,
+}
+
+impl Replies<'_> {
+    /// true, if a received response belongs to a `stop`, that was send within the timeout.
+    /// The text protocol has no id, the responses arrive in the order of the commands and the
+    /// `stop` is answered before the aborted motion.
+    fn take_unanswered(&self, command_timeout: Duration) -> bool {
+        let mut unanswered = lock(self.unanswered);
+        unanswered.retain(|sent| sent.elapsed() <= command_timeout);
+        if unanswered.is_empty() {
+            false
+        } else {
+            unanswered.remove(0);
+            true
+        }
+    }
+}
+
+impl CommandMode {
+    /// stop the keep-alive of `start_keepalive()`
+    pub fn stop_keepalive(&mut self) {
+        if let Some(stop) = self.keepalive.take() {
+            stop.store(true, Ordering::SeqCst);
+        }
+    }
+
+    /// stop the rc loop of `start_rc_loop()`
+    pub fn stop_rc_loop(&mut self) {
+        if let Some(stop) = self.rc_loop.take() {
+            stop.store(true, Ordering::SeqCst);
+        }
+    }
+
+    /// Set the time to wait for the response of the drone. Default: 3 seconds
+    ///
+    /// The drone responds after a motion is done, so the motions wait longer: the timeout
+    /// plus the time the motion takes at the speed of `speed()` (10 cm/s until it is set),
+    /// e.g. 6 seconds for `forward(30)`.
+    pub fn set_command_timeout(&mut self, timeout: Duration) {
+        self.command_timeout = timeout;
+    }
+
+    /// Set how often a command is send again, when the drone does not respond in time.
+    /// Default: 0
+    ///
+    /// Between the retries, the CommandMode waits 100ms, 200ms, 400ms, ...
+    /// Most of the motion commands are idempotent, a lost response is just send again.
+    pub fn set_retries(&mut self, retries: u8) {
+        self.retries = retries;
+    }
+
+    /// Send the command (and the retries) and return the raw response of the drone. The
+    /// response of a motion is expected after its `duration`, in addition to the timeout.
+    async fn send_request(
+        &self,
+        command: Vec<u8>,
+        duration: Duration,
+    ) -> Result<String, CommandError> {
+        let name = String::from_utf8_lossy(&command).into_owned();
+        let mut attempt = 0;
+        loop {
+            log::debug!(target: "tello::command", "send {:?}", name);
+            match self
+                .send_request_once(command.clone(), self.command_timeout + duration)
+                .await
+            {
+                // a stopped motion is not answered, it must not be send again
+                Err(CommandError::Timeout)
+                    if attempt < self.retries && !self.stopped.load(Ordering::SeqCst) =>
+                {
+                    log::debug!(target: "tello::command", "{:?} timed out, retry", name);
+                    Self::backoff(attempt).await;
+                    attempt += 1;
+                }
+                res => {
+                    log::debug!(target: "tello::command", "{:?} -> {:?}", name, res);
+                    break res;
+                }
+            }
+        }
+    }
+
+    /// send a control command and expect an `ok` from the drone
+    async fn send_command(&self, command: Vec<u8>) -> Result<(), CommandError> {
+        self.send_command_expect(command, "ok").await
+    }
+
+    /// send a motion, that takes about `duration`, and expect an `ok` after it
+    async fn send_motion_command(
+        &self,
+        command: Vec<u8>,
+        duration: Duration,
+    ) -> Result<(), CommandError> {
+        let name = String::from_utf8_lossy(&command).into_owned();
+        let res = self.send_request(command, duration).await?;
+        expect_response(&name, res, "ok")
+    }
+
+    /// send a control command and expect a response starting with `ok`, e.g. `led ok` for the
+    /// EXT commands
+    async fn send_command_expect(&self, command: Vec<u8>, ok: &str) -> Result<(), CommandError> {
+        let name = String::from_utf8_lossy(&command).into_owned();
+        let res = self.send_request(command, Duration::ZERO).await?;
+        expect_response(&name, res, ok)
+    }
+
+    /// send a read command (e.g. `battery?`) and return the trimmed response of the drone.
+    ///
+    /// A response starting with `error` is returned as `Err`.
+    pub async fn query(&self, cmd: &str) -> Result<String, CommandError> {
+        let res = self.send_request(cmd.into(), Duration::ZERO).await?;
+        let res = res.trim_matches(|c: char| c.is_whitespace() || c == '\0');
+        if res.starts_with("error") {
+            Err(drone_error(cmd, res.to_string()))
+        } else {
+            Ok(res.to_string())
+        }
+    }
+    /// get the current battery percentage (0-100)
+    pub async fn get_battery(&self) -> Result<u8, CommandError> {
+        let res = self.query("battery?").await?;
+        res.parse().map_err(|_| CommandError::UnknownResponse(res))
+    }
+    /// same as `get_battery()`
+    pub async fn battery(&self) -> Result<u8, CommandError> {
+        self.get_battery().await
+    }
+    /// Get the current speed setting in cm/s.
+    ///
+    /// There is no `speed()` alias like `battery()`, `speed()` sets the speed.
+    pub async fn get_speed(&self) -> Result<f32, CommandError> {
+        let res = self.query("speed?").await?;
+        res.parse().map_err(|_| CommandError::UnknownResponse(res))
+    }
+    /// get the flight time of the motors
+    pub async fn get_time(&self) -> Result<Duration, CommandError> {
+        let res = self.query("time?").await?;
+        parse_time(&res).ok_or(CommandError::UnknownResponse(res))
+    }
+    /// get the wifi signal-noise ratio
+    pub async fn get_wifi_snr(&self) -> Result<u8, CommandError> {
+        let res = self.query("wifi?").await?;
+        res.parse().map_err(|_| CommandError::UnknownResponse(res))
+    }
+    /// get the SDK version of the drone (e.g. `"20"` for SDK 2.0)
+    pub async fn get_sdk_version(&self) -> Result<String, CommandError> {
+        self.query("sdk?").await
+    }
+    /// get the serial number of the drone
+    pub async fn get_serial(&self) -> Result<String, CommandError> {
+        self.query("sn?").await
+    }
+    /// same as `get_sdk_version()`
+    pub async fn sdk_version(&self) -> Result<String, CommandError> {
+        self.get_sdk_version().await
+    }
+    /// same as `get_serial()`
+    pub async fn serial_number(&self) -> Result<String, CommandError> {
+        self.get_serial().await
+    }
+
+    /// Send a command to the RoboMaster TT expansion and return the trimmed response, e.g.
+    /// `ext("led 255 0 0")` sends `EXT led 255 0 0`. (Tello TT only)
+    ///
+    /// The expansion answers with the name of the module, e.g. `led ok` or `matrix ok`. A
+    /// response starting with `error` is returned as `Err`.
+    pub async fn ext(&self, raw: &str) -> Result<String, CommandError> {
+        self.query(&format!("EXT {}", raw)).await
+    }
+    /// set the color of the LED on top of the expansion. (Tello TT only)
+    pub async fn ext_led(&self, r: u8, g: u8, b: u8) -> Result<(), CommandError> {
+        self.send_command_expect(ext_led_command(r, g, b).into(), "led ok")
+            .await
+    }
+    /// alternate the LED between two colors, with a `frequency` of 0.1 - 10 Hz. (Tello TT
+    /// only)
+    pub async fn ext_led_blink(
+        &self,
+        frequency: f32,
+        first: (u8, u8, u8),
+        second: (u8, u8, u8),
+    ) -> Result<(), CommandError> {
+        let command = ext_led_blink_command(frequency, first, second)?;
+        self.send_command_expect(command.into(), "led ok").await
+    }
+    /// Scroll the `text` over the LED matrix with a `rate` of 0.1 - 2.5 Hz. (Tello TT only)
+    ///
+    /// The text has at most 70 printable ASCII characters.
+    pub async fn ext_display_text(
+        &self,
+        text: &str,
+        direction: ScrollDirection,
+        color: MatrixColor,
+        rate: f32,
+    ) -> Result<(), CommandError> {
+        let command = ext_text_command(text, direction, color, rate)?;
+        self.send_command_expect(command.into(), "matrix ok").await
+    }
+    /// Read the distance of the time of flight sensor of the expansion in mm. (Tello TT only)
+    ///
+    /// The sensor reports 8192, if nothing is in its range.
+    pub async fn ext_tof(&self) -> Result<u16, CommandError> {
+        let res = self.ext("tof?").await?;
+        parse_tof(&res).ok_or(CommandError::UnknownResponse(res))
+    }
+}
+
+/// the error of an `error ...` response of the `command`
+fn drone_error(command: &str, res: String) -> CommandError {
+    if res.trim_end().eq_ignore_ascii_case("error not support") {
+        CommandError::UnsupportedFirmware(command.to_string())
+    } else {
+        CommandError::Drone(res)
+    }
+}
+
+/// `Ok` for a response starting with `ok`, otherwise the error of the drone
+fn expect_response(command: &str, res: String, ok: &str) -> Result<(), CommandError> {
+    if res.starts_with(ok) {
+        Ok(())
+    } else if res.starts_with("error") {
+        Err(drone_error(command, res))
+    } else {
+        Err(CommandError::UnknownResponse(res))
+    }
+}
+
+/// time to fly the `distance` in cm with the `speed` in cm/s
+fn flight_time(distance: f64, speed: u8) -> Duration {
+    Duration::from_secs_f64(distance / speed.max(1) as f64)
+}
+
+/// length of the path from the drone to the point `p`
+fn path_length(p: (i32, i32, i32)) -> f64 {
+    let (x, y, z) = (p.0 as f64, p.1 as f64, p.2 as f64);
+    (x * x + y * y + z * z).sqrt()
+}
+
+/// parse the response of `time?` (e.g. `"12s"`)
+fn parse_time(res: &str) -> Option<Duration> {
+    res.trim_end_matches('s')
+        .parse()
+        .ok()
+        .map(Duration::from_secs)
+}
+
+/// SDK range for the distance of `up`, `down`, `left`, `right`, `forward` and `back` in cm
+const MOVE_RANGE: (u32, u32) = (20, 500);
+/// SDK range for the rotation of `cw` and `ccw` in degree (SDK 1.3, SDK 2.0 limits to 360)
+const ROTATION_RANGE: (u32, u32) = (1, 3600);
+/// SDK range for the coordinates of `go` and `curve` in cm (besides 0)
+const COORDINATE_RANGE: (i32, i32) = (20, 500);
+/// SDK range for the speed of `speed` and `go` in cm/s
+const SPEED_RANGE: (u8, u8) = (10, 100);
+/// SDK range for the speed of `curve` in cm/s
+const CURVE_SPEED_RANGE: (u8, u8) = (10, 60);
+/// range of the blink frequency of the TT LED in Hz
+const LED_BLINK_RANGE: (f32, f32) = (0.1, 10.0);
+/// range of the scroll rate of the TT LED matrix in Hz
+const MATRIX_RATE_RANGE: (f32, f32) = (0.1, 2.5);
+/// maximum length of the text on the TT LED matrix
+const MATRIX_TEXT_LEN: usize = 70;
+
+fn clamp_move(step: u32) -> u32 {
+    step.clamp(MOVE_RANGE.0, MOVE_RANGE.1)
+}
+
+fn clamp_rotation(degree: u32) -> u32 {
+    degree.clamp(ROTATION_RANGE.0, ROTATION_RANGE.1)
+}
+
+/// 0 stays 0, otherwise the absolute value is clamped and the sign is kept
+fn clamp_coordinate(value: i32) -> i32 {
+    if value == 0 {
+        0
+    } else {
+        value.signum() * value.abs().clamp(COORDINATE_RANGE.0, COORDINATE_RANGE.1)
+    }
+}
+
+/// `go x y z speed` with the clamped coordinates and speed
+fn go_command(x: i32, y: i32, z: i32, speed: u8) -> String {
+    format!(
+        "go {} {} {} {}",
+        clamp_coordinate(x),
+        clamp_coordinate(y),
+        clamp_coordinate(z),
+        clamp_speed(speed)
+    )
+}
+
+/// `curve x1 y1 z1 x2 y2 z2 speed` with the clamped coordinates and speed
+fn curve_command(curve: &Curve) -> String {
+    let Curve { p1, p2, speed } = *curve;
+    format!(
+        "curve {} {} {} {} {} {} {}",
+        clamp_coordinate(p1.0),
+        clamp_coordinate(p1.1),
+        clamp_coordinate(p1.2),
+        clamp_coordinate(p2.0),
+        clamp_coordinate(p2.1),
+        clamp_coordinate(p2.2),
+        clamp_curve_speed(speed)
+    )
+}
+
+/// mission pad id `m1` - `m8` of the Tello EDU. Another pad would be a different target, so
+/// it is not clamped.
+fn pad_id(mid: u8) -> Result<String, CommandError> {
+    if (1..=8).contains(&mid) {
+        Ok(format!("m{}", mid))
+    } else {
+        Err(CommandError::InvalidParameter(format!(
+            "mission pad {} is not within 1 - 8",
+            mid
+        )))
+    }
+}
+
+/// `go x y z speed mid` relative to the mission pad
+fn go_pad_command(x: i32, y: i32, z: i32, speed: u8, mid: u8) -> Result<String, CommandError> {
+    Ok(format!("{} {}", go_command(x, y, z, speed), pad_id(mid)?))
+}
+
+/// `curve x1 y1 z1 x2 y2 z2 speed mid` relative to the mission pad
+fn curve_pad_command(curve: &Curve, mid: u8) -> Result<String, CommandError> {
+    Ok(format!("{} {}", curve_command(curve), pad_id(mid)?))
+}
+
+/// `jump x y z speed yaw mid1 mid2` from the first to the second mission pad
+fn jump_command(jump: &Jump) -> Result<String, CommandError> {
+    Ok(format!(
+        "jump {} {} {} {} {} {} {}",
+        clamp_coordinate(jump.x),
+        clamp_coordinate(jump.y),
+        clamp_coordinate(jump.z),
+        clamp_speed(jump.speed),
+        jump.yaw.clamp(-360, 360),
+        pad_id(jump.mid1)?,
+        pad_id(jump.mid2)?
+    ))
+}
+
+/// `EXT led r g b`
+fn ext_led_command(r: u8, g: u8, b: u8) -> String {
+    format!("EXT led {} {} {}", r, g, b)
+}
+
+/// `EXT led bl t r1 g1 b1 r2 g2 b2`, alternating between the colors with the `frequency`
+fn ext_led_blink_command(
+    frequency: f32,
+    first: (u8, u8, u8),
+    second: (u8, u8, u8),
+) -> Result<String, CommandError> {
+    if !(LED_BLINK_RANGE.0..=LED_BLINK_RANGE.1).contains(&frequency) {
+        return Err(CommandError::InvalidParameter(format!(
+            "blink frequency {} Hz",
+            frequency
+        )));
+    }
+    Ok(format!(
+        "EXT led bl {} {} {} {} {} {} {}",
+        frequency, first.0, first.1, first.2, second.0, second.1, second.2
+    ))
+}
+
+/// `EXT mled l/r/u/d r/b/p t text`, the text is scrolled with the `rate`
+fn ext_text_command(
+    text: &str,
+    direction: ScrollDirection,
+    color: MatrixColor,
+    rate: f32,
+) -> Result<String, CommandError> {
+    if !(MATRIX_RATE_RANGE.0..=MATRIX_RATE_RANGE.1).contains(&rate) {
+        return Err(CommandError::InvalidParameter(format!(
+            "scroll rate {} Hz",
+            rate
+        )));
+    }
+    if text.is_empty()
+        || text.len() > MATRIX_TEXT_LEN
+        || !text.chars().all(|c| c == ' ' || c.is_ascii_graphic())
+    {
+        return Err(CommandError::InvalidParameter(format!("text {:?}", text)));
+    }
+    let direction = match direction {
+        ScrollDirection::Left => "l",
+        ScrollDirection::Right => "r",
+        ScrollDirection::Up => "u",
+        ScrollDirection::Down => "d",
+    };
+    let color = match color {
+        MatrixColor::Red => "r",
+        MatrixColor::Blue => "b",
+        MatrixColor::Purple => "p",
+    };
+    Ok(format!(
+        "EXT mled {} {} {} {}",
+        direction, color, rate, text
+    ))
+}
+
+/// parse the response of `EXT tof?` (e.g. `"tof 345"`)
+fn parse_tof(res: &str) -> Option<u16> {
+    res.strip_prefix("tof")?.trim().parse().ok()
+}
+
+fn clamp_speed(speed: u8) -> u8 {
+    speed.clamp(SPEED_RANGE.0, SPEED_RANGE.1)
+}
+
+fn clamp_curve_speed(speed: u8) -> u8 {
+    speed.clamp(CURVE_SPEED_RANGE.0, CURVE_SPEED_RANGE.1)
+}
+
+impl CommandMode {
+    /// enables the drone. This command should be the first one you send.
+    ///
+    /// Note: There is no disable(). you have to power-cycle the drone to get it
+    /// back to the normal mode.
+    ///
+    /// If the state or video port is changed, they are send to the drone with `set_ports()`.
+    pub async fn enable(&self) -> Result<(), CommandError> {
+        self.send_command("command".into()).await?;
+        if self.status_port != STATUS_PORT || self.video_port != VIDEO_PORT {
+            self.set_ports().await?;
+        }
+        Ok(())
+    }
+    /// send the state and video port, set with `with_status_port()` and `with_video_port()`
+    pub async fn set_ports(&self) -> Result<(), CommandError> {
+        let command = format!("port {} {}", self.status_port, self.video_port);
+        self.send_command(command.into()).await
+    }
+    /// Emergency will stop the motors immediately without landing
+    pub async fn emergency(&self) -> Result<(), CommandError> {
+        self.send_command("emergency".into()).await?;
+        self.motors_idling.store(false, Ordering::SeqCst);
+        Ok(())
+    }
+    /// stop the current motion and hover in place. The motors keep running, see `emergency()`.
+    ///
+    /// The command is send immediately without waiting for a response, so it can abort
+    /// a long motion (e.g. `forward 500`) that is still waiting for its `ok`. The motions
+    /// borrow the drone mutably, use a `stop_handle()` to stop them from another task.
+    ///
+    /// The aborted motion is not added to the odometry, it is only approximate afterwards.
+    pub async fn stop(&self) -> Result<(), CommandError> {
+        self.stopped.store(true, Ordering::SeqCst);
+        lock(&self.unanswered).push(Instant::now());
+        self.send_command_nowait("stop").await
+    }
+    /// use the yaw of the state stream for the odometry, instead of the commanded rotations.
+    ///
+    /// The state receiver has to be bound (`state_receiver()`) and kept alive. The current
+    /// odometry heading is kept as reference, `take_off()` resets it to the reported yaw.
+    pub fn set_yaw_fusion(&mut self, enabled: bool) {
+        self.yaw_fusion = enabled;
+        if let (true, Some(yaw)) = (enabled, self.reported_yaw()) {
+            let reference = yaw as f64 - self.odometry.heading();
+            self.odometry.set_yaw_reference(reference);
+        }
+    }
+    fn reported_yaw(&self) -> Option<i16> {
+        *lock(&self.last_yaw)
+    }
+    /// send a motion command. Returns false, if the motion was aborted by `stop()`.
+    ///
+    /// The motion stays in the `pending_moves()` of the odometry, if it is not confirmed.
+    async fn send_motion(
+        &mut self,
+        command: String,
+        duration: Duration,
+    ) -> Result<bool, CommandError> {
+        // heading before the motion, the rotations are added on top
+        if let (true, Some(yaw)) = (self.yaw_fusion, self.reported_yaw()) {
+            self.odometry.update_yaw(yaw as f64);
+        }
+        self.stopped.store(false, Ordering::SeqCst);
+        self.odometry.push_pending(&command);
+        match self
+            .send_motion_command(command.clone().into(), duration)
+            .await
+        {
+            // the drone answers the aborted motion with an error or not at all
+            Err(CommandError::Timeout | CommandError::Drone(_))
+                if self.stopped.load(Ordering::SeqCst) => {}
+            res => res?,
+        }
+        self.odometry.resolve_pending(&command);
+        Ok(!self.stopped.swap(false, Ordering::SeqCst))
+    }
+    /// send the stick values for continuous control, without waiting for a response.
+    ///
+    /// - `left_right`, `forward_back`, `up_down`, `yaw` -100 - 100
+    ///
+    /// To use the RCState of the keyboard or game pad, see `start_rc_loop()`.
+    pub async fn rc(
+        &self,
+        left_right: i8,
+        forward_back: i8,
+        up_down: i8,
+        yaw: i8,
+    ) -> Result<(), CommandError> {
+        self.send_command_nowait(&rc_command(left_right, forward_back, up_down, yaw))
+            .await
+    }
+    /// starts the drone to 1 meter above the ground
+    pub async fn take_off(&mut self) -> Result<(), CommandError> {
+        if self.motors_idling() {
+            log::warn!(target: "tello::command", "take off while the motors are idling");
+        }
+        self.send_motion_command("takeoff".into(), TAKEOFF_TIME)
+            .await?;
+        self.motors_idling.store(false, Ordering::SeqCst);
+        self.odometry.reset();
+        self.odometry.up(100);
+        if let (true, Some(yaw)) = (self.yaw_fusion, self.reported_yaw()) {
+            self.odometry.set_yaw_reference(yaw as f64);
+        }
+        Ok(())
+    }
+    /// Land the drone
+    pub async fn land(&self) -> Result<(), CommandError> {
+        self.send_motion_command("land".into(), TAKEOFF_TIME).await
+    }
+    /// Spin the props at idle on the ground, e.g. to warm up the IMU before a precise flight.
+    /// Fails with `CommandError::UnsupportedFirmware` on the older firmware.
+    ///
+    /// With the binary protocol, `drone.rc_state.start_engines()` starts the motors.
+    pub async fn motor_on(&self) -> Result<(), CommandError> {
+        self.send_command("motoron".into()).await?;
+        self.motors_idling.store(true, Ordering::SeqCst);
+        Ok(())
+    }
+    /// stop the idling props of `motor_on()`
+    pub async fn motor_off(&self) -> Result<(), CommandError> {
+        self.send_command("motoroff".into()).await?;
+        self.motors_idling.store(false, Ordering::SeqCst);
+        Ok(())
+    }
+    /// the props spin at idle, after `motor_on()` and before `motor_off()`, `take_off()` or
+    /// `emergency()`
+    pub fn motors_idling(&self) -> bool {
+        self.motors_idling.load(Ordering::SeqCst)
+    }
+    /// Enable the drone to send video frames to the video port (default 11111) of the command
+    /// sender IP. The video port is bound, if it is not done by `video_receiver()` yet.
+    pub async fn video_on(&self) -> Result<(), CommandError> {
+        self.bind_video_receiver()?;
+        self.send_command("streamon".into()).await
+    }
+    /// Disable the video stream
+    pub async fn video_off(&self) -> Result<(), CommandError> {
+        self.send_command("streamoff".into()).await
+    }
+    /// move upwards for 20-500 cm
+    pub async fn up(&mut self, step: u32) -> Result<(), CommandError> {
+        let step_norm = clamp_move(step);
+        let command = format!("up {}", step_norm);
+        if self.send_motion(command, self.move_time(step_norm)).await? {
+            self.odometry.up(step_norm);
+        }
+        Ok(())
+    }
+    /// move downwards for 20-500 cm (if possible)
+    pub async fn down(&mut self, step: u32) -> Result<(), CommandError> {
+        let step_norm = clamp_move(step);
+        let command = format!("down {}", step_norm);
+        if self.send_motion(command, self.move_time(step_norm)).await? {
+            self.odometry.down(step_norm);
+        }
+        Ok(())
+    }
+    /// move to the left for 20-500 cm
+    pub async fn left(&mut self, step: u32) -> Result<(), CommandError> {
+        let step_norm = clamp_move(step);
+        let command = format!("left {}", step_norm);
+        if self.send_motion(command, self.move_time(step_norm)).await? {
+            self.odometry.left(step_norm);
+        }
+        Ok(())
+    }
+    /// move to the right for 20-500 cm
+    pub async fn right(&mut self, step: u32) -> Result<(), CommandError> {
+        let step_norm = clamp_move(step);
+        let command = format!("right {}", step_norm);
+        if self.send_motion(command, self.move_time(step_norm)).await? {
+            self.odometry.right(step_norm);
+        }
+        Ok(())
+    }
+    /// move forwards for 20-500 cm
+    pub async fn forward(&mut self, step: u32) -> Result<(), CommandError> {
+        let step_norm = clamp_move(step);
+        let command = format!("forward {}", step_norm);
+        if self.send_motion(command, self.move_time(step_norm)).await? {
+            self.odometry.forward(step_norm);
+        }
+        Ok(())
+    }
+    /// move backwards for 20-500 cm
+    pub async fn back(&mut self, step: u32) -> Result<(), CommandError> {
+        let step_norm = clamp_move(step);
+        let command = format!("back {}", step_norm);
+        if self.send_motion(command, self.move_time(step_norm)).await? {
+            self.odometry.back(step_norm);
+        }
+        Ok(())
+    }
+    /// turn clockwise for 1 - 3600 degrees (10 times 360)
+    pub async fn cw(&mut self, step: u32) -> Result<(), CommandError> {
+        let step_norm = clamp_rotation(step);
+        let command = format!("cw {}", step_norm);
+        let duration = Duration::from_secs_f64(step_norm as f64 / TURN_RATE);
+        if self.send_motion(command, duration).await? {
+            self.odometry.cw(step_norm);
+        }
+        Ok(())
+    }
+    /// turn counter clockwise for 1 - 3600 degrees (10 times 360)
+    pub async fn ccw(&mut self, step: u32) -> Result<(), CommandError> {
+        let step_norm = clamp_rotation(step);
+        let command = format!("ccw {}", step_norm);
+        let duration = Duration::from_secs_f64(step_norm as f64 / TURN_RATE);
+        if self.send_motion(command, duration).await? {
+            self.odometry.ccw(step_norm);
+        }
+        Ok(())
+    }
+
+    /// Go to a given position in the 3D space, relative to the drone.
+    ///
+    /// - `x`, `y`, `z` 0 or (-)20 - (-)500 cm, in the body frame of the drone (see `Odometry::go`)
+    /// - `speed` 10 - 100 centimeter per second
+    pub async fn go_to(&mut self, x: i32, y: i32, z: i32, speed: u8) -> Result<(), CommandError> {
+        let command = go_command(x, y, z, speed);
+        let duration = flight_time(path_length((x, y, z)), clamp_speed(speed));
+        if self.send_motion(command, duration).await? {
+            self.odometry.go(
+                clamp_coordinate(x),
+                clamp_coordinate(y),
+                clamp_coordinate(z),
+            );
+        }
+        Ok(())
+    }
+
+    /// Moves in a curve parsing the first point to the second point in the shortest path.
+    /// The points are in the body frame of the drone, like `go_to()`.
+    ///
+    /// The radius could not be to large and the distance cold not exceed the 500 cm
+    /// the minimal distance to go is 0 or 20cm on `x`,`y`,`z`
+    /// - `speed` 10 - 60 centimeter per second
+    pub async fn curve(&mut self, curve: Curve) -> Result<(), CommandError> {
+        let command = curve_command(&curve);
+        let duration = flight_time(curve.length(), clamp_curve_speed(curve.speed));
+        if self.send_motion(command, duration).await? {
+            // the drone ends at the second point
+            let (x2, y2, z2) = curve.p2;
+            self.odometry.go(
+                clamp_coordinate(x2),
+                clamp_coordinate(y2),
+                clamp_coordinate(z2),
+            );
+        }
+        Ok(())
+    }
+
+    /// Return to the origin of the odometry (`x`, `y` 0) with the `speed` 10 - 100 cm/s. The
+    /// drone turns towards the origin and flies there with `go` commands of at most 500 cm.
+    /// The height and the heading at the origin are not changed.
+    ///
+    /// Closer than 20 cm (the shortest motion of the drone) the drone stays in place. The
+    /// odometry drifts, the drone is only approximately back at the origin.
+    pub async fn go_home(&mut self, speed: u8) -> Result<(), CommandError> {
+        let distance = self.odometry.distance_to(0.0, 0.0).round() as i32;
+        if distance < COORDINATE_RANGE.0 {
+            return Ok(());
+        }
+        let turn = (self.odometry.heading_to(0.0, 0.0) - self.odometry.heading()).rem_euclid(360.0);
+        let turn = turn.round() as u32;
+        match turn {
+            0 | 360 => (),
+            1..=180 => self.cw(turn).await?,
+            _ => self.ccw(360 - turn).await?,
+        }
+        // the hops are equally long, so the last one is not too short
+        let hops = (distance + COORDINATE_RANGE.1 - 1) / COORDINATE_RANGE.1;
+        for hop in 0..hops {
+            let step = distance * (hop + 1) / hops - distance * hop / hops;
+            self.go_to(step, 0, 0, speed).await?;
+        }
+        Ok(())
+    }
+
+    /// flip in the given direction. The SDK only supports Left, Right, Forward and Back, the
+    /// diagonal flips return `CommandError::Unsupported`.
+    ///
+    /// The drone refuses to flip, if the battery is below 50% or the light is too low.
+    /// The odometry is not changed, the drone ends up at the same position.
+    pub async fn flip(&self, direction: Flip) -> Result<(), CommandError> {
+        let dir = FlipDir::try_from(direction)?;
+        self.send_motion_command(flip_command(dir).into(), FLIP_TIME)
+            .await
+    }
+
+    /// enable the mission pad detection. (Tello EDU only)
+    ///
+    /// The detected pad and the position relative to it are part of the `CommandModeState`.
+    pub async fn mission_pads_on(&self) -> Result<(), CommandError> {
+        self.send_command("mon".into()).await
+    }
+    /// disable the mission pad detection. (Tello EDU only)
+    pub async fn mission_pads_off(&self) -> Result<(), CommandError> {
+        self.send_command("moff".into()).await
+    }
+    /// set the camera used for the mission pad detection. (Tello EDU only)
+    ///
+    /// Forward and both directions are detected with 10Hz, downward only with 20Hz.
+    pub async fn set_mission_pad_detection(
+        &self,
+        direction: MissionPadDirection,
+    ) -> Result<(), CommandError> {
+        let command = format!("mdirection {}", direction as u8);
+        self.send_command(command.into()).await
+    }
+
+    /// Go to a position, relative to the mission pad `mid` (1 - 8). (Tello EDU only)
+    ///
+    /// The odometry is not changed, the position of the pad is unknown.
+    /// - `x`, `y`, `z` 0 or (-)20 - (-)500 cm
+    /// - `speed` 10 - 100 centimeter per second
+    pub async fn go_to_pad(
+        &self,
+        x: i32,
+        y: i32,
+        z: i32,
+        speed: u8,
+        mid: u8,
+    ) -> Result<(), CommandError> {
+        let command = go_pad_command(x, y, z, speed, mid)?;
+        let duration = flight_time(path_length((x, y, z)), clamp_speed(speed));
+        self.send_motion_command(command.into(), duration).await
+    }
+
+    /// Moves in a curve, relative to the mission pad `mid` (1 - 8). (Tello EDU only)
+    ///
+    /// The odometry is not changed, the position of the pad is unknown.
+    /// - `speed` 10 - 60 centimeter per second
+    pub async fn curve_to_pad(&self, curve: Curve, mid: u8) -> Result<(), CommandError> {
+        let command = curve_pad_command(&curve, mid)?;
+        let duration = flight_time(curve.length(), clamp_curve_speed(curve.speed));
+        self.send_motion_command(command.into(), duration).await
+    }
+
+    /// Fly to the position `x`, `y`, `z` relative to the mission pad `mid1`, search for the
+    /// mission pad `mid2` there and rotate to `yaw` relative to it. (Tello EDU only)
+    ///
+    /// The odometry is not changed, the position of the pads is unknown.
+    pub async fn jump(&self, jump: Jump) -> Result<(), CommandError> {
+        let command = jump_command(&jump)?;
+        let duration = flight_time(
+            path_length((jump.x, jump.y, jump.z)),
+            clamp_speed(jump.speed),
+        );
+        self.send_motion_command(command.into(), duration).await
+    }
+
+    /// Execute the steps of the mission one after the other and return a report per step.
+    ///
+    /// Each step waits for the response of the drone. After a failed step, the remaining steps
+    /// are skipped, the drone hovers at its position (or stays on the ground). Use
+    /// `stop_handle().abort()` to abort the mission from another task.
+    pub async fn run_mission(&mut self, mission: &Mission) -> Vec<StepReport> {
+        self.aborted.store(false, Ordering::SeqCst);
+        let mut failed = false;
+        let mut reports = Vec::with_capacity(mission.steps().len());
+        for step in mission.steps() {
+            let result = if failed || self.aborted.load(Ordering::SeqCst) {
+                StepResult::Skipped
             } else {
-                self.odometry.down(z2_norm.abs() as u32);
+                match self.run_step(step).await {
+                    Err(e) => {
+                        failed = true;
+                        StepResult::Failed(e)
+                    }
+                    Ok(()) if self.aborted.load(Ordering::SeqCst) => StepResult::Aborted,
+                    Ok(()) => StepResult::Done,
+                }
+            };
+            reports.push(StepReport {
+                step: step.clone(),
+                result,
+            });
+        }
+        reports
+    }
+
+    async fn run_step(&mut self, step: &MissionStep) -> Result<(), CommandError> {
+        match *step {
+            MissionStep::TakeOff => self.take_off().await,
+            MissionStep::Land => self.land().await,
+            MissionStep::Up(cm) => self.up(cm).await,
+            MissionStep::Down(cm) => self.down(cm).await,
+            MissionStep::Left(cm) => self.left(cm).await,
+            MissionStep::Right(cm) => self.right(cm).await,
+            MissionStep::Forward(cm) => self.forward(cm).await,
+            MissionStep::Back(cm) => self.back(cm).await,
+            MissionStep::Cw(degree) => self.cw(degree).await,
+            MissionStep::Ccw(degree) => self.ccw(degree).await,
+            MissionStep::GoTo { x, y, z, speed } => self.go_to(x, y, z, speed).await,
+            MissionStep::WaitFor(duration) => {
+                // wait in slices, to notice an abort
+                let start = Instant::now();
+                while start.elapsed() < duration && !self.aborted.load(Ordering::SeqCst) {
+                    let left = duration - start.elapsed().min(duration);
+                    Self::pause(left.min(Duration::from_millis(50))).await;
+                }
+                Ok(())
             }
-            Ok(())
-        })
+        }
     }
 
-    /// set the speed for the forward, backward, right, left, up, down motion
-    pub async fn speed(&self, speed: u8) -> Result<(), String> {
-        // println!("speed");
-        let normalized_speed = speed.min(100).max(10);
+    /// set the speed for the forward, backward, right, left, up, down motion (10 - 100 cm/s)
+    pub async fn speed(&self, speed: u8) -> Result<(), CommandError> {
+        let normalized_speed = clamp_speed(speed);
         let command = format!("speed {}", normalized_speed);
-        self.send_command(command.into()).await
+        self.send_command(command.into()).await?;
+        self.speed.store(normalized_speed, Ordering::SeqCst);
+        Ok(())
+    }
+
+    /// time of a relative move of `distance` cm at the speed of `speed()`
+    fn move_time(&self, distance: u32) -> Duration {
+        flight_time(distance as f64, self.speed.load(Ordering::SeqCst))
+    }
+}
+
+#[test]
+pub fn test_clamp_move() {
+    assert_eq!(clamp_move(0), 20);
+    assert_eq!(clamp_move(19), 20);
+    assert_eq!(clamp_move(20), 20);
+    assert_eq!(clamp_move(500), 500);
+    assert_eq!(clamp_move(501), 500);
+}
+#[test]
+pub fn test_clamp_rotation() {
+    assert_eq!(clamp_rotation(0), 1);
+    assert_eq!(clamp_rotation(1), 1);
+    assert_eq!(clamp_rotation(3600), 3600);
+    assert_eq!(clamp_rotation(3601), 3600);
+}
+#[test]
+pub fn test_clamp_coordinate() {
+    assert_eq!(clamp_coordinate(0), 0);
+    assert_eq!(clamp_coordinate(1), 20);
+    assert_eq!(clamp_coordinate(-1), -20);
+    assert_eq!(clamp_coordinate(500), 500);
+    assert_eq!(clamp_coordinate(-500), -500);
+    assert_eq!(clamp_coordinate(501), 500);
+    assert_eq!(clamp_coordinate(-501), -500);
+}
+#[test]
+pub fn test_clamp_speed() {
+    assert_eq!(clamp_speed(9), 10);
+    assert_eq!(clamp_speed(10), 10);
+    assert_eq!(clamp_speed(100), 100);
+    assert_eq!(clamp_speed(101), 100);
+    assert_eq!(clamp_curve_speed(9), 10);
+    assert_eq!(clamp_curve_speed(60), 60);
+    assert_eq!(clamp_curve_speed(61), 60);
+}
+#[test]
+pub fn test_go_command() {
+    assert_eq!(go_command(-100, 0, 50, 30), "go -100 0 50 30");
+    assert_eq!(go_command(-30, 10, -600, 5), "go -30 20 -500 10");
+}
+#[test]
+pub fn test_curve_command() {
+    assert_eq!(
+        curve_command(&Curve {
+            p1: (-100, 50, 0),
+            p2: (-200, -10, 30),
+            speed: 80
+        }),
+        "curve -100 50 0 -200 -20 30 60"
+    );
+}
+#[test]
+pub fn test_motion_time() {
+    assert_eq!(flight_time(30.0, 10), Duration::from_secs(3));
+    assert_eq!(
+        flight_time(path_length((-30, 40, 0)), 50),
+        Duration::from_secs(1)
+    );
+    assert_eq!(
+        Duration::from_secs_f64(90.0 / TURN_RATE),
+        Duration::from_secs(3)
+    );
+}
+#[test]
+pub fn test_flip_command() {
+    assert_eq!(flip_command(FlipDir::Left), "flip l");
+    assert_eq!(flip_command(FlipDir::Right), "flip r");
+    assert_eq!(flip_command(FlipDir::Forward), "flip f");
+    assert_eq!(flip_command(FlipDir::Back), "flip b");
+    assert_eq!(FlipDir::try_from(Flip::Back), Ok(FlipDir::Back));
+    assert!(FlipDir::try_from(Flip::BackLeft).is_err());
+}
+
+/// fake drone, that drops the first command and responds `ok` to the next one
+#[cfg(test)]
+async fn retry_after_lost_response() {
+    let drone = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+    let addr = drone.local_addr().unwrap();
+    std::thread::spawn(move || {
+        let mut buf = [0u8; 64];
+        let _lost = drone.recv_from(&mut buf).unwrap();
+        let (_, from) = drone.recv_from(&mut buf).unwrap();
+        drone.send_to(b"ok", from).unwrap();
+    });
+
+    let mut command_mode = CommandMode::from(addr);
+    command_mode.set_command_timeout(Duration::from_millis(200));
+    command_mode.set_retries(1);
+    assert_eq!(command_mode.enable().await, Ok(()));
+}
+#[cfg(feature = "tokio_async")]
+#[tokio::test]
+pub async fn test_retry_after_lost_response() {
+    retry_after_lost_response().await
+}
+#[cfg(not(feature = "tokio_async"))]
+#[test]
+pub fn test_retry_after_lost_response() {
+    futures::executor::block_on(retry_after_lost_response())
+}
+/// fake drone, that answers the `stop`, the aborted first motion and the second motion.
+#[cfg(test)]
+async fn stopped_motion_skips_odometry() {
+    let drone = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+    let addr = drone.local_addr().unwrap();
+    std::thread::spawn(move || {
+        let mut buf = [0u8; 64];
+        let _aborted = drone.recv_from(&mut buf).unwrap();
+        let (_, from) = drone.recv_from(&mut buf).unwrap();
+        assert_eq!(&buf[..4], b"stop");
+        drone.send_to(b"ok", from).unwrap();
+        drone.send_to(b"error", from).unwrap();
+        let (_, from) = drone.recv_from(&mut buf).unwrap();
+        drone.send_to(b"ok", from).unwrap();
+    });
+
+    let mut command_mode = CommandMode::from(addr);
+    command_mode.set_command_timeout(Duration::from_secs(1));
+    let stop = command_mode.stop_handle();
+    #[cfg(feature = "tokio_async")]
+    tokio::spawn(async move {
+        sleep(Duration::from_millis(100)).await;
+        stop.stop().await.unwrap();
+    });
+    #[cfg(not(feature = "tokio_async"))]
+    std::thread::spawn(move || {
+        std::thread::sleep(Duration::from_millis(100));
+        futures::executor::block_on(stop.stop()).unwrap();
+    });
+
+    // the `ok` belongs to the stop, the `error` of the aborted motion is not returned
+    assert_eq!(command_mode.forward(100).await, Ok(()));
+    assert_eq!(command_mode.odometry, Odometry::default());
+
+    assert_eq!(command_mode.forward(100).await, Ok(()));
+    let mut expected = Odometry::default();
+    expected.forward(100);
+    assert_eq!(command_mode.odometry, expected);
+}
+#[cfg(feature = "tokio_async")]
+#[tokio::test]
+pub async fn test_stopped_motion_skips_odometry() {
+    stopped_motion_skips_odometry().await
+}
+#[cfg(not(feature = "tokio_async"))]
+#[test]
+pub fn test_stopped_motion_skips_odometry() {
+    futures::executor::block_on(stopped_motion_skips_odometry())
+}
+/// fake drone, that answers the `stop` after the next command is send
+#[cfg(test)]
+async fn late_stop_response() {
+    let drone = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+    let addr = drone.local_addr().unwrap();
+    std::thread::spawn(move || {
+        let mut buf = [0u8; 64];
+        let (len, _) = drone.recv_from(&mut buf).unwrap();
+        assert_eq!(&buf[..len], b"stop");
+        let (len, from) = drone.recv_from(&mut buf).unwrap();
+        assert_eq!(&buf[..len], b"battery?");
+        drone.send_to(b"ok", from).unwrap();
+        drone.send_to(b"87", from).unwrap();
+    });
+
+    let command_mode = CommandMode::from(addr);
+    assert_eq!(command_mode.stop().await, Ok(()));
+    assert_eq!(command_mode.get_battery().await, Ok(87));
+}
+#[cfg(feature = "tokio_async")]
+#[tokio::test]
+pub async fn test_late_stop_response() {
+    late_stop_response().await
+}
+#[cfg(not(feature = "tokio_async"))]
+#[test]
+pub fn test_late_stop_response() {
+    futures::executor::block_on(late_stop_response())
+}
+#[cfg(test)]
+use crate::mock::{MockConfig, MockDrone};
+/// a `MockDrone` and a `CommandMode` connected to it
+#[cfg(test)]
+fn mock_command_mode(config: MockConfig) -> (MockDrone, CommandMode) {
+    let mock = MockDrone::start(config).unwrap();
+    let command_mode = CommandMode::from(mock.addr());
+    (mock, command_mode)
+}
+/// the mock confirms every command
+#[cfg(test)]
+async fn motion_updates_odometry_once() {
+    let (_mock, mut command_mode) = mock_command_mode(MockConfig::default());
+    assert_eq!(command_mode.back(50).await, Ok(()));
+    assert_eq!(command_mode.odometry.y, -50.0);
+    // clamped to 20 by the command layer
+    assert_eq!(command_mode.forward(10).await, Ok(()));
+    assert_eq!(command_mode.odometry.y, -30.0);
+}
+#[cfg(feature = "tokio_async")]
+#[tokio::test]
+pub async fn test_motion_updates_odometry_once() {
+    motion_updates_odometry_once().await
+}
+#[cfg(not(feature = "tokio_async"))]
+#[test]
+pub fn test_motion_updates_odometry_once() {
+    futures::executor::block_on(motion_updates_odometry_once())
+}
+/// the mock confirms the rotation, the `go` and the `curve`
+#[cfg(test)]
+async fn go_to_updates_odometry() {
+    let (_mock, mut command_mode) = mock_command_mode(MockConfig::default());
+    assert_eq!(command_mode.cw(90).await, Ok(()));
+    // forward in the body frame is right in the odometry frame
+    assert_eq!(command_mode.go_to(100, 0, 50, 30).await, Ok(()));
+    let odometry = &command_mode.odometry;
+    assert_eq!(
+        (odometry.x.round(), odometry.y.round(), odometry.z),
+        (100.0, 0.0, 50.0)
+    );
+    // ends at the second point: 60 forward, 40 to the left and 20 up
+    let curve = Curve {
+        p1: (20, 20, 0),
+        p2: (60, 40, 20),
+        speed: 30,
+    };
+    assert_eq!(command_mode.curve(curve).await, Ok(()));
+    let odometry = &command_mode.odometry;
+    assert_eq!(
+        (odometry.x.round(), odometry.y.round(), odometry.z),
+        (160.0, 40.0, 70.0)
+    );
+}
+#[cfg(feature = "tokio_async")]
+#[tokio::test]
+pub async fn test_go_to_updates_odometry() {
+    go_to_updates_odometry().await
+}
+#[cfg(not(feature = "tokio_async"))]
+#[test]
+pub fn test_go_to_updates_odometry() {
+    futures::executor::block_on(go_to_updates_odometry())
+}
+/// the mock confirms and collects the commands
+#[cfg(test)]
+async fn go_home() {
+    let (mock, mut command_mode) = mock_command_mode(MockConfig::default());
+    command_mode.forward(500).await.unwrap();
+    command_mode.forward(100).await.unwrap();
+    command_mode.cw(90).await.unwrap();
+    command_mode.forward(80).await.unwrap();
+    assert_eq!(command_mode.go_home(20).await, Ok(()));
+    assert_eq!(
+        mock.commands()[4..],
+        ["cw 98", "go 302 0 0 20", "go 303 0 0 20"]
+    );
+    assert!(command_mode.odometry.distance_to(0.0, 0.0) < 5.0);
+
+    // close enough, the drone stays in place
+    assert_eq!(command_mode.go_home(20).await, Ok(()));
+    assert_eq!(mock.commands().len(), 7);
+}
+#[cfg(feature = "tokio_async")]
+#[tokio::test]
+pub async fn test_go_home() {
+    go_home().await
+}
+#[cfg(not(feature = "tokio_async"))]
+#[test]
+pub fn test_go_home() {
+    futures::executor::block_on(go_home())
+}
+/// the mock rejects every command
+#[cfg(test)]
+async fn failed_motion_keeps_odometry() {
+    let config = MockConfig::default().with_rule("", "error Motor stop");
+    let (_mock, mut command_mode) = mock_command_mode(config);
+    assert!(command_mode.take_off().await.is_err());
+    assert!(command_mode.forward(100).await.is_err());
+    assert_eq!(
+        (command_mode.odometry.y, command_mode.odometry.z),
+        (0.0, 0.0)
+    );
+    assert_eq!(command_mode.odometry.pending_moves(), ["forward 100"]);
+}
+#[cfg(feature = "tokio_async")]
+#[tokio::test]
+pub async fn test_failed_motion_keeps_odometry() {
+    failed_motion_keeps_odometry().await
+}
+#[cfg(not(feature = "tokio_async"))]
+#[test]
+pub fn test_failed_motion_keeps_odometry() {
+    futures::executor::block_on(failed_motion_keeps_odometry())
+}
+/// the mock rejects the `cw` command
+#[cfg(test)]
+async fn run_mission() {
+    let (mock, mut command_mode) =
+        mock_command_mode(MockConfig::default().with_rule("cw", "error"));
+    let mission = Mission::new()
+        .take_off()
+        .forward(100)
+        .wait_for(Duration::from_millis(10))
+        .land();
+    let results: Vec<_> = command_mode
+        .run_mission(&mission)
+        .await
+        .into_iter()
+        .map(|r| r.result)
+        .collect();
+    assert_eq!(results, vec![StepResult::Done; 4]);
+    let mut expected = Odometry::default();
+    expected.up(100);
+    expected.forward(100);
+    assert_eq!(command_mode.odometry, expected);
+
+    let mission = Mission::new().up(30).cw(90).land();
+    let results: Vec<_> = command_mode
+        .run_mission(&mission)
+        .await
+        .into_iter()
+        .map(|r| r.result)
+        .collect();
+    assert_eq!(
+        results,
+        vec![
+            StepResult::Done,
+            StepResult::Failed(CommandError::Drone("error".into())),
+            StepResult::Skipped
+        ]
+    );
+    assert_eq!(
+        mock.commands(),
+        vec!["takeoff", "forward 100", "land", "up 30", "cw 90"]
+    );
+}
+#[cfg(feature = "tokio_async")]
+#[tokio::test]
+pub async fn test_run_mission() {
+    run_mission().await
+}
+#[cfg(not(feature = "tokio_async"))]
+#[test]
+pub fn test_run_mission() {
+    futures::executor::block_on(run_mission())
+}
+/// the mission is aborted while waiting
+#[cfg(test)]
+async fn abort_mission() {
+    let drone = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+    let addr = drone.local_addr().unwrap();
+    let fake_drone = std::thread::spawn(move || {
+        let mut buf = [0u8; 64];
+        let (len, _) = drone.recv_from(&mut buf).unwrap();
+        String::from_utf8_lossy(&buf[..len]).to_string()
+    });
+
+    let mut command_mode = CommandMode::from(addr);
+    let stop = command_mode.stop_handle();
+    #[cfg(feature = "tokio_async")]
+    tokio::spawn(async move {
+        sleep(Duration::from_millis(100)).await;
+        stop.abort().await.unwrap();
+    });
+    #[cfg(not(feature = "tokio_async"))]
+    std::thread::spawn(move || {
+        std::thread::sleep(Duration::from_millis(100));
+        futures::executor::block_on(stop.abort()).unwrap();
+    });
+
+    let mission = Mission::new().wait_for(Duration::from_secs(5)).forward(100);
+    let start = Instant::now();
+    let results: Vec<_> = command_mode
+        .run_mission(&mission)
+        .await
+        .into_iter()
+        .map(|r| r.result)
+        .collect();
+    assert!(start.elapsed() < Duration::from_secs(1));
+    assert_eq!(results, vec![StepResult::Aborted, StepResult::Skipped]);
+    assert_eq!(fake_drone.join().unwrap(), "stop");
+}
+#[cfg(feature = "tokio_async")]
+#[tokio::test]
+pub async fn test_abort_mission() {
+    abort_mission().await
+}
+#[cfg(not(feature = "tokio_async"))]
+#[test]
+pub fn test_abort_mission() {
+    futures::executor::block_on(abort_mission())
+}
+/// fake drone with a slow `forward` command
+#[cfg(test)]
+async fn keepalive() {
+    let drone = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+    drone
+        .set_read_timeout(Some(Duration::from_millis(700)))
+        .unwrap();
+    let addr = drone.local_addr().unwrap();
+    let fake_drone = std::thread::spawn(move || {
+        let mut buf = [0u8; 64];
+        let mut received = vec![];
+        while let Ok((len, from)) = drone.recv_from(&mut buf) {
+            let command = String::from_utf8_lossy(&buf[..len]).to_string();
+            if command == "battery?" {
+                drone.send_to(b"87", from).unwrap();
+            } else {
+                std::thread::sleep(Duration::from_millis(500));
+                // nothing else is send, while the drone is moving
+                drone.set_nonblocking(true).unwrap();
+                assert!(drone.recv_from(&mut buf).is_err());
+                drone.set_nonblocking(false).unwrap();
+                drone.send_to(b"ok", from).unwrap();
+            }
+            received.push(command);
+        }
+        received
+    });
+
+    let mut command_mode = CommandMode::from(addr);
+    command_mode.start_keepalive(Duration::from_millis(200));
+    CommandMode::pause(Duration::from_millis(300)).await;
+    assert_eq!(command_mode.forward(20).await, Ok(()));
+    CommandMode::pause(Duration::from_millis(450)).await;
+    command_mode.stop_keepalive();
+
+    let received = fake_drone.join().unwrap();
+    let forward = received.iter().position(|c| c == "forward 20").unwrap();
+    assert!(forward > 0);
+    assert!(received.len() > forward + 1);
+    assert!(received
+        .iter()
+        .all(|c| c == "battery?" || c == "forward 20"));
+}
+#[cfg(feature = "tokio_async")]
+#[tokio::test]
+pub async fn test_keepalive() {
+    keepalive().await
+}
+#[cfg(not(feature = "tokio_async"))]
+#[test]
+pub fn test_keepalive() {
+    futures::executor::block_on(keepalive())
+}
+/// `stop_rc_loop()` ends the `rc` commands, while the rc_state is still alive
+#[cfg(test)]
+async fn stop_rc_loop() {
+    let mock = MockDrone::start(MockConfig::default()).unwrap();
+    let mut command_mode = CommandMode::from(mock.addr());
+    let rc_state = Arc::new(Mutex::new(RCState::default()));
+    assert_eq!(command_mode.start_rc_loop(&rc_state), Ok(()));
+    CommandMode::pause(Duration::from_millis(200)).await;
+    command_mode.stop_rc_loop();
+    CommandMode::pause(Duration::from_millis(100)).await;
+    let sent = mock.commands().len();
+    assert!(sent > 0);
+    CommandMode::pause(Duration::from_millis(200)).await;
+    assert_eq!(mock.commands().len(), sent);
+    assert!(mock.commands().iter().all(|c| c.starts_with("rc ")));
+}
+#[cfg(feature = "tokio_async")]
+#[tokio::test]
+pub async fn test_stop_rc_loop() {
+    stop_rc_loop().await
+}
+#[cfg(not(feature = "tokio_async"))]
+#[test]
+pub fn test_stop_rc_loop() {
+    futures::executor::block_on(stop_rc_loop())
+}
+/// the mock expects the `port` command after `command`
+#[cfg(test)]
+async fn enable_sends_ports() {
+    let mock = MockDrone::start(MockConfig::default()).unwrap();
+    let command_mode = CommandMode::from(mock.addr())
+        .with_status_port(free_port())
+        .with_video_port(free_port());
+    assert_eq!(command_mode.enable().await, Ok(()));
+    let commands = mock.commands();
+    assert_eq!(commands[0], "command");
+    assert_eq!(
+        commands[1],
+        format!(
+            "port {} {}",
+            command_mode.status_port, command_mode.video_port
+        )
+    );
+}
+#[cfg(feature = "tokio_async")]
+#[tokio::test]
+pub async fn test_enable_sends_ports() {
+    enable_sends_ports().await
+}
+#[cfg(not(feature = "tokio_async"))]
+#[test]
+pub fn test_enable_sends_ports() {
+    futures::executor::block_on(enable_sends_ports())
+}
+/// a port, that the OS just handed out and that is free again
+#[cfg(test)]
+fn free_port() -> u16 {
+    let socket = std::net::UdpSocket::bind(("0.0.0.0", 0)).unwrap();
+    socket.local_addr().unwrap().port()
+}
+/// bind the port as soon as it is released, the receiver tasks are aborted asynchronously
+#[cfg(test)]
+fn bind_released(port: u16) -> std::io::Result<std::net::UdpSocket> {
+    let deadline = Instant::now() + Duration::from_secs(1);
+    loop {
+        match std::net::UdpSocket::bind(("0.0.0.0", port)) {
+            Err(_) if Instant::now() < deadline => std::thread::yield_now(),
+            res => return res,
+        }
+    }
+}
+/// the receivers of a dropped CommandMode must release their ports
+#[cfg(test)]
+async fn drop_releases_ports() {
+    let (status_port, video_port) = (free_port(), free_port());
+    for _ in 0..2 {
+        let mut command_mode = CommandMode::from(SocketAddr::from(([127, 0, 0, 1], 8889)))
+            .with_status_port(status_port)
+            .with_video_port(video_port);
+        let _state = command_mode.state_receiver().unwrap();
+        let _video = command_mode.video_receiver().unwrap();
+        drop(command_mode);
+
+        bind_released(status_port).unwrap();
+        bind_released(video_port).unwrap();
+    }
+}
+#[cfg(feature = "tokio_async")]
+#[tokio::test(flavor = "multi_thread")]
+pub async fn test_drop_releases_ports() {
+    drop_releases_ports().await
+}
+#[cfg(not(feature = "tokio_async"))]
+#[test]
+pub fn test_drop_releases_ports() {
+    futures::executor::block_on(drop_releases_ports())
+}
+/// the rc loop of a dropped CommandMode stops, while the rc_state is still alive
+#[cfg(test)]
+async fn drop_stops_rc_loop() {
+    let mock = MockDrone::start(MockConfig::default()).unwrap();
+    let mut command_mode = CommandMode::from(mock.addr());
+    let rc_state = Arc::new(Mutex::new(RCState::default()));
+    assert_eq!(command_mode.start_rc_loop(&rc_state), Ok(()));
+    CommandMode::pause(Duration::from_millis(200)).await;
+    drop(command_mode);
+    CommandMode::pause(Duration::from_millis(100)).await;
+    let sent = mock.commands().len();
+    assert!(sent > 0);
+    CommandMode::pause(Duration::from_millis(200)).await;
+    assert_eq!(mock.commands().len(), sent);
+}
+#[cfg(feature = "tokio_async")]
+#[tokio::test]
+pub async fn test_drop_stops_rc_loop() {
+    drop_stops_rc_loop().await
+}
+#[cfg(not(feature = "tokio_async"))]
+#[test]
+pub fn test_drop_stops_rc_loop() {
+    futures::executor::block_on(drop_stops_rc_loop())
+}
+/// `video_on()` binds the video port through a shared reference
+#[cfg(test)]
+async fn video_on_binds_video_port() {
+    let mock = MockDrone::start(MockConfig::default()).unwrap();
+    let mut command_mode = CommandMode::from(mock.addr()).with_video_port(free_port());
+    let shared = &command_mode;
+    assert_eq!(shared.video_on().await, Ok(()));
+    assert!(shared.video_recv_buffer().is_some());
+    assert_eq!(mock.commands(), ["streamon"]);
+    assert!(matches!(command_mode.video_receiver(), Ok(Some(_))));
+    assert!(matches!(command_mode.video_receiver(), Ok(None)));
+}
+#[cfg(feature = "tokio_async")]
+#[tokio::test]
+pub async fn test_video_on_binds_video_port() {
+    video_on_binds_video_port().await
+}
+#[cfg(not(feature = "tokio_async"))]
+#[test]
+pub fn test_video_on_binds_video_port() {
+    futures::executor::block_on(video_on_binds_video_port())
+}
+/// a port used by another tool is reported to the caller
+#[cfg(test)]
+async fn receiver_port_in_use() {
+    let other_tool = std::net::UdpSocket::bind(("0.0.0.0", 0)).unwrap();
+    let port = other_tool.local_addr().unwrap().port();
+    let mut command_mode = CommandMode::from(SocketAddr::from(([127, 0, 0, 1], 8889)))
+        .with_status_port(port)
+        .with_video_port(port);
+    assert!(matches!(
+        command_mode.state_receiver(),
+        Err(CommandError::Socket(_))
+    ));
+    assert!(matches!(
+        command_mode.video_receiver(),
+        Err(CommandError::Socket(_))
+    ));
+
+    // the port is bound, once it is free again
+    drop(other_tool);
+    assert!(matches!(command_mode.state_receiver(), Ok(Some(_))));
+    assert!(matches!(command_mode.state_receiver(), Ok(None)));
+}
+#[cfg(feature = "tokio_async")]
+#[tokio::test]
+pub async fn test_receiver_port_in_use() {
+    receiver_port_in_use().await
+}
+#[cfg(not(feature = "tokio_async"))]
+#[test]
+pub fn test_receiver_port_in_use() {
+    futures::executor::block_on(receiver_port_in_use())
+}
+/// send two short frames of the SDK stream to the video port
+#[cfg(test)]
+fn send_sdk_frames(port: u16) {
+    let camera = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+    for frame in &[[0u8, 0, 0, 1, 7], [0, 0, 0, 1, 8]] {
+        camera.send_to(frame, ("127.0.0.1", port)).unwrap();
+    }
+}
+#[cfg(feature = "tokio_async")]
+#[tokio::test]
+pub async fn test_video_frames() {
+    use tokio_stream::StreamExt;
+    let port = free_port();
+    let mut command_mode = CommandMode::from(SocketAddr::from(([127, 0, 0, 1], 8889)))
+        .with_video_port(port)
+        .with_video_recv_buffer(64 * 1024);
+    assert_eq!(command_mode.video_recv_buffer(), None);
+    let mut frames = command_mode.video_frames(4).unwrap().unwrap();
+    assert!(command_mode.video_recv_buffer().unwrap() >= 64 * 1024);
+    assert!(command_mode.video_frames(4).unwrap().is_none());
+    send_sdk_frames(port);
+
+    let next = tokio::time::timeout(Duration::from_secs(1), frames.next())
+        .await
+        .unwrap();
+    assert_eq!(next, Some((0, vec![0, 0, 0, 1, 7].into())));
+    let next = tokio::time::timeout(Duration::from_secs(1), frames.next())
+        .await
+        .unwrap();
+    assert_eq!(next, Some((1, vec![0, 0, 0, 1, 8].into())));
+    assert_eq!(frames.dropped_frames(), 0);
+}
+#[cfg(not(feature = "tokio_async"))]
+#[test]
+pub fn test_video_frames_iter() {
+    let port = free_port();
+    let mut command_mode = CommandMode::from(SocketAddr::from(([127, 0, 0, 1], 8889)))
+        .with_video_port(port)
+        .with_video_recv_buffer(64 * 1024);
+    assert_eq!(command_mode.video_recv_buffer(), None);
+    let mut frames = command_mode.video_frames_iter(4).unwrap().unwrap();
+    assert!(command_mode.video_recv_buffer().unwrap() >= 64 * 1024);
+    assert!(command_mode.video_frames_iter(4).unwrap().is_none());
+    send_sdk_frames(port);
+
+    assert_eq!(frames.next(), Some((0, vec![0, 0, 0, 1, 7].into())));
+    assert_eq!(frames.next(), Some((1, vec![0, 0, 0, 1, 8].into())));
+    assert_eq!(frames.dropped_frames(), 0);
+
+    // the iterator ends with the command mode
+    drop(command_mode);
+    assert_eq!(frames.next(), None);
+}
+#[test]
+pub fn test_rc_command() {
+    assert_eq!(rc_command(0, 100, -100, 50), "rc 0 100 -100 50");
+    assert_eq!(rc_command(127, -128, 0, 0), "rc 100 -100 0 0");
+    let mut rc_state = RCState::default();
+    rc_state.go_left();
+    rc_state.go_forward_back(0.5);
+    rc_state.go_up();
+    assert_eq!(rc_command_from_state(&mut rc_state), "rc -100 50 100 0");
+}
+#[test]
+pub fn test_parse_time() {
+    assert_eq!(parse_time("0s"), Some(Duration::from_secs(0)));
+    assert_eq!(parse_time("42s"), Some(Duration::from_secs(42)));
+    assert_eq!(parse_time("42"), Some(Duration::from_secs(42)));
+    assert_eq!(parse_time("error"), None);
+}
+#[test]
+pub fn test_mission_pad_commands() {
+    assert_eq!(
+        go_pad_command(100, 0, 50, 30, 2),
+        Ok("go 100 0 50 30 m2".to_string())
+    );
+    let curve = Curve {
+        p1: (20, 20, 20),
+        p2: (60, 40, 0),
+        speed: 80,
+    };
+    assert_eq!(
+        curve_pad_command(&curve, 8),
+        Ok("curve 20 20 20 60 40 0 60 m8".to_string())
+    );
+    let jump = Jump {
+        x: 100,
+        y: 0,
+        z: 80,
+        speed: 30,
+        yaw: 400,
+        mid1: 1,
+        mid2: 2,
+    };
+    assert_eq!(
+        jump_command(&jump),
+        Ok("jump 100 0 80 30 360 m1 m2".to_string())
+    );
+    // an invalid pad is not clamped to another one
+    assert!(matches!(
+        curve_pad_command(&curve, 9),
+        Err(CommandError::InvalidParameter(_))
+    ));
+    assert!(matches!(
+        jump_command(&Jump { mid2: 0, ..jump }),
+        Err(CommandError::InvalidParameter(_))
+    ));
+}
+#[test]
+pub fn test_ext_commands() {
+    assert_eq!(ext_led_command(255, 0, 10), "EXT led 255 0 10");
+    assert_eq!(
+        ext_led_blink_command(2.5, (255, 0, 0), (0, 0, 255)),
+        Ok("EXT led bl 2.5 255 0 0 0 0 255".to_string())
+    );
+    assert!(ext_led_blink_command(10.5, (0, 0, 0), (0, 0, 0)).is_err());
+    assert!(ext_led_blink_command(f32::NAN, (0, 0, 0), (0, 0, 0)).is_err());
+    assert_eq!(
+        ext_text_command("Hi TT", ScrollDirection::Left, MatrixColor::Purple, 1.0),
+        Ok("EXT mled l p 1 Hi TT".to_string())
+    );
+    let text = |text: &str| ext_text_command(text, ScrollDirection::Up, MatrixColor::Red, 0.5);
+    assert!(text("").is_err());
+    assert!(text("ä").is_err());
+    assert!(text(&"x".repeat(70)).is_ok());
+    assert!(text(&"x".repeat(71)).is_err());
+    assert!(ext_text_command("Hi", ScrollDirection::Up, MatrixColor::Red, 3.0).is_err());
+}
+#[test]
+pub fn test_drone_error() {
+    assert_eq!(
+        drone_error("motoron", "error Not support".into()),
+        CommandError::UnsupportedFirmware("motoron".into())
+    );
+    assert_eq!(
+        drone_error("motoron", "error Not support\r\n".into()),
+        CommandError::UnsupportedFirmware("motoron".into())
+    );
+    assert_eq!(
+        drone_error("flip l", "error Not enough battery".into()),
+        CommandError::Drone("error Not enough battery".into())
+    );
+}
+#[test]
+pub fn test_parse_tof() {
+    assert_eq!(parse_tof("tof 345"), Some(345));
+    assert_eq!(parse_tof("tof 8192"), Some(8192));
+    assert_eq!(parse_tof("345"), None);
+    assert_eq!(parse_tof("tof"), None);
+}
+#[test]
+pub fn test_parse_edu_state() {
+    let state = b"mid:3;x:-20;y:45;z:80;mpry:1,-2,90;pitch:0;roll:0;yaw:-45;vgx:0;vgy:0;vgz:0;\
+templ:69;temph:70;tof:10;h:0;bat:92;baro:548.55;time:0;agx:-5.00;agy:0.00;agz:-998.00;\r\n";
+    let state = CommandModeState::try_from(&state[..]).unwrap();
+    assert_eq!(state.mid, 3);
+    assert_eq!((state.x, state.y, state.z), (-20, 45, 80));
+    assert_eq!(state.mpry, (1, -2, 90));
+    assert_eq!(state.bat, 92);
+
+    let state = b"pitch:1;unknown:7;yaw:-45;bat:80;\r\n";
+    let state = CommandModeState::try_from(&state[..]).unwrap();
+    assert_eq!((state.pitch, state.yaw, state.bat), (1, -45, 80));
+}
+#[test]
+pub fn test_parse_malformed_state() {
+    // truncated packet
+    let state = CommandModeState::try_from(&b"pitch:2;roll:-1;yaw:30;bat:8"[..]).unwrap();
+    assert_eq!(
+        (state.pitch, state.roll, state.yaw, state.bat),
+        (2, -1, 30, 8)
+    );
+    let state = CommandModeState::try_from(&b"pitch:2;baro:;bat:x1;time:"[..]).unwrap();
+    assert_eq!((state.pitch, state.baro, state.bat), (2, 0.0, 0));
+
+    // padded with the stale bytes of the receive buffer
+    let mut buf = [0u8; 150];
+    buf[..20].copy_from_slice(b"bat:87;agz:-998.00\r\n");
+    let state = CommandModeState::try_from(&buf).unwrap();
+    assert_eq!((state.bat, state.agz), (87, -998.0));
+
+    // out of order fields
+    let state = CommandModeState::try_from(&b"agz:-1.00;h:40;tof:52;pitch:7;\r\n"[..]).unwrap();
+    assert_eq!(
+        (state.pitch, state.tof, state.h, state.agz),
+        (7, 52, 40, -1.0)
+    );
+}
+#[test]
+pub fn test_parse_random_state() {
+    // fields of the state, mixed with random ascii
+    let tokens: &[&[u8]] = &[
+        b"mid:", b"x:", b"mpry:", b"pitch:", b"yaw:", b"bat:", b"baro:", b"agz:", b"wifi:", b";",
+        b":", b",", b"-", b".", b"1", b"99", b"-100", b"\r\n",
+    ];
+    // xorshift, the test has to be reproducible
+    let mut seed = 0x2545_f491_u32;
+    let mut next = move || {
+        seed ^= seed << 13;
+        seed ^= seed >> 17;
+        seed ^= seed << 5;
+        seed as usize
+    };
+    for _ in 0..10_000 {
+        let mut data = Vec::new();
+        for _ in 0..next() % 40 {
+            match next() % 3 {
+                0 => data.push((next() % 0x80) as u8),
+                _ => data.extend_from_slice(tokens[next() % tokens.len()]),
+            }
+        }
+        assert!(CommandModeState::try_from(&data[..]).is_ok());
     }
+
+    // a firmware with a prefix of the wifi signal
+    let state =
+        CommandModeState::try_from(&b"wifi:90;mid:-1;x:0;y:0;z:0;mpry:0,0,0;bat:55;"[..]).unwrap();
+    assert_eq!((state.mid, state.bat), (-1, 55));
+    assert_eq!(state.extras["wifi"], "90");
+}
+#[test]
+pub fn test_parse_state_with_reused_buffer() {
+    let long = b"pitch:1;roll:2;yaw:3;baro:548.55;time:0;agx:-5.00;agy:0.00;agz:-998.00;\r\n";
+    let short = b"pitch:4;agz:-1.00;\r\n";
+    let mut buf = [0u8; 256];
+
+    buf[..long.len()].copy_from_slice(long);
+    let state = CommandModeState::try_from(&buf[..long.len()]).unwrap();
+    assert_eq!((state.pitch, state.yaw, state.agz), (1, 3, -998.0));
+
+    buf[..short.len()].copy_from_slice(short);
+    let state = CommandModeState::try_from(&buf[..short.len()]).unwrap();
+    assert_eq!((state.pitch, state.yaw, state.agz), (4, 0, -1.0));
+}
+#[test]
+pub fn test_parse_state_extras() {
+    let state = CommandModeState::try_from(&b"pitch:1;yaw:-45;bat:80;\r\n"[..]).unwrap();
+    assert_eq!(state.raw, "pitch:1;yaw:-45;bat:80;");
+    assert!(state.extras.is_empty());
+    // a Tello without mission pads does not report the `mid`
+    assert_eq!(state.mid, -1);
+
+    let state = CommandModeState::try_from(&b"pitch:1;wind:3;fw:next;\r\n"[..]).unwrap();
+    assert_eq!(state.pitch, 1);
+    assert_eq!(state.extras.len(), 2);
+    assert_eq!(state.extras["wind"], "3");
+    assert_eq!(state.extras["fw"], "next");
+}
+#[test]
+pub fn test_state_units() {
+    let state = b"vgx:3;vgy:-2;vgz:1;agx:-5.00;agy:0.00;agz:-998.00;\r\n";
+    let state = CommandModeState::try_from(&state[..]).unwrap();
+    assert_eq!(state.speed_cms(), (3, -2, 1));
+
+    let (x, y, z) = state.accel_mps2();
+    assert!((x - -0.049_033).abs() < 1e-5);
+    assert_eq!(y, 0.0);
+    assert!((z - -9.786_8).abs() < 1e-3);
+    assert!((state.total_accel() - 9.786_9).abs() < 1e-3);
+
+    // 1 g
+    let state = CommandModeState {
+        agz: 1000.0,
+        ..CommandModeState::default()
+    };
+    assert_eq!(state.accel_mps2(), (0.0, 0.0, STANDARD_GRAVITY));
+    assert_eq!(state.total_accel(), STANDARD_GRAVITY);
+}
+#[test]
+pub fn test_compare_states() {
+    let state = b"pitch:1;roll:2;yaw:3;bat:80;\r\n";
+    let first = CommandModeState::try_from(&state[..]).unwrap();
+    assert_eq!(first, CommandModeState::try_from(&state[..]).unwrap());
+
+    let other = CommandModeState::try_from(&b"pitch:1;roll:2;yaw:4;bat:80;\r\n"[..]).unwrap();
+    assert_ne!(first, other);
+    let other = CommandModeState::try_from(&b"pitch:1;roll:2;yaw:3;bat:80;wind:1;\r\n"[..]);
+    assert_ne!(first, other.unwrap());
 }
@@ -0,0 +1,399 @@
+use std::fmt;
+use std::io;
+use std::net::{SocketAddr, UdpSocket};
+use std::thread;
+use std::time::Duration;
+
+use crate::{CommandMode, Drone, VideoBitrate};
+
+/// The two protocols of the tello drone.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ProtocolMode {
+    /// the non-public binary protocol, used by the native app. (`Drone`)
+    Binary,
+    /// the text based SDK protocol. (`CommandMode`)
+    Sdk,
+}
+
+/// Preferences for `connect_auto`.
+#[derive(Debug, Clone)]
+pub struct ConnectPrefs {
+    /// the mode to use, if the drone answers on both probes
+    pub prefer: ProtocolMode,
+    /// how long to wait for the answer of each probe
+    pub probe_timeout: Duration,
+    /// video port to request, when the binary drone gets connected
+    pub video_port: u16,
+    /// settings of the binary drone, see `Drone::set_connect_profile()`
+    pub profile: ConnectProfile,
+}
+
+impl Default for ConnectPrefs {
+    fn default() -> Self {
+        Self {
+            prefer: ProtocolMode::Binary,
+            probe_timeout: Duration::from_millis(1000),
+            video_port: 11111,
+            profile: ConnectProfile::default(),
+        }
+    }
+}
+
+/// a request of the `ConnectProfile`, the drone replies with the `PackageData`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SetupQuery {
+    Version,
+    LoaderVersion,
+    AltLimit,
+    AttAngle,
+    BatteryThreshold,
+    Region,
+}
+
+/// Settings and queries, the `Drone` sends after the third flight data of a connection (see
+/// `Drone::set_connect_profile()`). The settings are send first, then the queries.
+///
+/// The default profile sets the bitrate `VideoBitrate::Mbps3` and the exposure 2, and queries
+/// the version, alt limit, battery threshold, att angle and region. `ConnectProfile::new()`
+/// changes nothing.
+///
+/// # Example
+///
+/// ```no_run
+/// # use tello::{ConnectProfile, Drone, VideoBitrate};
+/// # let mut drone = Drone::new("192.168.10.1:8889");
+/// let profile = ConnectProfile::default()
+///     .with_bitrate(VideoBitrate::Auto)
+///     .with_exposure(0);
+/// drone.set_connect_profile(profile);
+/// drone.connect(11111);
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConnectProfile {
+    pub(crate) bitrate: Option<VideoBitrate>,
+    pub(crate) exposure: Option<u8>,
+    pub(crate) alt_limit: Option<u8>,
+    pub(crate) att_angle: Option<f32>,
+    pub(crate) queries: Vec<SetupQuery>,
+}
+
+impl Default for ConnectProfile {
+    fn default() -> Self {
+        Self::new()
+            .with_bitrate(VideoBitrate::Mbps3)
+            .with_exposure(2)
+            .with_queries(&[
+                SetupQuery::Version,
+                SetupQuery::AltLimit,
+                SetupQuery::BatteryThreshold,
+                SetupQuery::AttAngle,
+                SetupQuery::Region,
+            ])
+    }
+}
+
+impl ConnectProfile {
+    /// a profile without settings and queries
+    pub fn new() -> Self {
+        Self {
+            bitrate: None,
+            exposure: None,
+            alt_limit: None,
+            att_angle: None,
+            queries: Vec::new(),
+        }
+    }
+    /// see `Drone::set_video_bitrate_preset()`
+    pub fn with_bitrate(mut self, bitrate: VideoBitrate) -> Self {
+        self.bitrate = Some(bitrate);
+        self
+    }
+    /// see `Drone::set_exposure()`
+    pub fn with_exposure(mut self, level: u8) -> Self {
+        self.exposure = Some(level);
+        self
+    }
+    /// see `Drone::set_alt_limit()`
+    pub fn with_alt_limit(mut self, limit: u8) -> Self {
+        self.alt_limit = Some(limit);
+        self
+    }
+    /// see `Drone::set_att_angle()`
+    pub fn with_att_angle(mut self, degrees: f32) -> Self {
+        self.att_angle = Some(degrees);
+        self
+    }
+    /// replace the queries
+    pub fn with_queries(mut self, queries: &[SetupQuery]) -> Self {
+        self.queries = queries.to_vec();
+        self
+    }
+}
+
+/// Reason, why a single probe did not detect the drone.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ProbeFailure {
+    /// no answer within the `probe_timeout`
+    Timeout,
+    /// the drone answered, but not with the expected response
+    UnexpectedResponse(String),
+    /// the local socket could not be created or used
+    Socket(String),
+}
+
+impl fmt::Display for ProbeFailure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ProbeFailure::Timeout => write!(f, "timeout"),
+            ProbeFailure::UnexpectedResponse(res) => write!(f, "unexpected response {:?}", res),
+            ProbeFailure::Socket(e) => write!(f, "socket error {}", e),
+        }
+    }
+}
+
+/// Error of `connect_auto`
+#[derive(Debug)]
+pub enum ConnectError {
+    /// the given address is not a valid `<ip>:<port>`
+    InvalidAddress(String),
+    /// neither the binary nor the SDK probe got an answer from the drone
+    NoResponse {
+        binary: ProbeFailure,
+        sdk: ProbeFailure,
+    },
+    /// the sockets of the detected client could not be bound, e.g. the port 8889 is in use
+    Socket(io::Error),
+}
+
+impl fmt::Display for ConnectError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConnectError::InvalidAddress(addr) => write!(f, "invalid address {:?}", addr),
+            ConnectError::NoResponse { binary, sdk } => write!(
+                f,
+                "drone did not respond (binary probe: {}, sdk probe: {})",
+                binary, sdk
+            ),
+            ConnectError::Socket(e) => write!(f, "socket error {}", e),
+        }
+    }
+}
+
+impl std::error::Error for ConnectError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ConnectError::Socket(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+/// The client, matching the detected protocol of the drone.
+#[derive(Debug)]
+pub enum Client {
+    /// both clients are boxed, they are large and of very different size
+    Binary(Box<Drone>),
+    Sdk(Box<CommandMode>),
+}
+
+/// Probe the drone on both protocols and return the matching client.
+///
+/// The `conn_req` of the binary protocol and the SDK `command` are send at the same time.
+/// When both probes are answered, the `prefs.prefer` mode is used.
+///
+/// - `Client::Binary`: the drone is already connected with the `prefs.video_port`.
+/// - `Client::Sdk`: the drone is already in the command mode, `enable()` is not required.
+///
+/// If you are using the `tokio_async` feature, you have to call this function in the tokio runtime.
+///
+/// # Example
+///
+/// ```no_run
+/// use tello::{connect_auto, Client, ConnectPrefs};
+///
+/// match connect_auto("192.168.10.1:8889", ConnectPrefs::default()) {
+///     Ok(Client::Binary(drone)) => println!("binary protocol"),
+///     Ok(Client::Sdk(drone)) => println!("sdk protocol"),
+///     Err(e) => println!("{}", e),
+/// }
+/// ```
+pub fn connect_auto(addr: &str, prefs: ConnectPrefs) -> Result<Client, ConnectError> {
+    let peer_addr = addr
+        .parse::<SocketAddr>()
+        .map_err(|_| ConnectError::InvalidAddress(addr.to_string()))?;
+
+    match probe(peer_addr, &prefs)? {
+        ProtocolMode::Binary => {
+            let mut drone = Drone::try_new(addr).map_err(ConnectError::Socket)?;
+            drone.set_connect_profile(prefs.profile);
+            drone
+                .try_connect(prefs.video_port)
+                .map_err(ConnectError::Socket)?;
+            Ok(Client::Binary(Box::new(drone)))
+        }
+        ProtocolMode::Sdk => Ok(Client::Sdk(Box::new(CommandMode::from(peer_addr)))),
+    }
+}
+
+/// send both probes concurrently and resolve the protocol mode with the preference.
+fn probe(peer_addr: SocketAddr, prefs: &ConnectPrefs) -> Result<ProtocolMode, ConnectError> {
+    let timeout = prefs.probe_timeout;
+    let mut conn_req = b"conn_req:".to_vec();
+    conn_req.extend_from_slice(&prefs.video_port.to_le_bytes());
+
+    let binary = thread::spawn(move || {
+        probe_request(peer_addr, &conn_req, timeout).and_then(|res| {
+            if res.starts_with("conn_ack") {
+                Ok(())
+            } else {
+                Err(ProbeFailure::UnexpectedResponse(res))
+            }
+        })
+    });
+    let sdk = thread::spawn(move || {
+        probe_request(peer_addr, b"command", timeout).and_then(|res| {
+            if res.starts_with("ok") {
+                Ok(())
+            } else {
+                Err(ProbeFailure::UnexpectedResponse(res))
+            }
+        })
+    });
+
+    let binary = binary
+        .join()
+        .unwrap_or_else(|_| Err(ProbeFailure::Socket("probe panicked".to_string())));
+    let sdk = sdk
+        .join()
+        .unwrap_or_else(|_| Err(ProbeFailure::Socket("probe panicked".to_string())));
+
+    match (binary, sdk) {
+        (Ok(()), Ok(())) => Ok(prefs.prefer),
+        (Ok(()), Err(_)) => Ok(ProtocolMode::Binary),
+        (Err(_), Ok(())) => Ok(ProtocolMode::Sdk),
+        (Err(binary), Err(sdk)) => Err(ConnectError::NoResponse { binary, sdk }),
+    }
+}
+
+/// send the request from a temporary socket and wait for the first answer.
+fn probe_request(
+    peer_addr: SocketAddr,
+    request: &[u8],
+    timeout: Duration,
+) -> Result<String, ProbeFailure> {
+    let socket = UdpSocket::bind(SocketAddr::from(([0, 0, 0, 0], 0)))
+        .map_err(|e| ProbeFailure::Socket(e.to_string()))?;
+    socket
+        .set_read_timeout(Some(timeout))
+        .map_err(|e| ProbeFailure::Socket(e.to_string()))?;
+    socket
+        .send_to(request, peer_addr)
+        .map_err(|e| ProbeFailure::Socket(e.to_string()))?;
+
+    let mut buf = [0u8; 64];
+    match socket.recv(&mut buf) {
+        Ok(bytes) => Ok(String::from_utf8_lossy(&buf[..bytes]).to_string()),
+        Err(e) => match e.kind() {
+            std::io::ErrorKind::WouldBlock | std::io::ErrorKind::TimedOut => {
+                Err(ProbeFailure::Timeout)
+            }
+            _ => Err(ProbeFailure::Socket(e.to_string())),
+        },
+    }
+}
+
+#[cfg(test)]
+use crate::mock::{MockConfig, MockDrone};
+#[cfg(test)]
+use crate::DRONE_PORT;
+
+/// a `MockDrone`, answering the probes of the enabled protocols
+#[cfg(test)]
+fn mock_drone(binary: bool, sdk: bool) -> MockDrone {
+    let mut config = MockConfig::default();
+    if !binary {
+        config = config.without_binary();
+    }
+    if !sdk {
+        config = config.with_rule("command", "error");
+    }
+    MockDrone::start(config).unwrap()
+}
+
+#[cfg(test)]
+fn test_prefs(prefer: ProtocolMode) -> ConnectPrefs {
+    ConnectPrefs {
+        prefer,
+        probe_timeout: Duration::from_millis(200),
+        video_port: crate::free_port(),
+        ..ConnectPrefs::default()
+    }
+}
+
+#[test]
+pub fn test_probe_binary_only() {
+    let mock = mock_drone(true, false);
+    let mode = probe(mock.addr(), &test_prefs(ProtocolMode::Sdk));
+    assert!(matches!(mode, Ok(ProtocolMode::Binary)));
+}
+
+#[test]
+pub fn test_probe_sdk_only() {
+    let mock = mock_drone(false, true);
+    let mode = probe(mock.addr(), &test_prefs(ProtocolMode::Binary));
+    assert!(matches!(mode, Ok(ProtocolMode::Sdk)));
+}
+
+#[test]
+pub fn test_probe_both_uses_preference() {
+    let mock = mock_drone(true, true);
+    assert!(matches!(
+        probe(mock.addr(), &test_prefs(ProtocolMode::Sdk)),
+        Ok(ProtocolMode::Sdk)
+    ));
+    assert!(matches!(
+        probe(mock.addr(), &test_prefs(ProtocolMode::Binary)),
+        Ok(ProtocolMode::Binary)
+    ));
+}
+
+#[test]
+pub fn test_probe_neither() {
+    let mock = mock_drone(false, false);
+    let mode = probe(mock.addr(), &test_prefs(ProtocolMode::Binary));
+    match mode {
+        Err(ConnectError::NoResponse { binary, sdk }) => {
+            assert_eq!(binary, ProbeFailure::Timeout);
+            assert_eq!(sdk, ProbeFailure::UnexpectedResponse("error".to_string()));
+        }
+        mode => panic!("unexpected {:?}", mode),
+    }
+}
+
+#[test]
+pub fn test_connect_auto_invalid_address() {
+    let res = connect_auto("no address", ConnectPrefs::default());
+    assert!(matches!(res, Err(ConnectError::InvalidAddress(_))));
+}
+
+#[test]
+pub fn test_connect_auto_binary() {
+    let _port = DRONE_PORT.lock().unwrap_or_else(|e| e.into_inner());
+    let mock = mock_drone(true, false);
+    let res = connect_auto(&mock.addr().to_string(), test_prefs(ProtocolMode::Sdk));
+    assert!(matches!(res, Ok(Client::Binary(_))));
+    // the probe and the connect of the drone
+    assert_eq!(mock.connections(), 2);
+}
+
+#[test]
+pub fn test_connect_auto_socket_error() {
+    let _port = DRONE_PORT.lock().unwrap_or_else(|e| e.into_inner());
+    let _taken = UdpSocket::bind("0.0.0.0:8889").unwrap();
+    let mock = mock_drone(true, false);
+    let res = connect_auto(&mock.addr().to_string(), test_prefs(ProtocolMode::Binary));
+    match res {
+        Err(ConnectError::Socket(e)) => assert_eq!(e.kind(), io::ErrorKind::AddrInUse),
+        res => panic!("unexpected {:?}", res.map(|_| ())),
+    }
+}
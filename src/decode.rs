@@ -0,0 +1,364 @@
+#[cfg(not(feature = "tokio_async"))]
+use crate::VideoFrameIter;
+#[cfg(feature = "tokio_async")]
+use crate::VideoFrameStream;
+use crate::{sps_dimensions, CommandError, CommandMode, Drone, Message, VideoFrames};
+use ffmpeg::{
+    codec, decoder, frame,
+    software::scaling::{Context as Scaler, Flags},
+    util::format::Pixel,
+    Packet,
+};
+use ffmpeg_next as ffmpeg;
+use std::collections::VecDeque;
+use std::fmt;
+use std::time::{Duration, Instant};
+
+/// a decoded video frame, see `Drone::decoded_frames()`
+#[derive(Debug, Clone, PartialEq)]
+pub struct DecodedFrame {
+    pub width: u32,
+    pub height: u32,
+    /// packed RGB24 pixels, `width * 3` bytes per row
+    pub rgb: Vec<u8>,
+    /// arrival time of the h264 frame, relative to the start of the decoder
+    pub timestamp: Duration,
+}
+
+/// does the frame (Annex-B) contain a SPS with another size than the last one, e.g. after
+/// `set_video_mode()`. The first SPS sets the size.
+fn resolution_changed(last: &mut Option<(u32, u32)>, frame: &[u8]) -> bool {
+    match sps_dimensions(frame) {
+        Some(dimensions) => last
+            .replace(dimensions)
+            .is_some_and(|last| last != dimensions),
+        None => false,
+    }
+}
+
+/// H.264 decoder for the frames of the drone, based on ffmpeg.
+///
+/// The drone streams the Main profile (`profile_idc` 77 in the SPS). Decoders of the
+/// constrained baseline profile only, like openh264, can't decode it.
+///
+/// A SPS with a new resolution (e.g. after `set_video_mode()`) resets the decoder and the size
+/// of the RGB frames follows the stream. The SPS of the periodic key-frames keeps the decoder
+/// state. Frames that can't be decoded are skipped and counted in `skipped_frames()`, until
+/// the next key-frame arrives.
+pub struct H264Decoder {
+    decoder: decoder::Video,
+    scaler: Option<(Scaler, Pixel, u32, u32)>,
+    /// the size of the last SPS
+    dimensions: Option<(u32, u32)>,
+    skipped: u64,
+    epoch: Instant,
+}
+
+impl fmt::Debug for H264Decoder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("H264Decoder")
+            .field("skipped", &self.skipped)
+            .finish_non_exhaustive()
+    }
+}
+
+impl H264Decoder {
+    pub fn new() -> Result<Self, ffmpeg::Error> {
+        ffmpeg::init()?;
+        let codec = decoder::find(codec::Id::H264).ok_or(ffmpeg::Error::DecoderNotFound)?;
+        let decoder = codec::Context::new_with_codec(codec).decoder().video()?;
+        Ok(Self {
+            decoder,
+            scaler: None,
+            dimensions: None,
+            skipped: 0,
+            epoch: Instant::now(),
+        })
+    }
+
+    /// number of the frames, that could not be decoded
+    pub fn skipped_frames(&self) -> u64 {
+        self.skipped
+    }
+
+    /// decode one h264 frame (Annex-B) of `Message::Frame` or the `VideoChunk`.
+    ///
+    /// Returns the decoded pictures, usually one or none while the decoder waits for a
+    /// key-frame.
+    pub fn decode(&mut self, data: &[u8]) -> Vec<DecodedFrame> {
+        let timestamp = self.epoch.elapsed();
+        if resolution_changed(&mut self.dimensions, data) {
+            // drop the references of the old resolution
+            self.decoder.flush();
+        }
+        if self.decoder.send_packet(&Packet::copy(data)).is_err() {
+            self.skipped += 1;
+            return Vec::new();
+        }
+
+        let mut decoded = Vec::new();
+        let mut picture = frame::Video::empty();
+        while self.decoder.receive_frame(&mut picture).is_ok() {
+            match self.convert(&picture, timestamp) {
+                Some(frame) => decoded.push(frame),
+                None => self.skipped += 1,
+            }
+        }
+        decoded
+    }
+
+    /// convert the picture to RGB24, the scaler is recreated when the size changes.
+    fn convert(&mut self, picture: &frame::Video, timestamp: Duration) -> Option<DecodedFrame> {
+        let (format, width, height) = (picture.format(), picture.width(), picture.height());
+        match &self.scaler {
+            Some((_, f, w, h)) if (*f, *w, *h) == (format, width, height) => (),
+            _ => {
+                let scaler = Scaler::get(
+                    format,
+                    width,
+                    height,
+                    Pixel::RGB24,
+                    width,
+                    height,
+                    Flags::BILINEAR,
+                )
+                .ok()?;
+                self.scaler = Some((scaler, format, width, height));
+            }
+        }
+        let (scaler, ..) = self.scaler.as_mut()?;
+
+        let mut rgb_picture = frame::Video::empty();
+        scaler.run(picture, &mut rgb_picture).ok()?;
+
+        // the rows of the picture are padded to the stride
+        let stride = rgb_picture.stride(0);
+        let row = width as usize * 3;
+        let rgb = rgb_picture
+            .data(0)
+            .chunks(stride)
+            .take(height as usize)
+            .flat_map(|line| &line[..row])
+            .copied()
+            .collect();
+        Some(DecodedFrame {
+            width,
+            height,
+            rgb,
+            timestamp,
+        })
+    }
+}
+
+/// Iterator of the decoded frames, see `Drone::decoded_frames()` and
+/// `CommandMode::decoded_frames()`. Like the `H264Decoder`, it is not `Send`.
+pub struct DecodedFrames<I> {
+    frames: I,
+    decoder: H264Decoder,
+    decoded: VecDeque<DecodedFrame>,
+}
+
+impl<I> DecodedFrames<I> {
+    pub(crate) fn new(frames: I) -> Result<Self, ffmpeg::Error> {
+        Ok(Self {
+            frames,
+            decoder: H264Decoder::new()?,
+            decoded: VecDeque::new(),
+        })
+    }
+
+    /// number of the frames, that could not be decoded
+    pub fn skipped_frames(&self) -> u64 {
+        self.decoder.skipped_frames()
+    }
+}
+
+impl<I, D> Iterator for DecodedFrames<I>
+where
+    I: Iterator<Item = (u32, D)>,
+    D: AsRef<[u8]>,
+{
+    type Item = DecodedFrame;
+
+    /// blocks until the next frame is decoded. Ends with the iterator of the h264 frames.
+    fn next(&mut self) -> Option<DecodedFrame> {
+        while self.decoded.is_empty() {
+            let (_, data) = self.frames.next()?;
+            self.decoded.extend(self.decoder.decode(data.as_ref()));
+        }
+        self.decoded.pop_front()
+    }
+}
+
+impl Drone {
+    /// Iterator of the decoded video frames, like `video_frames()`.
+    ///
+    /// Requires the `decode` feature (ffmpeg). Start the video with `start_video()` first.
+    ///
+    /// # Examples
+    /// ```no_run
+    /// let mut drone = Drone::new("192.168.10.1:8889");
+    /// drone.connect(11111);
+    /// drone.start_video().unwrap();
+    /// for frame in drone.decoded_frames().unwrap() {
+    ///     println!("{}x{} at {:?}", frame.width, frame.height, frame.timestamp);
+    /// }
+    /// ```
+    pub fn decoded_frames(&mut self) -> Result<DecodedFrames<VideoFrames<'_>>, ffmpeg::Error> {
+        DecodedFrames::new(self.video_frames())
+    }
+}
+
+/// a message of the `DecodingPoller`
+#[derive(Debug)]
+pub enum DecodedMessage {
+    /// a message of `Drone::poll()`, except the video frames
+    Message(Message),
+    /// the extended frame id and the decoded picture
+    Frame(u32, DecodedFrame),
+}
+
+/// Decodes the video frames of `Drone::poll()`. Use it instead of `poll()` in the loop, the
+/// other messages are passed through. The drone stays accessible between the polls.
+///
+/// The first frames after `start_video()` contain only the SPS/PPS or can't be decoded until
+/// the key-frame arrives, they return no picture (see `skipped_frames()`). Like the
+/// `H264Decoder`, it is not `Send`.
+///
+/// Requires the `decode` feature (ffmpeg).
+///
+/// # Examples
+/// ```no_run
+/// # use tello::{DecodedMessage, DecodingPoller, Drone};
+/// # use std::time::Duration;
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let mut drone = Drone::new("192.168.10.1:8889");
+/// let mut poller = DecodingPoller::new()?;
+/// drone.connect(11111);
+/// loop {
+///     while let Some(msg) = poller.poll(&mut drone) {
+///         match msg {
+///             DecodedMessage::Frame(frame_id, frame) => {
+///                 println!("{}: {}x{}", frame_id, frame.width, frame.height)
+///             }
+///             DecodedMessage::Message(msg) => println!("{:?}", msg),
+///         }
+///     }
+///     std::thread::sleep(Duration::from_millis(10));
+/// }
+/// # }
+/// ```
+pub struct DecodingPoller {
+    decoder: H264Decoder,
+    /// decoded pictures, not yet returned by `poll()`
+    decoded: VecDeque<(u32, DecodedFrame)>,
+}
+
+impl DecodingPoller {
+    /// see `H264Decoder::new()`
+    pub fn new() -> Result<Self, ffmpeg::Error> {
+        Ok(Self {
+            decoder: H264Decoder::new()?,
+            decoded: VecDeque::new(),
+        })
+    }
+
+    /// Poll the drone like `Drone::poll()`, the frames are returned once they are decoded.
+    /// `None`, if no message is pending.
+    pub fn poll(&mut self, drone: &mut Drone) -> Option<DecodedMessage> {
+        while self.decoded.is_empty() {
+            let (frame_id, data) = match drone.poll()? {
+                Message::Frame(_, data) => (drone.video.assembler.extended_frame_id(), data),
+                Message::FrameTs { id, data, .. } => (id, data),
+                msg => return Some(DecodedMessage::Message(msg)),
+            };
+            let pictures = self.decoder.decode(&data).into_iter();
+            self.decoded.extend(pictures.map(|frame| (frame_id, frame)));
+        }
+        let (frame_id, frame) = self.decoded.pop_front()?;
+        Some(DecodedMessage::Frame(frame_id, frame))
+    }
+
+    /// number of the frames, that could not be decoded
+    pub fn skipped_frames(&self) -> u64 {
+        self.decoder.skipped_frames()
+    }
+}
+
+#[cfg(feature = "tokio_async")]
+impl tokio_stream::Stream for DecodedFrames<VideoFrameStream> {
+    type Item = DecodedFrame;
+
+    /// The frames are decoded in the task that polls the stream. The ffmpeg decoder is not
+    /// `Send`, so the stream can't be moved into `tokio::spawn()`. Poll it in the task that
+    /// consumes the frames, or in a `LocalSet` or a thread with its own runtime.
+    fn poll_next(
+        mut self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<Option<DecodedFrame>> {
+        use std::task::Poll;
+        use tokio_stream::Stream;
+        let this = &mut *self;
+        while this.decoded.is_empty() {
+            match std::pin::Pin::new(&mut this.frames).poll_next(cx) {
+                Poll::Ready(Some((_, data))) => this.decoded.extend(this.decoder.decode(&data)),
+                Poll::Ready(None) => return Poll::Ready(None),
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+        Poll::Ready(this.decoded.pop_front())
+    }
+}
+
+impl CommandMode {
+    /// Take over the video receiver as `Stream` of the decoded frames, see `video_frames()`.
+    ///
+    /// Requires the `decode` feature (ffmpeg). Enable the video with `video_on()`.
+    #[cfg(feature = "tokio_async")]
+    pub fn decoded_frames(
+        &mut self,
+        capacity: usize,
+    ) -> Result<Option<DecodedFrames<VideoFrameStream>>, CommandError> {
+        match self.video_frames(capacity)? {
+            Some(frames) => DecodedFrames::new(frames).map(Some).map_err(decoder_error),
+            None => Ok(None),
+        }
+    }
+
+    /// Take over the video receiver as blocking `Iterator` of the decoded frames, see
+    /// `video_frames_iter()`.
+    ///
+    /// Requires the `decode` feature (ffmpeg). Enable the video with `video_on()`.
+    #[cfg(not(feature = "tokio_async"))]
+    pub fn decoded_frames(
+        &mut self,
+        capacity: usize,
+    ) -> Result<Option<DecodedFrames<VideoFrameIter>>, CommandError> {
+        match self.video_frames_iter(capacity)? {
+            Some(frames) => DecodedFrames::new(frames).map(Some).map_err(decoder_error),
+            None => Ok(None),
+        }
+    }
+}
+
+fn decoder_error(e: ffmpeg::Error) -> CommandError {
+    CommandError::Unsupported(format!("h264 decoder: {}", e))
+}
+
+#[test]
+pub fn test_resolution_changed() {
+    let sps_960 = [
+        0, 0, 0, 1, 0x67, 0x4d, 0x40, 0x28, 0x95, 0xa0, 0x3c, 0x05, 0xb9,
+    ];
+    let sps_1280 = [
+        0, 0, 1, 0x67, 0x4d, 0x40, 0x28, 0x95, 0xa0, 0x14, 0x01, 0x6e, 0x40,
+    ];
+    let mut last = None;
+    assert!(!resolution_changed(&mut last, &sps_960));
+    // the key-frames repeat the SPS
+    assert!(!resolution_changed(&mut last, &sps_960));
+    assert!(!resolution_changed(&mut last, &[0, 0, 0, 1, 0x65, 0x88]));
+    assert!(resolution_changed(&mut last, &sps_1280));
+    assert!(!resolution_changed(&mut last, &sps_1280));
+    assert_eq!(last, Some((1280, 720)));
+}
@@ -1,14 +1,54 @@
 use super::PackageData;
 use byteorder::{LittleEndian, ReadBytesExt};
-use std::io::{BufRead, Cursor, Seek, SeekFrom};
+use std::cmp::Ordering;
+use std::convert::TryFrom;
+use std::io::Cursor;
+use std::time::{Duration, Instant};
+
+/// default threshold of the `MetaWarning::WeakWifi` warning
+const WEAK_WIFI_THRESHOLD: u8 = 30;
+
+/// warnings of `DroneMeta::update()` and of the received packages, the drone returns them as
+/// `Message::Warning`
+#[derive(Debug, Clone)]
+pub enum MetaWarning {
+    /// the wifi strength dropped below the threshold, see `set_wifi_warning_threshold()`
+    WeakWifi(WifiInfo),
+    /// a log message without the id was received, it can't be acknowledged
+    MalformedLog(String),
+}
 
 /// Represents the last received meta data from the drone
 ///
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone)]
 pub struct DroneMeta {
     flight: Option<FlightData>,
     wifi: Option<WifiInfo>,
     light: Option<LightInfo>,
+    log: Option<LogData>,
+    att_limit: Option<f32>,
+    activation_time: Option<String>,
+    loader_version: Option<String>,
+    firmware: Option<FirmwareVersion>,
+    sport_mode: Option<bool>,
+    wifi_warning_threshold: u8,
+}
+
+impl Default for DroneMeta {
+    fn default() -> Self {
+        DroneMeta {
+            flight: None,
+            wifi: None,
+            light: None,
+            log: None,
+            att_limit: None,
+            activation_time: None,
+            loader_version: None,
+            firmware: None,
+            sport_mode: None,
+            wifi_warning_threshold: WEAK_WIFI_THRESHOLD,
+        }
+    }
 }
 
 impl DroneMeta {
@@ -31,27 +71,106 @@ impl DroneMeta {
     pub fn get_light_info(&self) -> Option<LightInfo> {
         self.light.clone()
     }
+    /// returns an option of the LogData with the last known MVO position and IMU attitude.
+    ///
+    /// this will always represent the last state event if the network connection is dropped
+    pub fn get_log_data(&self) -> Option<LogData> {
+        self.log.clone()
+    }
+    /// returns the last known attitude limit in degrees, see `Drone::get_att_angle()`.
+    pub fn get_att_limit(&self) -> Option<f32> {
+        self.att_limit
+    }
+    /// the sport mode of the sticks, see `RCState::set_sport_mode()`. `None` before the first
+    /// stick command.
+    pub fn sport_mode(&self) -> Option<bool> {
+        self.sport_mode
+    }
+    pub(crate) fn set_sport_mode(&mut self, enabled: bool) {
+        self.sport_mode = Some(enabled);
+    }
+    /// the drone waits for the throw after `Drone::throw_and_go()`, reported by the
+    /// `throw_fly_timer` of the flight data
+    pub fn throw_and_go_armed(&self) -> bool {
+        self.throw_countdown().is_some()
+    }
+    /// the remaining time to throw the drone, while `throw_and_go_armed()`. The
+    /// `throw_fly_timer` counts down in 100ms steps.
+    pub fn throw_countdown(&self) -> Option<Duration> {
+        match &self.flight {
+            Some(flight) if flight.throw_fly_timer > 0 && !flight.em_sky => Some(
+                Duration::from_millis(flight.throw_fly_timer as u64 * THROW_TIMER_STEP_MS),
+            ),
+            _ => None,
+        }
+    }
+    /// returns the activation time, see `Drone::get_activation_time()`.
+    pub fn get_activation_time(&self) -> Option<String> {
+        self.activation_time.clone()
+    }
+    /// returns the boot loader version, see `Drone::get_loader_version()`.
+    pub fn get_loader_version(&self) -> Option<String> {
+        self.loader_version.clone()
+    }
+    /// returns the firmware version, see `Drone::get_version()`. `None` until a version of a
+    /// known format was received, a reply of an unknown format keeps the last version.
+    pub fn firmware(&self) -> Option<FirmwareVersion> {
+        self.firmware.clone()
+    }
+    /// warn, when the wifi strength drops below `threshold` (0 - 100, default 30).
+    /// 0 disables the warning.
+    pub fn set_wifi_warning_threshold(&mut self, threshold: u8) {
+        self.wifi_warning_threshold = threshold;
+    }
     /// applies the package to the current data.
     /// It ignore non Meta package data and just overwrite the current metadata
-    pub fn update(&mut self, package: &PackageData) {
+    ///
+    /// Returns a warning, when the wifi strength drops below the threshold.
+    pub fn update(&mut self, package: &PackageData) -> Option<MetaWarning> {
         match package {
             PackageData::FlightData(fd) => self.flight = Some(fd.clone()),
-            PackageData::WifiInfo(wifi) => self.wifi = Some(wifi.clone()),
+            PackageData::WifiInfo(wifi) => {
+                let was_weak = self
+                    .wifi
+                    .as_ref()
+                    .is_some_and(|w| w.strength < self.wifi_warning_threshold);
+                self.wifi = Some(wifi.clone());
+                if !was_weak && wifi.strength < self.wifi_warning_threshold {
+                    return Some(MetaWarning::WeakWifi(wifi.clone()));
+                }
+            }
             PackageData::LightInfo(li) => self.light = Some(li.clone()),
+            PackageData::AttLimit(limit) => self.att_limit = Some(*limit),
+            PackageData::ActivationTime(time) => self.activation_time = Some(time.clone()),
+            PackageData::LoaderVersion(version) => self.loader_version = Some(version.clone()),
+            PackageData::Version(version) => {
+                // a garbled reply does not replace a known version
+                if let Ok(firmware) = FirmwareVersion::try_from(version.as_str()) {
+                    self.firmware = Some(firmware);
+                }
+            }
+            PackageData::LogData(ld) => {
+                // a log package does not always contain all records. Keep the last known values.
+                let log = self.log.get_or_insert_with(LogData::default);
+                if ld.mvo.is_some() {
+                    log.mvo = ld.mvo.clone();
+                }
+                if ld.imu.is_some() {
+                    log.imu = ld.imu.clone();
+                }
+            }
             _ => (),
         };
+        None
     }
 }
 
+/// signed 16 bit value, little endian
 fn int16(val0: u8, val1: u8) -> i16 {
-    if val1 != 0 {
-        (((val0 as i32) | ((val1 as i32) << 8)) - 0x10000) as i16
-    } else {
-        (val0 as i16) | ((val1 as i16) << 8)
-    }
+    i16::from_le_bytes([val0, val1])
 }
 
-#[derive(Clone)]
+#[derive(Clone, PartialEq)]
 pub struct FlightData {
     pub height: i16,
     pub north_speed: i16,
@@ -100,8 +219,32 @@ impl std::fmt::Debug for FlightData {
     }
 }
 
+/// size of the flight data payload
+const FLIGHT_DATA_SIZE: usize = 24;
+
 impl From<Vec<u8>> for FlightData {
-    fn from(data: Vec<u8>) -> FlightData {
+    /// parse the flight data. Missing bytes of a short payload are filled with 0.
+    fn from(mut data: Vec<u8>) -> FlightData {
+        if data.len() < FLIGHT_DATA_SIZE {
+            data.resize(FLIGHT_DATA_SIZE, 0);
+        }
+        FlightData::parse(&data)
+    }
+}
+
+impl TryFrom<&[u8]> for FlightData {
+    type Error = String;
+    /// parse the flight data. A payload shorter than 24 bytes is an error.
+    fn try_from(data: &[u8]) -> Result<FlightData, String> {
+        if data.len() < FLIGHT_DATA_SIZE {
+            return Err(format!("flight data too short ({} bytes)", data.len()));
+        }
+        Ok(FlightData::parse(data))
+    }
+}
+
+impl FlightData {
+    fn parse(data: &[u8]) -> FlightData {
         FlightData {
             height: int16(data[0], data[1]),
             north_speed: int16(data[2], data[3]),
@@ -143,35 +286,294 @@ impl From<Vec<u8>> for FlightData {
             temperature_height: ((data[23]) & 0x1) != 0,
         }
     }
+
+    /// the `fly_mode` as `FlyMode`
+    pub fn fly_mode_enum(&self) -> FlyMode {
+        FlyMode::from(self.fly_mode)
+    }
+
+    /// the health bits of the sensors, e.g. for a pre-flight check
+    pub fn sensor_health(&self) -> SensorHealth {
+        SensorHealth {
+            imu: self.imu_state,
+            pressure: self.pressure_state,
+            down_vision: self.down_visual_state,
+            gravity: self.gravity_state,
+            strong_wind: self.wind_state,
+        }
+    }
+}
+
+/// Flight mode of the flight controller, see `FlightData::fly_mode_enum()`.
+///
+/// The values are not documented by Ryze, they follow the mapping of other Tello libraries:
+/// 1 take off, 6 hovering, 11 landing and 12 an obstacle. More modes may be added in minor
+/// versions, until then they are kept as `Unknown`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum FlyMode {
+    /// automatic take off
+    TakingOff,
+    /// the position is held, on the ground and hovering
+    Hovering,
+    /// automatic landing
+    Landing,
+    /// an obstacle stopped the drone
+    Obstacle,
+    /// a value without a known meaning
+    Unknown(u8),
+}
+
+impl From<u8> for FlyMode {
+    fn from(mode: u8) -> FlyMode {
+        match mode {
+            1 => FlyMode::TakingOff,
+            6 => FlyMode::Hovering,
+            11 => FlyMode::Landing,
+            12 => FlyMode::Obstacle,
+            mode => FlyMode::Unknown(mode),
+        }
+    }
+}
+
+/// Health of the sensors of the `FlightData`, see `FlightData::sensor_health()`.
+///
+/// It displays as a short summary, e.g. `"all sensors ok"` or `"pressure sensor failed, strong
+/// wind"`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SensorHealth {
+    /// the IMU works
+    pub imu: bool,
+    /// the barometer works
+    pub pressure: bool,
+    /// the downward vision positioning works (it needs light and a textured ground)
+    pub down_vision: bool,
+    /// the gravity sensor works
+    pub gravity: bool,
+    /// the drone reports a strong wind
+    pub strong_wind: bool,
+}
+
+impl SensorHealth {
+    /// all sensors work and there is no strong wind
+    pub fn all_ok(&self) -> bool {
+        self.problems().is_empty()
+    }
+
+    fn problems(&self) -> Vec<&'static str> {
+        [
+            (!self.imu, "IMU failed"),
+            (!self.pressure, "pressure sensor failed"),
+            (!self.down_vision, "no downward vision"),
+            (!self.gravity, "gravity sensor failed"),
+            (self.strong_wind, "strong wind"),
+        ]
+        .iter()
+        .filter(|(problem, _)| *problem)
+        .map(|(_, text)| *text)
+        .collect()
+    }
+}
+
+impl std::fmt::Display for SensorHealth {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let problems = self.problems();
+        if problems.is_empty() {
+            write!(f, "all sensors ok")
+        } else {
+            write!(f, "{}", problems.join(", "))
+        }
+    }
 }
 
 /// current strength of the wifi signal and distortion.
 /// When the drone is in the AP mode, the max strength value is 90
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct WifiInfo {
     strength: u8,
     disturb: u8,
 }
+impl WifiInfo {
+    /// strength of the wifi signal 0 - 100 (max 90 in the AP mode)
+    pub fn strength(&self) -> u8 {
+        self.strength
+    }
+    /// interference of the wifi signal 0 - 100
+    pub fn disturb(&self) -> u8 {
+        self.disturb
+    }
+    /// Heuristic link quality 0 - 100, e.g. for a UI bar.
+    ///
+    /// The `strength` is reduced by half of the `disturb` (in percent) and by twice the
+    /// `frame_loss` (0.0 - 1.0, the share of the broken video frames). So 20% interference
+    /// costs 10% and a frame loss of 50% or more gives 0, a lossy video is useless for FPV.
+    pub fn link_quality(&self, frame_loss: f32) -> u8 {
+        let radio = self.strength.min(100) as f32 * (1.0 - self.disturb.min(100) as f32 / 200.0);
+        let video = (1.0 - 2.0 * frame_loss).clamp(0.0, 1.0);
+        (radio * video).round() as u8
+    }
+}
 impl From<Vec<u8>> for WifiInfo {
-    /// parse the incoming network package
+    /// parse the incoming network package. Missing bytes of a short payload are 0.
     fn from(data: Vec<u8>) -> WifiInfo {
         WifiInfo {
-            strength: data[0],
-            disturb: data[1],
+            strength: data.first().copied().unwrap_or(0),
+            disturb: data.get(1).copied().unwrap_or(0),
+        }
+    }
+}
+
+impl TryFrom<&[u8]> for WifiInfo {
+    type Error = String;
+    /// parse the incoming network package. A payload shorter than 2 bytes is an error.
+    fn try_from(data: &[u8]) -> Result<WifiInfo, String> {
+        match data {
+            [strength, disturb, ..] => Ok(WifiInfo {
+                strength: *strength,
+                disturb: *disturb,
+            }),
+            _ => Err(format!("wifi info too short ({} bytes)", data.len())),
         }
     }
 }
 
 /// some features like a flip or bouncing is only available when the battery is charged and there is enough light
 /// check the FlightData for the battery state
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct LightInfo {
-    good: bool,
+    strength: u8,
+}
+impl LightInfo {
+    /// the raw light strength of the downward vision system, 0 is the best
+    pub fn strength(&self) -> u8 {
+        self.strength
+    }
+    /// the light strength as `LightLevel`
+    pub fn level(&self) -> LightLevel {
+        match self.strength {
+            0 => LightLevel::Good,
+            1 => LightLevel::Weak,
+            _ => LightLevel::Bad,
+        }
+    }
+    /// There is enough light for the advanced maneuvers like a flip or bouncing. A UI can
+    /// warn before they are tried.
+    pub fn is_adequate(&self) -> bool {
+        self.level() == LightLevel::Good
+    }
 }
 impl From<Vec<u8>> for LightInfo {
-    /// parse the incoming network package
+    /// parse the incoming network package. The strength of an empty payload is 0.
     fn from(data: Vec<u8>) -> LightInfo {
-        LightInfo { good: data[0] == 0 }
+        LightInfo {
+            strength: data.first().copied().unwrap_or(0),
+        }
+    }
+}
+
+impl TryFrom<&[u8]> for LightInfo {
+    type Error = String;
+    /// parse the incoming network package. An empty payload is an error.
+    fn try_from(data: &[u8]) -> Result<LightInfo, String> {
+        match data.first() {
+            Some(strength) => Ok(LightInfo {
+                strength: *strength,
+            }),
+            None => Err("light info without data".to_string()),
+        }
+    }
+}
+
+/// lighting of the downward vision system, see `LightInfo::level()`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LightLevel {
+    /// bright enough for all maneuvers
+    Good,
+    /// the position is still held, but flips and bouncing may fail
+    Weak,
+    /// too dark for the vision positioning
+    Bad,
+}
+
+/// Firmware version of the drone, e.g. `01.04.91.01` of a Tello or `02.04.69.85` of a
+/// Tello EDU.
+///
+/// The versions are compared by their numbers, e.g. to check a feature:
+/// `firmware >= FirmwareVersion::new(2, 0, 0, 0)`. It displays as the raw string.
+#[derive(Debug, Clone)]
+pub struct FirmwareVersion {
+    pub major: u16,
+    pub minor: u16,
+    pub patch: u16,
+    /// the fourth number, 0 if the version has only three
+    pub build: u16,
+    /// the version, as it was received
+    pub raw: String,
+}
+
+impl FirmwareVersion {
+    pub fn new(major: u16, minor: u16, patch: u16, build: u16) -> FirmwareVersion {
+        FirmwareVersion {
+            major,
+            minor,
+            patch,
+            build,
+            raw: format!("{:02}.{:02}.{:02}.{:02}", major, minor, patch, build),
+        }
+    }
+
+    fn numbers(&self) -> (u16, u16, u16, u16) {
+        (self.major, self.minor, self.patch, self.build)
+    }
+}
+
+impl TryFrom<&str> for FirmwareVersion {
+    type Error = String;
+    /// parse three or four numbers, separated by dots, with an optional `v` in front
+    fn try_from(version: &str) -> Result<FirmwareVersion, String> {
+        let raw = version.trim_matches(|c: char| c.is_whitespace() || c == '\0');
+        let numbers: Vec<u16> = raw
+            .trim_start_matches(['v', 'V'])
+            .split('.')
+            .map(|n| n.parse())
+            .collect::<Result<_, _>>()
+            .map_err(|_| format!("invalid firmware version {:?}", version))?;
+        match numbers[..] {
+            [major, minor, patch] => Ok((major, minor, patch, 0)),
+            [major, minor, patch, build] => Ok((major, minor, patch, build)),
+            _ => Err(format!("invalid firmware version {:?}", version)),
+        }
+        .map(|(major, minor, patch, build)| FirmwareVersion {
+            major,
+            minor,
+            patch,
+            build,
+            raw: raw.to_string(),
+        })
+    }
+}
+
+impl PartialEq for FirmwareVersion {
+    fn eq(&self, other: &Self) -> bool {
+        self.numbers() == other.numbers()
+    }
+}
+impl Eq for FirmwareVersion {}
+
+impl PartialOrd for FirmwareVersion {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+impl Ord for FirmwareVersion {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.numbers().cmp(&other.numbers())
+    }
+}
+
+impl std::fmt::Display for FirmwareVersion {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.raw)
     }
 }
 
@@ -179,22 +581,545 @@ impl From<Vec<u8>> for LightInfo {
 #[derive(Debug, Clone)]
 pub struct LogMessage {
     pub id: u16,
+    /// text of the message, invalid UTF-8 (e.g. of binary data) is replaced
     pub message: String,
+    /// the whole payload
+    pub raw: Vec<u8>,
 }
-impl From<Vec<u8>> for LogMessage {
-    /// parse the incoming network package
-    fn from(data: Vec<u8>) -> LogMessage {
-        let mut cur = Cursor::new(data);
-        let id: u16 = cur.read_u16::<LittleEndian>().unwrap();
-        cur.seek(SeekFrom::Start(19)).unwrap();
-        let mut msg: Vec<u8> = Vec::new();
-        cur.read_until(0, &mut msg).unwrap();
-        LogMessage {
+
+/// offset of the text in the log message
+const LOG_MESSAGE_TEXT: usize = 19;
+
+impl TryFrom<&[u8]> for LogMessage {
+    type Error = String;
+    /// parse the incoming network package. A payload without the id is an error.
+    fn try_from(data: &[u8]) -> Result<LogMessage, String> {
+        if data.len() < 2 {
+            return Err(format!("log message too short ({} bytes)", data.len()));
+        }
+        let id = u16::from_le_bytes([data[0], data[1]]);
+        let text = data.get(LOG_MESSAGE_TEXT..).unwrap_or_default();
+        let text = text.split(|b| *b == 0).next().unwrap_or_default();
+        Ok(LogMessage {
             id,
-            message: String::from_utf8(msg)
-                .unwrap()
-                .trim_matches('\u{0}')
-                .to_string(),
+            message: String::from_utf8_lossy(text).to_string(),
+            raw: data.to_vec(),
+        })
+    }
+}
+
+const LOG_RECORD_SEPARATOR: u8 = 0x55;
+const LOG_RECORD_NEW_MVO_FEEDBACK: u16 = 0x001d;
+const LOG_RECORD_IMU_ATTI: u16 = 0x0800;
+
+/// position and velocity estimated by the visual odometry (MVO) of the drone.
+///
+/// The position is relative to the point where the drone started the motors.
+#[derive(Debug, Clone, Default)]
+pub struct LogMvo {
+    /// velocity in m/s
+    pub vel_x: f32,
+    pub vel_y: f32,
+    pub vel_z: f32,
+    /// position in m
+    pub pos_x: f32,
+    pub pos_y: f32,
+    pub pos_z: f32,
+}
+
+/// attitude of the drone, calculated by the IMU.
+#[derive(Debug, Clone, Default)]
+pub struct LogImu {
+    /// acceleration in g
+    pub acc_x: f32,
+    pub acc_y: f32,
+    pub acc_z: f32,
+    /// angular velocity in rad/s
+    pub gyro_x: f32,
+    pub gyro_y: f32,
+    pub gyro_z: f32,
+    /// attitude as quaternion
+    pub quat_w: f32,
+    pub quat_x: f32,
+    pub quat_y: f32,
+    pub quat_z: f32,
+    /// velocity in m/s
+    pub vg_x: f32,
+    pub vg_y: f32,
+    pub vg_z: f32,
+}
+
+/// parsed records of the LogDataMsg (0x1051).
+///
+/// The layout is taken from the tellopilots forum. Each package contains some records,
+/// starting with `0x55`, the length, the record id and a xor-key for the payload.
+/// Only the MVO (position/velocity) and the IMU (attitude) records are parsed.
+#[derive(Debug, Clone, Default)]
+pub struct LogData {
+    pub mvo: Option<LogMvo>,
+    pub imu: Option<LogImu>,
+}
+
+fn read_f32_at(data: &[u8], offset: usize) -> f32 {
+    let mut cur = Cursor::new(&data[offset..offset + 4]);
+    cur.read_f32::<LittleEndian>().unwrap_or_default()
+}
+
+fn read_i16_at(data: &[u8], offset: usize) -> i16 {
+    let mut cur = Cursor::new(&data[offset..offset + 2]);
+    cur.read_i16::<LittleEndian>().unwrap_or_default()
+}
+
+impl From<Vec<u8>> for LogData {
+    /// parse the incoming network package. Broken or unknown records are skipped
+    fn from(data: Vec<u8>) -> LogData {
+        let mut log = LogData::default();
+        let mut pos = 1;
+        while pos + 10 < data.len() {
+            if data[pos] != LOG_RECORD_SEPARATOR {
+                break;
+            }
+            let length = data[pos + 1] as usize | (data[pos + 2] as usize) << 8;
+            let id = data[pos + 4] as u16 | (data[pos + 5] as u16) << 8;
+            let xor = data[pos + 6];
+            if length < 12 || pos + length > data.len() {
+                break;
+            }
+            // record header has 10 bytes and the record ends with a crc16
+            let payload: Vec<u8> = data[pos + 10..pos + length - 2]
+                .iter()
+                .map(|b| b ^ xor)
+                .collect();
+
+            match id {
+                LOG_RECORD_NEW_MVO_FEEDBACK if payload.len() >= 20 => {
+                    log.mvo = Some(LogMvo {
+                        vel_x: read_i16_at(&payload, 2) as f32 / 100.0,
+                        vel_y: read_i16_at(&payload, 4) as f32 / 100.0,
+                        vel_z: read_i16_at(&payload, 6) as f32 / 100.0,
+                        pos_x: read_f32_at(&payload, 8),
+                        pos_y: read_f32_at(&payload, 12),
+                        pos_z: read_f32_at(&payload, 16),
+                    })
+                }
+                LOG_RECORD_IMU_ATTI if payload.len() >= 88 => {
+                    log.imu = Some(LogImu {
+                        acc_x: read_f32_at(&payload, 20),
+                        acc_y: read_f32_at(&payload, 24),
+                        acc_z: read_f32_at(&payload, 28),
+                        gyro_x: read_f32_at(&payload, 32),
+                        gyro_y: read_f32_at(&payload, 36),
+                        gyro_z: read_f32_at(&payload, 40),
+                        quat_w: read_f32_at(&payload, 48),
+                        quat_x: read_f32_at(&payload, 52),
+                        quat_y: read_f32_at(&payload, 56),
+                        quat_z: read_f32_at(&payload, 60),
+                        vg_x: read_f32_at(&payload, 76),
+                        vg_y: read_f32_at(&payload, 80),
+                        vg_z: read_f32_at(&payload, 84),
+                    })
+                }
+                _ => (),
+            }
+            pos += length;
+        }
+        log
+    }
+}
+
+/// the drone waits this long for the throw, after `throw_and_go()`
+const THROW_ARMING_TIMEOUT: Duration = Duration::from_secs(5);
+/// step of the `throw_fly_timer` of the flight data
+const THROW_TIMER_STEP_MS: u64 = 100;
+
+/// changes of the gesture modes, the drone returns them as `Message::Gesture`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GestureEvent {
+    /// the throw timer started, throw the drone before `DroneMeta::throw_countdown()` is over
+    ThrowArmed,
+    /// the drone was thrown and flies
+    Thrown,
+    /// the drone landed on the palm, see `Drone::palm_land()`
+    PalmLanded,
+}
+
+/// progress of the `palm_land()` gesture
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum PalmLandState {
+    /// no palm land requested
+    #[default]
+    Idle,
+    /// the drone is descending and waits for a palm below it
+    Searching,
+    /// the drone detected the palm and landed on it
+    Landed,
+}
+
+/// state of the gesture modes (throw and go, palm land), updated by the flight data.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct GestureState {
+    throw_armed_until: Option<Instant>,
+    /// the `throw_fly_timer` of the last flight data was running
+    throw_timer_running: bool,
+    palm_land: PalmLandState,
+}
+
+impl GestureState {
+    pub fn arm_throw(&mut self, now: Instant) {
+        self.throw_armed_until = Some(now + THROW_ARMING_TIMEOUT);
+    }
+
+    pub fn is_armed_for_throw(&self, now: Instant) -> bool {
+        matches!(self.throw_armed_until, Some(until) if now < until)
+    }
+
+    pub fn start_palm_land(&mut self) {
+        self.palm_land = PalmLandState::Searching;
+    }
+
+    pub fn palm_land(&self) -> PalmLandState {
+        self.palm_land
+    }
+
+    /// the throw is detected, when the drone is in the sky. There is no palm flag in the
+    /// flight data, the palm is detected when the drone stops flying while searching for it.
+    ///
+    /// Returns the event, when the throw timer starts, the drone was thrown or landed on the
+    /// palm.
+    pub fn update(&mut self, flight: &FlightData, now: Instant) -> Option<GestureEvent> {
+        let timer_running = flight.throw_fly_timer > 0 && !flight.em_sky;
+        let was_running = std::mem::replace(&mut self.throw_timer_running, timer_running);
+        if flight.em_sky {
+            let armed = self.is_armed_for_throw(now) || was_running;
+            self.throw_armed_until = None;
+            if armed {
+                Some(GestureEvent::Thrown)
+            } else {
+                None
+            }
+        } else if self.palm_land == PalmLandState::Searching {
+            self.palm_land = PalmLandState::Landed;
+            Some(GestureEvent::PalmLanded)
+        } else if timer_running && !was_running {
+            Some(GestureEvent::ThrowArmed)
+        } else {
+            None
         }
     }
 }
+
+/// payload of a LogDataMsg with a MVO record at the position (north, east, down) in meter
+#[cfg(test)]
+pub(crate) fn log_data_mvo(velocity: (i16, i16, i16), position: (f32, f32, f32)) -> Vec<u8> {
+    let xor = 0x5a;
+    let mut payload = [0u8; 80];
+    payload[2..4].copy_from_slice(&velocity.0.to_le_bytes());
+    payload[4..6].copy_from_slice(&velocity.1.to_le_bytes());
+    payload[6..8].copy_from_slice(&velocity.2.to_le_bytes());
+    payload[8..12].copy_from_slice(&position.0.to_le_bytes());
+    payload[12..16].copy_from_slice(&position.1.to_le_bytes());
+    payload[16..20].copy_from_slice(&position.2.to_le_bytes());
+
+    let length = (payload.len() + 12) as u16;
+    let mut record = vec![LOG_RECORD_SEPARATOR];
+    record.extend_from_slice(&length.to_le_bytes());
+    record.push(0); // crc8
+    record.extend_from_slice(&LOG_RECORD_NEW_MVO_FEEDBACK.to_le_bytes());
+    record.extend_from_slice(&[xor, 0x12, 0x34, 0x00]); // xor + tick
+    record.extend(payload.iter().map(|b| b ^ xor));
+    record.extend_from_slice(&[0, 0]); // crc16
+
+    let mut data = vec![0x00];
+    data.append(&mut record);
+    data
+}
+
+#[test]
+pub fn test_parse_log_data_mvo() {
+    let data = log_data_mvo((-25, 110, 0), (1.5, -0.25, -1.0));
+    let log = LogData::from(data);
+    assert!(log.imu.is_none());
+    let mvo = log.mvo.unwrap();
+    assert_eq!(mvo.vel_x, -0.25);
+    assert_eq!(mvo.vel_y, 1.1);
+    assert_eq!(mvo.vel_z, 0.0);
+    assert_eq!(mvo.pos_x, 1.5);
+    assert_eq!(mvo.pos_y, -0.25);
+    assert_eq!(mvo.pos_z, -1.0);
+}
+
+#[test]
+pub fn test_parse_log_data_broken() {
+    assert!(LogData::from(vec![]).mvo.is_none());
+    assert!(
+        LogData::from(vec![0x00, 0x55, 0xff, 0x00, 0x00, 0x1d, 0x00])
+            .mvo
+            .is_none()
+    );
+}
+#[test]
+pub fn test_gesture_state() {
+    let mut flight_data = vec![0u8; 24];
+    let on_ground = FlightData::from(flight_data.clone());
+    flight_data[17] = 0x1;
+    let in_sky = FlightData::from(flight_data);
+    let now = Instant::now();
+
+    let mut gestures = GestureState::default();
+    gestures.arm_throw(now);
+    assert!(gestures.is_armed_for_throw(now));
+    assert!(!gestures.is_armed_for_throw(now + Duration::from_secs(6)));
+    assert_eq!(gestures.update(&on_ground, now), None);
+    assert!(gestures.is_armed_for_throw(now));
+    assert_eq!(gestures.update(&in_sky, now), Some(GestureEvent::Thrown));
+    assert!(!gestures.is_armed_for_throw(now));
+    assert_eq!(gestures.update(&in_sky, now), None);
+
+    gestures.start_palm_land();
+    assert_eq!(gestures.update(&in_sky, now), None);
+    assert_eq!(gestures.palm_land(), PalmLandState::Searching);
+    assert_eq!(
+        gestures.update(&on_ground, now),
+        Some(GestureEvent::PalmLanded)
+    );
+    assert_eq!(gestures.palm_land(), PalmLandState::Landed);
+}
+#[test]
+pub fn test_throw_countdown() {
+    let flight = |timer: u8, em_sky: bool| {
+        let mut data = vec![0u8; 24];
+        data[17] = em_sky as u8;
+        data[19] = timer;
+        FlightData::from(data)
+    };
+    let now = Instant::now();
+    let mut meta = DroneMeta::default();
+    let mut gestures = GestureState::default();
+    assert!(!meta.throw_and_go_armed());
+
+    meta.update(&PackageData::FlightData(flight(50, false)));
+    assert!(meta.throw_and_go_armed());
+    assert_eq!(meta.throw_countdown(), Some(Duration::from_secs(5)));
+    assert_eq!(
+        gestures.update(&flight(50, false), now),
+        Some(GestureEvent::ThrowArmed)
+    );
+    meta.update(&PackageData::FlightData(flight(12, false)));
+    assert_eq!(meta.throw_countdown(), Some(Duration::from_millis(1200)));
+    assert_eq!(gestures.update(&flight(12, false), now), None);
+
+    // the timer was running, when the drone started flying
+    meta.update(&PackageData::FlightData(flight(10, true)));
+    assert!(!meta.throw_and_go_armed());
+    assert_eq!(
+        gestures.update(&flight(10, true), now),
+        Some(GestureEvent::Thrown)
+    );
+    assert_eq!(gestures.update(&flight(0, true), now), None);
+}
+#[test]
+pub fn test_parse_short_flight_data() {
+    assert!(FlightData::try_from(&[0u8; 10][..]).is_err());
+    assert!(FlightData::try_from(&[0u8; 24][..]).is_ok());
+    let flight = FlightData::from(vec![10, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+    assert_eq!(flight.height, 10);
+    assert!(!flight.em_sky);
+}
+#[test]
+pub fn test_wifi_info() {
+    let wifi = WifiInfo::from(vec![85, 12]);
+    assert_eq!((wifi.strength(), wifi.disturb()), (85, 12));
+
+    let mut meta = DroneMeta::default();
+    assert!(meta.update(&PackageData::WifiInfo(wifi)).is_none());
+    let weak = |strength| PackageData::WifiInfo(WifiInfo::from(vec![strength, 0]));
+    match meta.update(&weak(20)) {
+        Some(MetaWarning::WeakWifi(wifi)) => assert_eq!(wifi.strength(), 20),
+        w => panic!("unexpected {:?}", w),
+    }
+    // only once, until the signal recovers
+    assert!(meta.update(&weak(15)).is_none());
+    assert!(meta.update(&weak(60)).is_none());
+    meta.set_wifi_warning_threshold(0);
+    assert!(meta.update(&weak(5)).is_none());
+}
+#[test]
+pub fn test_short_wifi_and_light_info() {
+    assert_eq!(
+        WifiInfo::try_from(&[85u8, 12, 0][..]),
+        Ok(WifiInfo::from(vec![85, 12]))
+    );
+    assert!(WifiInfo::try_from(&[85u8][..]).is_err());
+    assert!(WifiInfo::try_from(&[][..]).is_err());
+    assert_eq!(
+        LightInfo::try_from(&[1u8][..]),
+        Ok(LightInfo::from(vec![1]))
+    );
+    assert!(LightInfo::try_from(&[][..]).is_err());
+    // `From` fills the missing bytes with 0
+    assert_eq!(WifiInfo::from(vec![85]).disturb(), 0);
+    assert_eq!(LightInfo::from(vec![]).strength(), 0);
+}
+#[test]
+pub fn test_link_quality() {
+    let wifi = |strength, disturb| WifiInfo::from(vec![strength, disturb]);
+    assert_eq!(wifi(90, 0).link_quality(0.0), 90);
+    assert_eq!(wifi(80, 20).link_quality(0.0), 72);
+    assert_eq!(wifi(80, 100).link_quality(0.0), 40);
+    assert_eq!(wifi(80, 0).link_quality(0.1), 64);
+    assert_eq!(wifi(80, 20).link_quality(0.25), 36);
+    assert_eq!(wifi(100, 0).link_quality(0.5), 0);
+    assert_eq!(wifi(100, 0).link_quality(1.0), 0);
+    assert_eq!(wifi(0, 0).link_quality(0.0), 0);
+    // out of range values are clamped
+    assert_eq!(wifi(255, 255).link_quality(-1.0), 50);
+}
+#[test]
+pub fn test_parse_log_message() {
+    let mut payload = vec![0x34, 0x12];
+    payload.resize(LOG_MESSAGE_TEXT, 0);
+    payload.extend_from_slice(b"motor\0\xff");
+    let log = LogMessage::try_from(&payload[..]).unwrap();
+    assert_eq!((log.id, log.message.as_str()), (0x1234, "motor"));
+    assert_eq!(log.raw, payload);
+
+    // binary data and short packages
+    payload.truncate(LOG_MESSAGE_TEXT);
+    payload.extend_from_slice(&[0xff, 0xfe, b'a']);
+    let log = LogMessage::try_from(&payload[..]).unwrap();
+    assert_eq!(log.message, "\u{fffd}\u{fffd}a");
+    assert_eq!(LogMessage::try_from(&[7u8, 0][..]).unwrap().message, "");
+    assert!(LogMessage::try_from(&[7u8][..]).is_err());
+}
+#[test]
+pub fn test_int16() {
+    assert_eq!(int16(0xff, 0x00), 255);
+    assert_eq!(int16(0x00, 0x01), 256);
+    assert_eq!(int16(0x2c, 0x01), 300);
+    assert_eq!(int16(0xff, 0xff), -1);
+    assert_eq!(int16(0xd4, 0xfe), -300);
+    assert_eq!(int16(0xff, 0x7f), i16::MAX);
+    assert_eq!(int16(0x00, 0x80), i16::MIN);
+
+    // height 300, north speed -1, fly time 256 and battery left 255
+    let mut data = vec![0u8; 24];
+    data[..4].copy_from_slice(&[0x2c, 0x01, 0xff, 0xff]);
+    data[8..10].copy_from_slice(&[0x00, 0x01]);
+    data[13..15].copy_from_slice(&[0xff, 0x00]);
+    let flight = FlightData::from(data);
+    assert_eq!((flight.height, flight.north_speed), (300, -1));
+    assert_eq!((flight.fly_time, flight.drone_battery_left), (256, 255));
+}
+#[test]
+pub fn test_compare_telemetry() {
+    let mut data = vec![0u8; 24];
+    data[12] = 80;
+    let flight = FlightData::from(data.clone());
+    assert_eq!(flight, FlightData::from(data.clone()));
+    data[12] = 79;
+    assert_ne!(flight, FlightData::from(data));
+
+    assert_eq!(WifiInfo::from(vec![85, 12]), WifiInfo::from(vec![85, 12]));
+    assert_ne!(WifiInfo::from(vec![85, 12]), WifiInfo::from(vec![84, 12]));
+    assert_eq!(LightInfo::from(vec![0]), LightInfo::from(vec![0]));
+    assert_ne!(LightInfo::from(vec![0]), LightInfo::from(vec![1]));
+}
+#[test]
+pub fn test_light_level() {
+    let light = LightInfo::from(vec![0]);
+    assert_eq!((light.strength(), light.level()), (0, LightLevel::Good));
+    assert!(light.is_adequate());
+    assert_eq!(LightInfo::from(vec![1]).level(), LightLevel::Weak);
+    assert!(!LightInfo::from(vec![1]).is_adequate());
+    assert_eq!(LightInfo::from(vec![2]).level(), LightLevel::Bad);
+    assert!(!LightInfo::from(vec![255]).is_adequate());
+
+    let mut meta = DroneMeta::default();
+    meta.update(&PackageData::LightInfo(LightInfo::from(vec![1])));
+    assert_eq!(
+        meta.get_light_info().map(|l| l.level()),
+        Some(LightLevel::Weak)
+    );
+}
+#[test]
+pub fn test_sensor_health() {
+    let mut data = vec![0u8; 24];
+    // imu, pressure, down vision, power, battery and gravity
+    data[10] = 0b0011_1111;
+    let health = FlightData::from(data.clone()).sensor_health();
+    assert_eq!(
+        health,
+        SensorHealth {
+            imu: true,
+            pressure: true,
+            down_vision: true,
+            gravity: true,
+            strong_wind: false,
+        }
+    );
+    assert!(health.all_ok());
+    assert_eq!(health.to_string(), "all sensors ok");
+
+    // no pressure, no vision, and the wind bit
+    data[10] = 0b1010_1001;
+    let health = FlightData::from(data).sensor_health();
+    assert_eq!(
+        (health.imu, health.pressure, health.down_vision),
+        (true, false, false)
+    );
+    assert_eq!((health.gravity, health.strong_wind), (true, true));
+    assert!(!health.all_ok());
+    assert_eq!(
+        health.to_string(),
+        "pressure sensor failed, no downward vision, strong wind"
+    );
+}
+#[test]
+pub fn test_fly_mode() {
+    let mode = |mode: u8| {
+        let mut data = vec![0u8; 24];
+        data[18] = mode;
+        FlightData::from(data).fly_mode_enum()
+    };
+    assert_eq!(mode(1), FlyMode::TakingOff);
+    assert_eq!(mode(6), FlyMode::Hovering);
+    assert_eq!(mode(11), FlyMode::Landing);
+    assert_eq!(mode(12), FlyMode::Obstacle);
+    assert_eq!(mode(0), FlyMode::Unknown(0));
+    assert_eq!(mode(41), FlyMode::Unknown(41));
+}
+#[test]
+pub fn test_firmware_version() {
+    // Tello, Tello EDU and the Tello TT
+    let tello = FirmwareVersion::try_from("01.04.91.01").unwrap();
+    assert_eq!(tello.numbers(), (1, 4, 91, 1));
+    assert_eq!(tello.to_string(), "01.04.91.01");
+    let edu = FirmwareVersion::try_from("02.04.69.85\0\0").unwrap();
+    assert_eq!(edu.numbers(), (2, 4, 69, 85));
+    assert_eq!(edu.raw, "02.04.69.85");
+    let tt = FirmwareVersion::try_from("v02.05.01.19").unwrap();
+    assert_eq!(tt.numbers(), (2, 5, 1, 19));
+    assert_eq!(FirmwareVersion::try_from("1.3.62").unwrap().build, 0);
+    assert!(FirmwareVersion::try_from("").is_err());
+    assert!(FirmwareVersion::try_from("01.04").is_err());
+    assert!(FirmwareVersion::try_from("01.04.91.01.02").is_err());
+    assert!(FirmwareVersion::try_from("01.04.x.01").is_err());
+
+    assert!(tello < edu);
+    assert!(edu < tt);
+    assert!(tello < FirmwareVersion::new(1, 4, 92, 0));
+    assert!(edu >= FirmwareVersion::new(2, 0, 0, 0));
+    assert_eq!(FirmwareVersion::new(1, 4, 91, 1), tello);
+    assert_eq!(FirmwareVersion::new(1, 4, 91, 1).raw, "01.04.91.01");
+
+    let mut meta = DroneMeta::default();
+    assert_eq!(meta.firmware(), None);
+    meta.update(&PackageData::Version("01.04.91.01".into()));
+    assert_eq!(meta.firmware(), Some(tello.clone()));
+    meta.update(&PackageData::Version("unknown".into()));
+    assert_eq!(meta.firmware(), Some(tello));
+    meta.update(&PackageData::Version("\u{fffd}02.04\0".into()));
+    assert_eq!(meta.firmware().map(|f| f.numbers()), Some((1, 4, 91, 1)));
+    meta.update(&PackageData::Version("02.04.69.85".into()));
+    assert_eq!(meta.firmware(), Some(edu));
+}
@@ -0,0 +1,148 @@
+use crate::command_mode::CommandError;
+use std::time::Duration;
+
+/// one step of a `Mission`
+#[derive(Debug, Clone, PartialEq)]
+pub enum MissionStep {
+    TakeOff,
+    Land,
+    Up(u32),
+    Down(u32),
+    Left(u32),
+    Right(u32),
+    Forward(u32),
+    Back(u32),
+    Cw(u32),
+    Ccw(u32),
+    GoTo {
+        x: i32,
+        y: i32,
+        z: i32,
+        speed: u8,
+    },
+    /// hover for the given time
+    WaitFor(Duration),
+}
+
+/// outcome of a step, see `StepReport`
+#[derive(Debug, Clone, PartialEq)]
+pub enum StepResult {
+    /// the drone confirmed the step
+    Done,
+    /// the drone rejected the step or did not respond
+    Failed(CommandError),
+    /// the mission was aborted during this step
+    Aborted,
+    /// not executed, because a previous step failed or the mission was aborted
+    Skipped,
+}
+
+/// result of a step, returned by `CommandMode::run_mission()`
+#[derive(Debug, Clone, PartialEq)]
+pub struct StepReport {
+    pub step: MissionStep,
+    pub result: StepResult,
+}
+
+/// A list of steps, executed one after the other by `CommandMode::run_mission()`.
+///
+/// Each step waits for the response of the drone, there is no need for a fixed sleep
+/// between the motions.
+///
+/// # Example
+///
+/// ```no_run
+/// # use tello::{CommandMode, Mission};
+/// # use std::time::Duration;
+/// # async fn fly(drone: &mut CommandMode) {
+/// let mission = Mission::new()
+///     .take_off()
+///     .forward(100)
+///     .cw(90)
+///     .wait_for(Duration::from_secs(2))
+///     .land();
+/// let report = drone.run_mission(&mission).await;
+/// # }
+/// ```
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Mission {
+    steps: Vec<MissionStep>,
+}
+
+impl Mission {
+    /// create an empty mission
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// steps of the mission
+    pub fn steps(&self) -> &[MissionStep] {
+        &self.steps
+    }
+    /// add any step
+    pub fn step(mut self, step: MissionStep) -> Self {
+        self.steps.push(step);
+        self
+    }
+    pub fn take_off(self) -> Self {
+        self.step(MissionStep::TakeOff)
+    }
+    pub fn land(self) -> Self {
+        self.step(MissionStep::Land)
+    }
+    pub fn up(self, cm: u32) -> Self {
+        self.step(MissionStep::Up(cm))
+    }
+    pub fn down(self, cm: u32) -> Self {
+        self.step(MissionStep::Down(cm))
+    }
+    pub fn left(self, cm: u32) -> Self {
+        self.step(MissionStep::Left(cm))
+    }
+    pub fn right(self, cm: u32) -> Self {
+        self.step(MissionStep::Right(cm))
+    }
+    pub fn forward(self, cm: u32) -> Self {
+        self.step(MissionStep::Forward(cm))
+    }
+    pub fn back(self, cm: u32) -> Self {
+        self.step(MissionStep::Back(cm))
+    }
+    pub fn cw(self, degree: u32) -> Self {
+        self.step(MissionStep::Cw(degree))
+    }
+    pub fn ccw(self, degree: u32) -> Self {
+        self.step(MissionStep::Ccw(degree))
+    }
+    /// see `CommandMode::go_to()`
+    pub fn go_to(self, x: i32, y: i32, z: i32, speed: u8) -> Self {
+        self.step(MissionStep::GoTo { x, y, z, speed })
+    }
+    pub fn wait_for(self, duration: Duration) -> Self {
+        self.step(MissionStep::WaitFor(duration))
+    }
+}
+
+#[test]
+pub fn test_mission_builder() {
+    let mission = Mission::new()
+        .take_off()
+        .forward(100)
+        .go_to(50, -20, 0, 30)
+        .wait_for(Duration::from_millis(10))
+        .land();
+    assert_eq!(
+        mission.steps(),
+        &[
+            MissionStep::TakeOff,
+            MissionStep::Forward(100),
+            MissionStep::GoTo {
+                x: 50,
+                y: -20,
+                z: 0,
+                speed: 30
+            },
+            MissionStep::WaitFor(Duration::from_millis(10)),
+            MissionStep::Land,
+        ]
+    );
+}
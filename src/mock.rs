@@ -0,0 +1,514 @@
+//! A fake drone on localhost, to test the binary protocol (`Drone`) and the SDK protocol
+//! (`CommandMode`) without the hardware. Enabled with the `mock` feature.
+//!
+//! ```no_run
+//! # use tello::mock::{MockConfig, MockDrone};
+//! # use tello::{CommandIds, Drone};
+//! let mock = MockDrone::start(MockConfig::default().with_battery(80)).unwrap();
+//! let mut drone = Drone::new(&mock.addr().to_string());
+//! drone.connect(11111);
+//! // poll the drone ...
+//! assert!(mock.packets().contains(&CommandIds::TakeoffCmd));
+//! ```
+use crate::crc::crc16;
+use crate::picture::file_chunk;
+use crate::{CommandIds, PackageTypes, UdpCommand};
+use std::io;
+use std::net::{SocketAddr, UdpSocket};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+/// state of the SDK protocol, send to the state port after the `command` command
+const DEFAULT_STATE: &str = "mid:-1;x:0;y:0;z:0;mpry:0,0,0;pitch:0;roll:0;yaw:0;vgx:0;vgy:0;\
+vgz:0;templ:60;temph:62;tof:10;h:0;bat:87;baro:100.00;time:0;agx:0.00;agy:0.00;agz:-1000.00;\r\n";
+
+/// a synthetic clip: SPS, PPS and an IDR frame. The NAL units are not decodable, replace them
+/// with `with_video_clip()` to test a decoder.
+fn default_clip() -> Vec<Vec<u8>> {
+    vec![
+        vec![0, 0, 0, 1, 0x67, 0x4d, 0x40, 0x28],
+        vec![0, 0, 0, 1, 0x68, 0xee, 0x3c, 0x80],
+        [&[0u8, 0, 0, 1, 0x65][..], &[0x88; 3000][..]].concat(),
+    ]
+}
+
+/// a synthetic JPEG of ten chunks, the binary take picture command sends it
+fn default_picture() -> Vec<u8> {
+    [&[0xffu8, 0xd8][..], &[0x42; 9996][..], &[0xff, 0xd9][..]].concat()
+}
+
+/// behaviour of the `MockDrone`
+#[derive(Debug, Clone)]
+pub struct MockConfig {
+    /// interval of the flight data, wifi info, state and video packets
+    pub interval: Duration,
+    /// height (dm) of the flight data
+    pub height: i16,
+    pub battery_percentage: u8,
+    pub wifi_strength: u8,
+    pub wifi_disturb: u8,
+    /// state string of the SDK protocol
+    pub state: String,
+    /// h264 frames (Annex-B), send in a loop after the video is started
+    pub video_clip: Vec<Vec<u8>>,
+    /// replies of the SDK protocol: the first rule with a prefix of the command is used,
+    /// otherwise the reply is `ok` (`battery?` replies the `battery_percentage`)
+    pub rules: Vec<(String, String)>,
+    /// state port of the SDK protocol, until it is changed with the `port` command
+    pub state_port: u16,
+    /// video port of the SDK protocol, until it is changed with the `port` command
+    pub video_port: u16,
+    /// climb rate in cm/s at the full up stick of the `rc` command. If it is set, the `h` and
+    /// `tof` of the state follow the simulated height.
+    pub climb_rate: Option<f32>,
+    /// drop the last packet of every binary video frame, like a lossy link
+    pub truncate_frames: bool,
+    /// report `battery_lower` in the flight data
+    pub battery_lower: bool,
+    /// the file, send after the binary take picture command
+    pub picture: Vec<u8>,
+    /// answer the `conn_req` of the binary protocol, `false` for a drone in the SDK mode only
+    pub binary: bool,
+}
+
+impl Default for MockConfig {
+    fn default() -> Self {
+        Self {
+            interval: Duration::from_millis(100),
+            height: 0,
+            battery_percentage: 87,
+            wifi_strength: 90,
+            wifi_disturb: 0,
+            state: DEFAULT_STATE.to_string(),
+            video_clip: default_clip(),
+            rules: Vec::new(),
+            state_port: 8890,
+            video_port: 11111,
+            climb_rate: None,
+            truncate_frames: false,
+            battery_lower: false,
+            picture: default_picture(),
+            binary: true,
+        }
+    }
+}
+
+impl MockConfig {
+    pub fn with_interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+    pub fn with_height(mut self, height: i16) -> Self {
+        self.height = height;
+        self
+    }
+    pub fn with_battery(mut self, percentage: u8) -> Self {
+        self.battery_percentage = percentage;
+        self
+    }
+    pub fn with_wifi(mut self, strength: u8, disturb: u8) -> Self {
+        self.wifi_strength = strength;
+        self.wifi_disturb = disturb;
+        self
+    }
+    pub fn with_state(mut self, state: &str) -> Self {
+        self.state = state.to_string();
+        self
+    }
+    pub fn with_video_clip(mut self, frames: Vec<Vec<u8>>) -> Self {
+        self.video_clip = frames;
+        self
+    }
+    /// reply `response` to the commands starting with `command`, e.g. `("flip", "error")`.
+    /// Rules added later have a lower priority.
+    pub fn with_rule(mut self, command: &str, response: &str) -> Self {
+        self.rules.push((command.to_string(), response.to_string()));
+        self
+    }
+    pub fn with_state_port(mut self, port: u16) -> Self {
+        self.state_port = port;
+        self
+    }
+    pub fn with_video_port(mut self, port: u16) -> Self {
+        self.video_port = port;
+        self
+    }
+    /// simulate the height of the SDK protocol, see `climb_rate`
+    pub fn with_climb_rate(mut self, cm_per_s: f32) -> Self {
+        self.climb_rate = Some(cm_per_s);
+        self
+    }
+    /// see `truncate_frames`
+    pub fn with_truncated_frames(mut self) -> Self {
+        self.truncate_frames = true;
+        self
+    }
+    /// see `battery_lower`
+    pub fn with_battery_lower(mut self) -> Self {
+        self.battery_lower = true;
+        self
+    }
+    /// see `picture`
+    pub fn with_picture(mut self, jpeg: Vec<u8>) -> Self {
+        self.picture = jpeg;
+        self
+    }
+    /// see `binary`
+    pub fn without_binary(mut self) -> Self {
+        self.binary = false;
+        self
+    }
+}
+
+/// what the mock received so far
+#[derive(Debug, Default)]
+struct Received {
+    packets: Vec<CommandIds>,
+    /// receive time of each stick command
+    stick_times: Vec<Instant>,
+    commands: Vec<String>,
+    /// number of the `conn_req` handshakes of the binary protocol
+    connections: usize,
+}
+
+/// A fake drone on a free localhost port, see the module documentation.
+///
+/// The thread of the mock stops, when it is dropped.
+#[derive(Debug)]
+pub struct MockDrone {
+    addr: SocketAddr,
+    received: Arc<Mutex<Received>>,
+    shutdown: Arc<AtomicBool>,
+    offline: Arc<AtomicBool>,
+    worker: Option<JoinHandle<()>>,
+}
+
+impl MockDrone {
+    /// bind the mock on a free localhost port and start it
+    pub fn start(config: MockConfig) -> io::Result<MockDrone> {
+        let socket = UdpSocket::bind("127.0.0.1:0")?;
+        socket.set_read_timeout(Some(Duration::from_millis(5)))?;
+        let addr = socket.local_addr()?;
+        let received = Arc::new(Mutex::new(Received::default()));
+        let shutdown = Arc::new(AtomicBool::new(false));
+        let offline = Arc::new(AtomicBool::new(false));
+
+        let mut server = Server {
+            socket,
+            video_socket: UdpSocket::bind("127.0.0.1:0")?,
+            config,
+            received: received.clone(),
+            offline: offline.clone(),
+            binary_client: None,
+            binary_video: None,
+            sdk_client: None,
+            sdk_video: false,
+            frame_id: 0,
+            next_frame: 0,
+            height: 0.0,
+            up_down: 0.0,
+            last_state: Instant::now(),
+            in_sky: false,
+            file_id: 0,
+        };
+        let stop = shutdown.clone();
+        let worker = std::thread::spawn(move || server.run(&stop));
+        Ok(MockDrone {
+            addr,
+            received,
+            shutdown,
+            offline,
+            worker: Some(worker),
+        })
+    }
+
+    /// address of the mock, use it instead of `192.168.10.1:8889`
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    /// command ids of the received binary packages, including the stick commands
+    pub fn packets(&self) -> Vec<CommandIds> {
+        self.received.lock().unwrap().packets.clone()
+    }
+
+    /// the times, the stick commands were received, e.g. to measure the latency
+    pub fn stick_times(&self) -> Vec<Instant> {
+        self.received.lock().unwrap().stick_times.clone()
+    }
+
+    /// received commands of the SDK protocol
+    pub fn commands(&self) -> Vec<String> {
+        self.received.lock().unwrap().commands.clone()
+    }
+
+    /// number of the received `conn_req` handshakes of the binary protocol
+    pub fn connections(&self) -> usize {
+        self.received.lock().unwrap().connections
+    }
+
+    /// Simulate a lost wifi link: while offline, the received packets are dropped and nothing
+    /// is send. The binary client is forgotten, it has to send the `conn_req` again.
+    pub fn set_offline(&self, offline: bool) {
+        self.offline.store(offline, Ordering::SeqCst);
+    }
+}
+
+impl Drop for MockDrone {
+    fn drop(&mut self) {
+        self.shutdown.store(true, Ordering::SeqCst);
+        if let Some(worker) = self.worker.take() {
+            let _ = worker.join();
+        }
+    }
+}
+
+struct Server {
+    socket: UdpSocket,
+    video_socket: UdpSocket,
+    config: MockConfig,
+    received: Arc<Mutex<Received>>,
+    offline: Arc<AtomicBool>,
+    /// client of the binary protocol, after `conn_req`
+    binary_client: Option<SocketAddr>,
+    /// video address of the binary client, after the video is started
+    binary_video: Option<SocketAddr>,
+    /// client of the SDK protocol, after `command`
+    sdk_client: Option<SocketAddr>,
+    sdk_video: bool,
+    frame_id: u8,
+    next_frame: usize,
+    /// simulated height in cm and the up/down stick (-1 - 1) of the last `rc` command
+    height: f32,
+    up_down: f32,
+    last_state: Instant,
+    /// the binary take off and land switch the `em_sky` flag of the flight data
+    in_sky: bool,
+    /// id of the last picture
+    file_id: u16,
+}
+
+impl Server {
+    fn run(&mut self, shutdown: &AtomicBool) {
+        let mut buf = [0u8; 1500];
+        let mut last_tick = Instant::now();
+        while !shutdown.load(Ordering::SeqCst) {
+            let received = self.socket.recv_from(&mut buf);
+            if self.offline.load(Ordering::SeqCst) {
+                self.binary_client = None;
+                self.binary_video = None;
+                continue;
+            }
+            if let Ok((len, src)) = received {
+                self.handle(&buf[..len], src);
+            }
+            if last_tick.elapsed() >= self.config.interval {
+                last_tick = Instant::now();
+                self.tick();
+            }
+        }
+    }
+
+    fn handle(&mut self, data: &[u8], src: SocketAddr) {
+        if data.starts_with(b"conn_req:") && data.len() >= 11 {
+            if !self.config.binary {
+                return;
+            }
+            let video_port = u16::from_le_bytes([data[9], data[10]]);
+            self.received.lock().unwrap().connections += 1;
+            self.binary_client = Some(src);
+            self.binary_video = Some(SocketAddr::new(src.ip(), video_port));
+            let mut ack = b"conn_ack:".to_vec();
+            ack.extend_from_slice(&data[9..11]);
+            let _ = self.socket.send_to(&ack, src);
+        } else if data.first() == Some(&0xcc) && data.len() >= 11 {
+            self.handle_packet(data, src);
+        } else if let Ok(command) = std::str::from_utf8(data) {
+            self.handle_command(command.trim(), src);
+        }
+    }
+
+    /// ack the packages of the binary protocol, like the drone with the command id and the
+    /// sequence number
+    fn handle_packet(&mut self, data: &[u8], src: SocketAddr) {
+        let cmd = CommandIds::from(u16::from_le_bytes([data[5], data[6]]));
+        let mut received = self.received.lock().unwrap();
+        received.packets.push(cmd);
+        if cmd == CommandIds::StickCmd {
+            received.stick_times.push(Instant::now());
+        }
+        drop(received);
+        match cmd {
+            CommandIds::StickCmd => return,
+            CommandIds::TakeoffCmd => self.in_sky = true,
+            CommandIds::LandCmd => self.in_sky = false,
+            // the answer of the file announcement, and the acks of the pieces
+            CommandIds::TelloCmdFileSize => return self.send_picture(src),
+            CommandIds::TelloCmdFileData | CommandIds::TelloCmdFileComplete => return,
+            _ => (),
+        }
+        let mut ack = UdpCommand::new(cmd, PackageTypes::X50);
+        ack.write_u8(0);
+        let mut ack: Vec<u8> = ack.into();
+        // reply with the sequence number of the command
+        ack[7..9].copy_from_slice(&data[7..9]);
+        let crc = crc16(ack[..ack.len() - 2].iter().copied());
+        let len = ack.len();
+        ack[len - 2..].copy_from_slice(&crc.to_le_bytes());
+        let _ = self.socket.send_to(&ack, src);
+
+        if cmd == CommandIds::TakePictureCommand {
+            // announce the file: type, size and id
+            self.file_id = self.file_id.wrapping_add(1);
+            let mut announcement = vec![1];
+            announcement.extend_from_slice(&(self.config.picture.len() as u32).to_le_bytes());
+            announcement.extend_from_slice(&self.file_id.to_le_bytes());
+            let mut size = UdpCommand::new(CommandIds::TelloCmdFileSize, PackageTypes::X50);
+            size.write(&announcement);
+            let _ = self.socket.send_to(&Into::<Vec<u8>>::into(size), src);
+        }
+    }
+
+    /// send the chunks of the picture
+    fn send_picture(&mut self, src: SocketAddr) {
+        for (index, chunk) in self.config.picture.chunks(1024).enumerate() {
+            let mut data = UdpCommand::new(CommandIds::TelloCmdFileData, PackageTypes::X50);
+            data.write(&file_chunk(self.file_id, index as u32, chunk));
+            let _ = self.socket.send_to(&Into::<Vec<u8>>::into(data), src);
+        }
+    }
+
+    fn handle_command(&mut self, command: &str, src: SocketAddr) {
+        self.received
+            .lock()
+            .unwrap()
+            .commands
+            .push(command.to_string());
+
+        let mut words = command.split_whitespace();
+        match (words.next(), words.next(), words.next()) {
+            (Some("command"), ..) => self.sdk_client = Some(src),
+            (Some("streamon"), ..) => self.sdk_video = true,
+            (Some("streamoff"), ..) => self.sdk_video = false,
+            (Some("port"), Some(state), Some(video)) => {
+                if let (Ok(state), Ok(video)) = (state.parse(), video.parse()) {
+                    self.config.state_port = state;
+                    self.config.video_port = video;
+                }
+            }
+            _ => (),
+        }
+        // `rc` commands have no response
+        if command.starts_with("rc ") {
+            if let Some(Ok(up_down)) = command.split_whitespace().nth(3).map(str::parse::<f32>) {
+                self.up_down = (up_down / 100.0).clamp(-1.0, 1.0);
+            }
+            return;
+        }
+        let response = match self
+            .config
+            .rules
+            .iter()
+            .find(|(prefix, _)| command.starts_with(prefix.as_str()))
+        {
+            Some((_, response)) => response.clone(),
+            None if command == "battery?" => self.config.battery_percentage.to_string(),
+            None => "ok".to_string(),
+        };
+        let _ = self.socket.send_to(response.as_bytes(), src);
+    }
+
+    /// send the periodic packets
+    fn tick(&mut self) {
+        if let Some(client) = self.binary_client {
+            let _ = self.socket.send_to(&self.flight_data(), client);
+            let mut wifi = UdpCommand::new(CommandIds::WifiMsg, PackageTypes::X50);
+            wifi.write(&[self.config.wifi_strength, self.config.wifi_disturb]);
+            let _ = self.socket.send_to(&Into::<Vec<u8>>::into(wifi), client);
+        }
+        if let Some(client) = self.sdk_client {
+            let state_addr = SocketAddr::new(client.ip(), self.config.state_port);
+            let state = self.state();
+            let _ = self.socket.send_to(state.as_bytes(), state_addr);
+        }
+        if self.config.video_clip.is_empty() {
+            return;
+        }
+        let frame = self.config.video_clip[self.next_frame % self.config.video_clip.len()].clone();
+        self.next_frame += 1;
+        if let Some(video) = self.binary_video {
+            if self
+                .received
+                .lock()
+                .unwrap()
+                .packets
+                .contains(&CommandIds::VideoStartCmd)
+            {
+                self.send_binary_frame(&frame, video);
+            }
+        }
+        if let (Some(client), true) = (self.sdk_client, self.sdk_video) {
+            let video = SocketAddr::new(client.ip(), self.config.video_port);
+            // the SDK stream has no header, a frame ends with a short packet
+            let mut last_len = 0;
+            for chunk in frame.chunks(1460) {
+                let _ = self.video_socket.send_to(chunk, video);
+                last_len = chunk.len();
+            }
+            if last_len == 1460 {
+                let _ = self.video_socket.send_to(&[], video);
+            }
+        }
+    }
+
+    /// `[frame_id, sqn, data...]`, the frame is closed with an empty packet and the high bit
+    /// set in its sqn
+    fn send_binary_frame(&mut self, frame: &[u8], video: SocketAddr) {
+        let mut sqn = 0u8;
+        for chunk in frame.chunks(1460 - 2) {
+            let packet = [&[self.frame_id, sqn][..], chunk].concat();
+            let _ = self.video_socket.send_to(&packet, video);
+            sqn += 1;
+        }
+        if !self.config.truncate_frames {
+            let _ = self
+                .video_socket
+                .send_to(&[self.frame_id, sqn | 0x80], video);
+        }
+        self.frame_id = self.frame_id.wrapping_add(1);
+    }
+
+    /// the state string, with the simulated height
+    fn state(&mut self) -> String {
+        let climb_rate = match self.config.climb_rate {
+            Some(climb_rate) => climb_rate,
+            None => return self.config.state.clone(),
+        };
+        let dt = self.last_state.elapsed().as_secs_f32();
+        self.last_state = Instant::now();
+        self.height = (self.height + self.up_down * climb_rate * dt).max(0.0);
+        let height = self.height.round() as i16;
+        self.config
+            .state
+            .split_inclusive(';')
+            .map(|field| match field.split(':').next() {
+                Some("h") => format!("h:{};", height),
+                // the time of flight sensor reports at least 10cm
+                Some("tof") => format!("tof:{};", height.max(10)),
+                _ => field.to_string(),
+            })
+            .collect()
+    }
+
+    fn flight_data(&self) -> Vec<u8> {
+        let mut payload = [0u8; 24];
+        payload[..2].copy_from_slice(&self.config.height.to_le_bytes());
+        payload[12] = self.config.battery_percentage;
+        payload[17] = self.in_sky as u8 | (self.config.battery_lower as u8) << 6;
+        let mut packet = UdpCommand::new(CommandIds::FlightMsg, PackageTypes::X50);
+        packet.write(&payload);
+        packet.into()
+    }
+}
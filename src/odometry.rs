@@ -1,58 +1,158 @@
+/// estimated position of the drone in cm and the rotation in rad.
+///
+/// The odometry axes are fixed to the start position: `y` is forward, `x` to the right, `z` up
+/// and `rot` counter clock wise. The SDK commands `go` and `curve` use the body frame of the
+/// drone instead: `x` forward, `y` to the left and `z` up, see `go()`.
 #[derive(Default, Debug, PartialEq, Clone)]
 pub struct Odometry {
     pub x: f64,
     pub y: f64,
     pub z: f64,
     pub rot: f64,
+    /// motions send to the drone, without a confirmation
+    pending: Vec<String>,
+    /// reported yaw (deg) of the drone at `rot` 0, enables the yaw fusion
+    yaw_reference: Option<f64>,
 }
 
 impl Odometry {
-    fn translate(&mut self, x: f64, y: f64) -> () {
+    fn translate(&mut self, x: f64, y: f64) {
         self.x += x * self.rot.cos() - y * self.rot.sin();
         self.y += x * self.rot.sin() + y * self.rot.cos();
     }
 
-    pub fn reset(&mut self) -> () {
+    pub fn reset(&mut self) {
         self.x = 0.0;
         self.y = 0.0;
         self.z = 0.0;
         self.rot = 0.0;
+        self.pending.clear();
     }
 
-    pub fn up(&mut self, z: u32) -> () {
-        self.z += z.max(20).min(500) as f64;
+    /// motions that are send to the drone, but not confirmed (e.g. timeout or an error).
+    /// The drone may have moved, so the position is uncertain while this list is not empty.
+    pub fn pending_moves(&self) -> &[String] {
+        &self.pending
     }
-    pub fn down(&mut self, z: u32) -> () {
-        self.z -= z.max(20).min(500) as f64;
+
+    /// forget the pending motions, e.g. after the position is checked.
+    pub fn clear_pending(&mut self) {
+        self.pending.clear();
+    }
+
+    pub(crate) fn push_pending(&mut self, command: &str) {
+        self.pending.push(command.to_string());
+    }
+
+    /// the motion is confirmed or known to be aborted
+    pub(crate) fn resolve_pending(&mut self, command: &str) {
+        if let Some(idx) = self.pending.iter().rposition(|c| c == command) {
+            self.pending.remove(idx);
+        }
+    }
+
+    /// enable the yaw fusion. `deg` is the yaw, reported by the drone, in the direction of the
+    /// odometry `y` axis (e.g. the yaw after the take off).
+    ///
+    /// Afterwards `update_yaw()` replaces the commanded rotation with the reported one.
+    pub fn set_yaw_reference(&mut self, deg: f64) {
+        self.yaw_reference = Some(deg);
     }
-    pub fn right(&mut self, x: u32) -> () {
-        let x = x.max(20).min(500) as f64;
+
+    /// apply the yaw (deg, clock wise) of the state stream. Ignored without a yaw reference.
+    pub fn update_yaw(&mut self, deg: f64) {
+        if let Some(reference) = self.yaw_reference {
+            self.rot = -(deg - reference).to_radians();
+        }
+    }
+
+    /// heading in degrees [0, 360), clock wise like the yaw of the drone.
+    pub fn heading(&self) -> f64 {
+        (-self.rot.to_degrees()).rem_euclid(360.0)
+    }
+
+    /// horizontal distance in cm from the current position to the point `x`, `y`
+    pub fn distance_to(&self, x: f64, y: f64) -> f64 {
+        (x - self.x).hypot(y - self.y)
+    }
+
+    /// heading in degrees [0, 360) from the current position to the point `x`, `y`, clock wise
+    /// like `heading()`. Turn by the difference of both to face the point.
+    pub fn heading_to(&self, x: f64, y: f64) -> f64 {
+        (x - self.x)
+            .atan2(y - self.y)
+            .to_degrees()
+            .rem_euclid(360.0)
+    }
+
+    /// snap the height estimation to the height `h` (cm) of the `CommandModeState`.
+    pub fn correct_height(&mut self, h: i16) {
+        self.z = h as f64;
+    }
+
+    /// returns the position relative to the `origin` position and rotation.
+    pub fn relative_to(&self, origin: &Odometry) -> Odometry {
+        let dx = self.x - origin.x;
+        let dy = self.y - origin.y;
+        let (sin, cos) = (-origin.rot).sin_cos();
+        Odometry {
+            x: dx * cos - dy * sin,
+            y: dx * sin + dy * cos,
+            z: self.z - origin.z,
+            rot: self.rot - origin.rot,
+            pending: self.pending.clone(),
+            yaw_reference: self.yaw_reference,
+        }
+    }
+
+    /// the distances are added as they are, the command layer has to clamp them to the
+    /// range of the drone before.
+    pub fn up(&mut self, z: u32) {
+        self.z += z as f64;
+    }
+    pub fn down(&mut self, z: u32) {
+        self.z -= z as f64;
+    }
+    pub fn right(&mut self, x: u32) {
         self.translate(x as f64, 0.0);
     }
-    pub fn left(&mut self, x: u32) -> () {
-        let x = x.max(20).min(500) as f64;
-        self.translate(-x, 0.0);
+    pub fn left(&mut self, x: u32) {
+        self.translate(-(x as f64), 0.0);
+    }
+    pub fn forward(&mut self, y: u32) {
+        self.translate(0.0, y as f64);
     }
-    pub fn forward(&mut self, y: u32) -> () {
-        let y = y.max(20).min(500) as f64;
-        self.translate(0.0, y);
+    pub fn back(&mut self, y: u32) {
+        self.translate(0.0, -(y as f64));
     }
-    pub fn back(&mut self, y: u32) -> () {
-        let y = y.max(20).min(500) as f64;
-        self.translate(0.0, -y);
+    /// move by a displacement in the body frame of the drone (`x` forward, `y` left, `z` up),
+    /// like the SDK `go x y z` command.
+    pub fn go(&mut self, x: i32, y: i32, z: i32) {
+        self.translate(-y as f64, x as f64);
+        self.z += z as f64;
     }
-    pub fn cw(&mut self, rot: u32) -> () {
-        let mut rot: f64 = rot.max(1).min(3600).into();
-        rot = rot / 180.0 * std::f64::consts::PI;
-        self.rot -= rot
+    pub fn cw(&mut self, rot: u32) {
+        self.rot -= (rot as f64).to_radians()
     }
-    pub fn ccw(&mut self, rot: u32) -> () {
-        let mut rot: f64 = rot.max(1).min(3600).into();
-        rot = rot / 180.0 * std::f64::consts::PI;
-        self.rot += rot
+    pub fn ccw(&mut self, rot: u32) {
+        self.rot += (rot as f64).to_radians()
     }
 }
 
+#[test]
+pub fn test_relative_to() {
+    let mut origin = Odometry::default();
+    origin.forward(100);
+    origin.cw(90);
+    let mut p = origin.clone();
+    p.forward(100);
+    p.up(50);
+    let rel = p.relative_to(&origin);
+    assert_eq!(rel.x.round(), 0.0f64);
+    assert_eq!(rel.y.round(), 100.0f64);
+    assert_eq!(rel.z.round(), 50.0f64);
+    assert_eq!(rel.rot, 0.0f64);
+}
 #[test]
 pub fn test_go_back_again() {
     let mut p = Odometry::default();
@@ -109,3 +209,84 @@ pub fn test_go_square() {
     assert_eq!(p.x.round(), -100.0f64);
     assert_eq!(p.y.round(), -100.0f64);
 }
+#[test]
+pub fn test_exact_distances() {
+    let mut p = Odometry::default();
+    p.back(50);
+    assert_eq!(p.y, -50.0f64);
+    p.forward(10);
+    assert_eq!(p.y, -40.0f64);
+    p.up(0);
+    p.right(0);
+    assert_eq!((p.x, p.z), (0.0f64, 0.0f64));
+}
+#[test]
+pub fn test_pending_moves() {
+    let mut p = Odometry::default();
+    p.push_pending("forward 50");
+    p.push_pending("up 20");
+    p.resolve_pending("forward 50");
+    assert_eq!(p.pending_moves(), ["up 20"]);
+    p.correct_height(80);
+    assert_eq!(p.z, 80.0f64);
+    p.reset();
+    assert!(p.pending_moves().is_empty());
+}
+#[test]
+pub fn test_go_to_square() {
+    let mut p = Odometry::default();
+    p.go(100, 0, 50);
+    assert_eq!((p.x, p.y, p.z), (0.0f64, 100.0f64, 50.0f64));
+    p.go(0, -100, 0);
+    assert_eq!((p.x.round(), p.y.round()), (100.0f64, 100.0f64));
+    p.go(-100, 0, 0);
+    p.go(0, 100, -50);
+    assert_eq!((p.x.round(), p.y.round(), p.z), (0.0f64, 0.0f64, 0.0f64));
+}
+#[test]
+pub fn test_go_rotated() {
+    let mut p = Odometry::default();
+    p.cw(90);
+    p.go(100, 0, 0);
+    assert_eq!((p.x.round(), p.y.round()), (100.0f64, 0.0f64));
+    p.cw(90);
+    p.go(50, 50, 0);
+    assert_eq!((p.x.round(), p.y.round()), (150.0f64, -50.0f64));
+    let mut q = Odometry::default();
+    q.forward(100);
+    q.left(30);
+    p.reset();
+    p.go(100, 30, 0);
+    assert_eq!((p.x, p.y), (q.x, q.y));
+}
+#[test]
+pub fn test_square_with_reported_yaw() {
+    let mut p = Odometry::default();
+    p.set_yaw_reference(10.0);
+    // the drone turns 90° each time, the commands drift
+    for (commanded, reported) in [(88, 100.0), (93, 190.0), (91, 280.0)].iter() {
+        p.forward(100);
+        p.cw(*commanded);
+        p.update_yaw(*reported);
+    }
+    p.forward(100);
+    assert_eq!((p.x.round(), p.y.round()), (0.0f64, 0.0f64));
+    assert_eq!(p.heading().round(), 270.0f64);
+
+    p.reset();
+    p.ccw(90);
+    assert_eq!(p.heading().round(), 270.0f64);
+}
+#[test]
+pub fn test_distance_and_heading_to() {
+    let mut p = Odometry::default();
+    assert_eq!(p.distance_to(30.0, 40.0), 50.0);
+    assert_eq!(p.heading_to(0.0, 100.0), 0.0);
+    assert_eq!(p.heading_to(100.0, 0.0), 90.0);
+    assert_eq!(p.heading_to(-100.0, 0.0), 270.0);
+    p.forward(100);
+    p.cw(90);
+    p.forward(100);
+    assert_eq!(p.distance_to(0.0, 0.0).round(), 141.0);
+    assert_eq!(p.heading_to(0.0, 0.0).round(), 225.0);
+}
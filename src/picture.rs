@@ -0,0 +1,149 @@
+//! Download of the pictures of `Drone::take_picture()`.
+//!
+//! After the picture command, the drone announces the file with a `TelloCmdFileSize`
+//! package and sends it, after the announcement was answered, in chunks of 1024 bytes
+//! (`TelloCmdFileData`). Eight chunks are a piece, every piece is acknowledged. The last piece
+//! is acknowledged as done and followed by a `TelloCmdFileComplete`.
+use crate::{CommandIds, PackageTypes, UdpCommand};
+use byteorder::{LittleEndian, ReadBytesExt};
+use std::collections::{BTreeMap, HashSet};
+use std::io::Cursor;
+
+/// number of the chunks of a piece
+const CHUNKS_PER_PIECE: usize = 8;
+
+/// a chunk completed a piece or the whole file
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum FileProgress {
+    /// the piece is complete, acknowledge it
+    Piece(u32),
+    /// the last piece is complete, acknowledge it as done and send the complete
+    Done(u32, Vec<u8>),
+}
+
+/// a picture, received by chunks
+#[derive(Debug)]
+pub(crate) struct FileDownload {
+    id: u16,
+    size: usize,
+    received: usize,
+    chunks: BTreeMap<u32, Vec<u8>>,
+    /// received chunks per piece
+    pieces: BTreeMap<u32, HashSet<u32>>,
+}
+
+impl FileDownload {
+    /// start the download, announced by the payload of a `TelloCmdFileSize` package:
+    /// file type (u8), size (u32) and file id (u16)
+    pub fn start(payload: &[u8]) -> Option<FileDownload> {
+        let mut cur = Cursor::new(payload);
+        let _file_type = cur.read_u8().ok()?;
+        let size = cur.read_u32::<LittleEndian>().ok()? as usize;
+        let id = cur.read_u16::<LittleEndian>().ok()?;
+        Some(FileDownload {
+            id,
+            size,
+            received: 0,
+            chunks: BTreeMap::new(),
+            pieces: BTreeMap::new(),
+        })
+    }
+
+    pub fn id(&self) -> u16 {
+        self.id
+    }
+
+    /// Add the payload of a `TelloCmdFileData` package: file id (u16), piece (u32), chunk
+    /// (u32), length (u16) and the data. Chunks of other files and repeated chunks are ignored.
+    pub fn push(&mut self, payload: &[u8]) -> Option<FileProgress> {
+        let mut cur = Cursor::new(payload);
+        let id = cur.read_u16::<LittleEndian>().ok()?;
+        let piece = cur.read_u32::<LittleEndian>().ok()?;
+        let chunk = cur.read_u32::<LittleEndian>().ok()?;
+        let len = cur.read_u16::<LittleEndian>().ok()? as usize;
+        let data = payload.get(12..12 + len)?;
+        if id != self.id || self.chunks.contains_key(&chunk) {
+            return None;
+        }
+        self.received += len;
+        self.chunks.insert(chunk, data.to_vec());
+        let chunks = self.pieces.entry(piece).or_default();
+        chunks.insert(chunk);
+
+        if self.received >= self.size {
+            let file: Vec<u8> = std::mem::take(&mut self.chunks)
+                .into_values()
+                .flatten()
+                .collect();
+            Some(FileProgress::Done(piece, file))
+        } else if chunks.len() == CHUNKS_PER_PIECE {
+            Some(FileProgress::Piece(piece))
+        } else {
+            None
+        }
+    }
+
+    /// the answer of the announcement, the drone starts to send the chunks
+    pub fn size_ack() -> UdpCommand {
+        let mut cmd = UdpCommand::new(CommandIds::TelloCmdFileSize, PackageTypes::X50);
+        cmd.write_u8(0);
+        cmd
+    }
+
+    /// acknowledge a complete piece, the last one with `done`
+    pub fn piece_ack(&self, piece: u32, done: bool) -> UdpCommand {
+        let mut cmd = UdpCommand::new(CommandIds::TelloCmdFileData, PackageTypes::X50);
+        cmd.write_u8(done as u8);
+        cmd.write_u16(self.id);
+        cmd.write(&piece.to_le_bytes());
+        cmd
+    }
+
+    /// the download is complete
+    pub fn complete(&self) -> UdpCommand {
+        let mut cmd = UdpCommand::new(CommandIds::TelloCmdFileComplete, PackageTypes::X48);
+        cmd.write_u16(self.id);
+        cmd.write(&(self.size as u32).to_le_bytes());
+        cmd
+    }
+}
+
+/// the payload of a `TelloCmdFileData` package, see `FileDownload::push()`
+#[cfg(any(test, feature = "mock"))]
+pub(crate) fn file_chunk(id: u16, chunk: u32, data: &[u8]) -> Vec<u8> {
+    let piece = chunk / CHUNKS_PER_PIECE as u32;
+    let mut payload = id.to_le_bytes().to_vec();
+    payload.extend_from_slice(&piece.to_le_bytes());
+    payload.extend_from_slice(&chunk.to_le_bytes());
+    payload.extend_from_slice(&(data.len() as u16).to_le_bytes());
+    payload.extend_from_slice(data);
+    payload
+}
+
+#[test]
+pub fn test_file_download() {
+    let picture: Vec<u8> = (0..10_000).map(|i| i as u8).collect();
+    let mut announcement = vec![1];
+    announcement.extend_from_slice(&(picture.len() as u32).to_le_bytes());
+    announcement.extend_from_slice(&7u16.to_le_bytes());
+    let mut download = FileDownload::start(&announcement).unwrap();
+    assert_eq!(download.id(), 7);
+    assert!(FileDownload::start(&[1, 0, 0]).is_none());
+
+    let chunks: Vec<_> = picture.chunks(1024).collect();
+    // the chunks may arrive in a different order, or twice
+    for (index, chunk) in chunks.iter().enumerate().take(7).rev() {
+        assert_eq!(download.push(&file_chunk(7, index as u32, chunk)), None);
+    }
+    assert_eq!(download.push(&file_chunk(7, 0, chunks[0])), None);
+    assert_eq!(download.push(&file_chunk(8, 7, chunks[7])), None);
+    assert_eq!(
+        download.push(&file_chunk(7, 7, chunks[7])),
+        Some(FileProgress::Piece(0))
+    );
+    assert_eq!(download.push(&file_chunk(7, 8, chunks[8])), None);
+    assert_eq!(
+        download.push(&file_chunk(7, 9, chunks[9])),
+        Some(FileProgress::Done(1, picture))
+    );
+}
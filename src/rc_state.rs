@@ -1,4 +1,4 @@
-use std::time::SystemTime;
+use std::time::Instant;
 
 /// represent the current input to remote control the drone.
 #[derive(Clone, Debug, Default)]
@@ -7,9 +7,13 @@ pub struct RCState {
     forward_back: f32,
     turn: f32,
     up_down: f32,
+    /// offsets of the axes `(left_right, forward_back, up_down, turn)`, see `set_trim()`
+    trim: (f32, f32, f32, f32),
+    /// the `fast` flag of the stick command, see `set_sport_mode()`
+    sport_mode: bool,
 
     start_engines: bool,
-    start_engines_set_time: Option<SystemTime>,
+    start_engines_set_time: Option<Instant>,
 }
 
 impl RCState {
@@ -17,7 +21,22 @@ impl RCState {
     ///
     pub fn start_engines(&mut self) {
         self.start_engines = true;
-        self.start_engines_set_time = Some(SystemTime::now());
+        self.start_engines_set_time = Some(Instant::now());
+    }
+
+    /// hold the same key-combination to stop the motors, it toggles them like on the remote.
+    /// The sticks are released, so the drone does not move after the combination.
+    pub fn stop_engines(&mut self) {
+        self.hover();
+        self.start_engines();
+    }
+
+    /// release all sticks, the drone stops and hovers (the trims are kept)
+    pub fn hover(&mut self) {
+        self.left_right = 0.0;
+        self.forward_back = 0.0;
+        self.turn = 0.0;
+        self.up_down = 0.0;
     }
 
     /// returns the current stick parameter to send them to the drone
@@ -27,12 +46,7 @@ impl RCState {
     pub fn get_stick_parameter(&mut self) -> (f32, f32, f32, f32, bool) {
         if self.start_engines {
             if let Some(start) = self.start_engines_set_time {
-                let elapsed = SystemTime::now().duration_since(start);
-                if let Ok(time) = elapsed {
-                    if time.as_millis() > 350 {
-                        self.start_engines = false;
-                    }
-                } else {
+                if start.elapsed().as_millis() > 350 {
                     self.start_engines = false;
                 }
             } else {
@@ -40,16 +54,56 @@ impl RCState {
             }
             (-1.0, -1.0, -1.0, 1.0, true)
         } else {
+            let (lr, fb, ud, yaw) = self.trim;
+            let trimmed = |value: f32, trim: f32| (value + trim).clamp(-1.0, 1.0);
             (
-                self.up_down,
-                self.forward_back,
-                self.left_right,
-                self.turn,
-                true,
+                trimmed(self.up_down, ud),
+                trimmed(self.forward_back, fb),
+                trimmed(self.left_right, lr),
+                trimmed(self.turn, yaw),
+                self.sport_mode,
             )
         }
     }
 
+    /// Set the trim of the axes, e.g. `set_trim(0.0, -0.08, 0.0, 0.0)` cancels a drift forward.
+    ///
+    /// The trims are added to the sticks in `get_stick_parameter()` (clamped to -1 - 1) and kept
+    /// until they are set again. The values are clamped to -1 - 1 (NaN is 0.0) like the axes.
+    pub fn set_trim(&mut self, left_right: f32, forward_back: f32, up_down: f32, turn: f32) {
+        self.trim = (
+            clamp_axis(left_right),
+            clamp_axis(forward_back),
+            clamp_axis(up_down),
+            clamp_axis(turn),
+        );
+    }
+
+    /// the trim of the axes `(left_right, forward_back, up_down, turn)`
+    pub fn trim(&self) -> (f32, f32, f32, f32) {
+        self.trim
+    }
+
+    /// Fly in the sport mode: the `fast` flag of the stick command, the drone flies and turns
+    /// faster with the same sticks. It is off by default, like in the official app. The
+    /// combination of `start_engines()` is always send with the flag, like on the remote.
+    pub fn set_sport_mode(&mut self, enabled: bool) {
+        self.sport_mode = enabled;
+    }
+    /// the sport mode is on, see `set_sport_mode()`
+    pub fn sport_mode(&self) -> bool {
+        self.sport_mode
+    }
+
+    /// Set all axes at once, e.g. with the sticks of a game pad. The values are clamped to
+    /// -1 - 1 (NaN is 0.0), like in `go_left_right()` and the other analog setters.
+    pub fn set_axes(&mut self, left_right: f32, forward_back: f32, up_down: f32, turn: f32) {
+        self.left_right = clamp_axis(left_right);
+        self.forward_back = clamp_axis(forward_back);
+        self.up_down = clamp_axis(up_down);
+        self.turn = clamp_axis(turn);
+    }
+
     /// stop moving left or right by setting the axis to 0.0
     pub fn stop_left_right(&mut self) {
         self.left_right = 0.0;
@@ -74,12 +128,9 @@ impl RCState {
     }
 
     /// set a analog value to the left right axis
-    /// this value has to be between -1 and 1 (including), where -1 is left and 1 is right
+    /// the value is clamped to -1 - 1 (NaN is 0.0), where -1 is left and 1 is right
     pub fn go_left_right(&mut self, value: f32) {
-        assert!(value <= 1.0);
-        assert!(value >= -1.0);
-
-        self.left_right = value;
+        self.left_right = clamp_axis(value);
     }
 
     /// stop moving forward or back by setting the axis to 0.0
@@ -106,12 +157,9 @@ impl RCState {
     }
 
     /// set a analog value to the forward or back axis
-    /// this value has to be between -1 and 1 (including), where -1 is back and 1 is forward
+    /// the value is clamped to -1 - 1 (NaN is 0.0), where -1 is back and 1 is forward
     pub fn go_forward_back(&mut self, value: f32) {
-        assert!(value <= 1.0);
-        assert!(value >= -1.0);
-
-        self.forward_back = value;
+        self.forward_back = clamp_axis(value);
     }
 
     /// stop moving up or down by setting the axis to 0.0
@@ -138,12 +186,9 @@ impl RCState {
     }
 
     /// set a analog value to the up or down axis
-    /// this value has to be between -1 and 1 (including), where -1 is going down and 1 is flying up
+    /// the value is clamped to -1 - 1 (NaN is 0.0), where -1 is going down and 1 is flying up
     pub fn go_up_down(&mut self, value: f32) {
-        assert!(value <= 1.0);
-        assert!(value >= -1.0);
-
-        self.up_down = value;
+        self.up_down = clamp_axis(value);
     }
 
     /// stop turning by setting it to 0.0
@@ -170,11 +215,91 @@ impl RCState {
     }
 
     /// Set a analog value to turn the drone
-    /// This value has to be between -1 and 1 (including), where -1 is turning ccw and 1 is turning cw
+    /// The value is clamped to -1 - 1 (NaN is 0.0), where -1 is turning ccw and 1 is turning cw
     pub fn turn(&mut self, value: f32) {
-        assert!(value <= 1.0);
-        assert!(value >= -1.0);
+        self.turn = clamp_axis(value);
+    }
+}
 
-        self.turn = value;
+/// game pads report values slightly out of range or NaN, they must not panic mid flight
+fn clamp_axis(value: f32) -> f32 {
+    if value.is_nan() {
+        0.0
+    } else {
+        value.clamp(-1.0, 1.0)
     }
 }
+
+#[test]
+pub fn test_trim() {
+    let mut rc_state = RCState::default();
+    rc_state.set_trim(0.02, 0.08, -0.05, 0.0);
+    assert_eq!(rc_state.trim(), (0.02, 0.08, -0.05, 0.0));
+    // centered sticks send the trim
+    assert_eq!(
+        rc_state.get_stick_parameter(),
+        (-0.05, 0.08, 0.02, 0.0, false)
+    );
+    // full deflection is still clamped
+    rc_state.go_forward();
+    rc_state.go_down();
+    let (up_down, forward_back, ..) = rc_state.get_stick_parameter();
+    assert_eq!((up_down, forward_back), (-1.0, 1.0));
+    // the trims are kept, when the sticks are released
+    rc_state.stop_forward_back();
+    rc_state.stop_up_down();
+    assert_eq!(
+        rc_state.get_stick_parameter(),
+        (-0.05, 0.08, 0.02, 0.0, false)
+    );
+}
+
+#[test]
+pub fn test_set_axes() {
+    let mut rc_state = RCState::default();
+    rc_state.set_axes(0.5, -0.25, 1.0, -1.0);
+    assert_eq!(
+        rc_state.get_stick_parameter(),
+        (1.0, -0.25, 0.5, -1.0, false)
+    );
+    // out of range values are clamped
+    rc_state.set_axes(1.5, -3.0, f32::NAN, f32::INFINITY);
+    assert_eq!(rc_state.get_stick_parameter(), (0.0, -1.0, 1.0, 1.0, false));
+}
+
+#[test]
+pub fn test_clamp_analog_values() {
+    let mut rc_state = RCState::default();
+    rc_state.go_left_right(1.5);
+    rc_state.go_forward_back(-1.5);
+    rc_state.go_up_down(f32::NAN);
+    rc_state.turn(1.000_000_1);
+    assert_eq!(rc_state.get_stick_parameter(), (0.0, -1.0, 1.0, 1.0, false));
+
+    rc_state.go_left_right(f32::NAN);
+    rc_state.go_forward_back(1.5);
+    rc_state.go_up_down(-1.5);
+    rc_state.turn(f32::NAN);
+    assert_eq!(rc_state.get_stick_parameter(), (-1.0, 1.0, 0.0, 0.0, false));
+
+    // the trims as well
+    rc_state.hover();
+    rc_state.set_trim(f32::NAN, 1.5, -2.0, f32::NEG_INFINITY);
+    assert_eq!(rc_state.trim(), (0.0, 1.0, -1.0, -1.0));
+    assert_eq!(
+        rc_state.get_stick_parameter(),
+        (-1.0, 1.0, 0.0, -1.0, false)
+    );
+}
+
+#[test]
+pub fn test_sport_mode() {
+    let mut rc_state = RCState::default();
+    assert!(!rc_state.sport_mode());
+    rc_state.set_sport_mode(true);
+    assert_eq!(rc_state.get_stick_parameter(), (0.0, 0.0, 0.0, 0.0, true));
+    rc_state.set_sport_mode(false);
+    assert!(!rc_state.get_stick_parameter().4);
+    rc_state.start_engines();
+    assert!(rc_state.get_stick_parameter().4);
+}
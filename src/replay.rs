@@ -0,0 +1,278 @@
+//! Capture and replay the datagrams of the command socket, see `Drone::set_packet_logger()`.
+//!
+//! A capture is a sequence of records:
+//! - direction (u8): 0 received, 1 sent
+//! - time since the start of the capture in microseconds (u64, little endian)
+//! - length of the datagram (u32, little endian), at most 65535 bytes like an udp datagram
+//! - the datagram
+use crate::Message;
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+use std::convert::TryFrom;
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufReader, Read, Write};
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+/// the longest datagram of a record, a longer length is a broken capture
+const MAX_DATAGRAM: usize = 65_535;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Direction {
+    /// received from the drone
+    Received = 0,
+    /// send to the drone
+    Sent = 1,
+}
+
+/// a captured datagram
+#[derive(Debug, Clone, PartialEq)]
+pub struct Record {
+    pub direction: Direction,
+    /// time since the start of the capture
+    pub timestamp: Duration,
+    pub data: Vec<u8>,
+}
+
+/// append a record to the capture
+pub fn write_record<W: Write>(writer: &mut W, record: &Record) -> io::Result<()> {
+    // one write per record, so an aborted capture ends with a complete record
+    let mut buf = Vec::with_capacity(record.data.len() + 13);
+    buf.write_u8(record.direction as u8)?;
+    buf.write_u64::<LittleEndian>(record.timestamp.as_micros() as u64)?;
+    buf.write_u32::<LittleEndian>(record.data.len() as u32)?;
+    buf.extend_from_slice(&record.data);
+    writer.write_all(&buf)
+}
+
+/// read the next record of the capture, `None` at the end of the capture
+pub fn read_record<R: Read>(reader: &mut R) -> io::Result<Option<Record>> {
+    let direction = match reader.read_u8() {
+        Ok(0) => Direction::Received,
+        Ok(1) => Direction::Sent,
+        Ok(d) => {
+            let msg = format!("invalid direction {}", d);
+            return Err(io::Error::new(io::ErrorKind::InvalidData, msg));
+        }
+        Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(e) => return Err(e),
+    };
+    let timestamp = Duration::from_micros(reader.read_u64::<LittleEndian>()?);
+    let len = reader.read_u32::<LittleEndian>()? as usize;
+    if len > MAX_DATAGRAM {
+        let msg = format!("invalid datagram length {}", len);
+        return Err(io::Error::new(io::ErrorKind::InvalidData, msg));
+    }
+    let mut data = vec![0; len];
+    reader.read_exact(&mut data)?;
+    Ok(Some(Record {
+        direction,
+        timestamp,
+        data,
+    }))
+}
+
+/// Writer of the capture, see `Drone::set_packet_logger()`.
+#[derive(Debug)]
+pub(crate) struct PacketLogger {
+    file: File,
+    epoch: Instant,
+}
+
+impl PacketLogger {
+    /// open the file to append the records
+    pub(crate) fn create<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(Self {
+            file,
+            epoch: Instant::now(),
+        })
+    }
+
+    pub(crate) fn log(&mut self, direction: Direction, data: &[u8]) -> io::Result<()> {
+        let record = Record {
+            direction,
+            timestamp: self.epoch.elapsed(),
+            data: data.to_vec(),
+        };
+        write_record(&mut self.file, &record)
+    }
+}
+
+/// Replay of a capture. It parses the received datagrams, like the receive path of the
+/// `Drone`, and yields the same `Message` sequence.
+///
+/// The datagrams, that can't be parsed, are skipped like in `Drone::poll()`. A broken record
+/// ends the replay.
+///
+/// # Examples
+/// ```no_run
+/// # use tello::replay::Player;
+/// for msg in Player::open("flight.cap").unwrap().realtime(true) {
+///     println!("{:?}", msg);
+/// }
+/// ```
+#[derive(Debug)]
+pub struct Player<R = BufReader<File>> {
+    reader: R,
+    realtime: bool,
+    /// start of the replay and the timestamp of the first record
+    start: Option<(Instant, Duration)>,
+}
+
+impl Player {
+    /// open a capture of `Drone::set_packet_logger()`
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        Ok(Self::new(BufReader::new(File::open(path)?)))
+    }
+}
+
+impl<R: Read> Player<R> {
+    pub fn new(reader: R) -> Self {
+        Self {
+            reader,
+            realtime: false,
+            start: None,
+        }
+    }
+
+    /// replay the datagrams with the original timing, instead of as fast as possible
+    pub fn realtime(mut self, realtime: bool) -> Self {
+        self.realtime = realtime;
+        self
+    }
+
+    /// the next record (received and sent), without parsing or waiting
+    pub fn next_record(&mut self) -> io::Result<Option<Record>> {
+        read_record(&mut self.reader)
+    }
+
+    /// wait until the record is due
+    fn wait(&mut self, timestamp: Duration) {
+        let (start, first) = *self.start.get_or_insert((Instant::now(), timestamp));
+        let due = start + timestamp.saturating_sub(first);
+        let now = Instant::now();
+        if self.realtime && due > now {
+            std::thread::sleep(due - now);
+        }
+    }
+}
+
+impl<R: Read> Iterator for Player<R> {
+    type Item = Message;
+
+    fn next(&mut self) -> Option<Message> {
+        loop {
+            let record = self.next_record().ok()??;
+            if record.direction == Direction::Sent {
+                continue;
+            }
+            self.wait(record.timestamp);
+            if let Ok(msg) = Message::try_from(record.data) {
+                return Some(msg);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+use crate::{
+    CommandIds, Drone, Package, PackageData, PackageTypes, ResponseMsg, UdpCommand, DRONE_PORT,
+};
+
+#[cfg(test)]
+fn received(timestamp_ms: u64, data: Vec<u8>) -> Record {
+    Record {
+        direction: Direction::Received,
+        timestamp: Duration::from_millis(timestamp_ms),
+        data,
+    }
+}
+
+#[test]
+pub fn test_replay() {
+    let mut wifi = UdpCommand::new(CommandIds::WifiMsg, PackageTypes::X50);
+    wifi.write(&[80, 3]);
+    // a flight data package with only 21 bytes is skipped
+    let mut short_flight = UdpCommand::new(CommandIds::FlightMsg, PackageTypes::X50);
+    short_flight.write(&[0; 21]);
+
+    let mut capture = Vec::new();
+    let records = [
+        received(0, b"conn_ack:\x67\x2b".to_vec()),
+        Record {
+            direction: Direction::Sent,
+            timestamp: Duration::from_millis(1),
+            data: UdpCommand::new(CommandIds::TakeoffCmd, PackageTypes::X68).into(),
+        },
+        received(2, short_flight.into()),
+        received(3, wifi.into()),
+    ];
+    for record in &records {
+        write_record(&mut capture, record).unwrap();
+    }
+
+    let mut player = Player::new(&capture[..]);
+    assert_eq!(player.next_record().unwrap(), Some(records[0].clone()));
+    assert_eq!(player.next_record().unwrap(), Some(records[1].clone()));
+
+    let mut player = Player::new(&capture[..]);
+    match player.next() {
+        Some(Message::Response(ResponseMsg::Connected(_))) => (),
+        msg => panic!("unexpected {:?}", msg),
+    }
+    match player.next() {
+        Some(Message::Data(Package {
+            data: PackageData::WifiInfo(wifi),
+            ..
+        })) => assert_eq!((wifi.strength(), wifi.disturb()), (80, 3)),
+        msg => panic!("unexpected {:?}", msg),
+    }
+    assert!(player.next().is_none());
+
+    // a truncated record ends the replay
+    let player = Player::new(&capture[..capture.len() - 1]).realtime(true);
+    let start = Instant::now();
+    assert_eq!(player.count(), 1);
+    assert!(start.elapsed() >= Duration::from_millis(2));
+
+    // a broken length is an error, instead of a huge allocation
+    let mut broken = vec![0u8; 9];
+    broken.extend_from_slice(&u32::MAX.to_le_bytes());
+    let err = read_record(&mut &broken[..]).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    let mut longest = vec![0u8; 9];
+    longest.extend_from_slice(&65_535u32.to_le_bytes());
+    longest.resize(longest.len() + 65_535, 0);
+    let record = read_record(&mut &longest[..]).unwrap().unwrap();
+    assert_eq!(record.data.len(), 65_535);
+}
+
+#[test]
+pub fn test_packet_logger() {
+    let _port = DRONE_PORT.lock().unwrap_or_else(|e| e.into_inner());
+    let path = std::env::temp_dir().join(format!("tello-capture-{}.cap", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+
+    let fake_drone = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+    let mut drone = Drone::new(&fake_drone.local_addr().unwrap().to_string());
+    drone.set_packet_logger(&path).unwrap();
+    drone.take_off().unwrap();
+    let mut wifi = UdpCommand::new(CommandIds::WifiMsg, PackageTypes::X50);
+    wifi.write(&[80, 3]);
+    let wifi: Vec<u8> = wifi.into();
+    assert!(drone.handle_packet(wifi.clone()).is_some());
+    drone.stop_packet_logger();
+
+    let mut player = Player::open(&path).unwrap();
+    let sent = player.next_record().unwrap().unwrap();
+    assert_eq!(sent.direction, Direction::Sent);
+    assert_eq!(u16::from_le_bytes([sent.data[5], sent.data[6]]), 0x54);
+    let received = player.next_record().unwrap().unwrap();
+    assert_eq!(
+        (received.direction, received.data),
+        (Direction::Received, wifi)
+    );
+    assert!(received.timestamp >= sent.timestamp);
+    assert_eq!(player.next_record().unwrap(), None);
+    std::fs::remove_file(&path).unwrap();
+}
@@ -0,0 +1,424 @@
+//! Send the commands from one thread and poll the drone in another, see `Drone::split()`.
+use crate::replay::Direction;
+use crate::{
+    alt_limit_command, att_limit_command, battery_threshold_command, bounce_command, encode_packet,
+    flip_command, in_sky, land_command, motors_on, palm_land_command, rc_state_command,
+    take_off_command, take_picture_command, throw_and_go_command, CommandIds, ConnectProfile,
+    Drone, DroneError, DroneMeta, Flip, Message, RCState, Result, UdpCommand, VideoBitrate,
+    VideoFrameIter, VideoMode, MAX_PAYLOAD, SEQ_NO,
+};
+use std::net::UdpSocket;
+use std::ops::Deref;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+/// a package, sent by a `DroneController`
+#[derive(Debug)]
+struct SentPackage {
+    cmd_id: u16,
+    sq_nr: u16,
+    data: Vec<u8>,
+    at: Instant,
+}
+
+/// state of the controllers, used by the poller
+#[derive(Debug, Default)]
+struct Shared {
+    rc_state: Mutex<RCState>,
+    /// `set_rc()` changed the sticks, see `Drone::set_immediate_stick()`
+    sticks_changed: AtomicBool,
+    /// the last flight data reports the drone in the sky
+    in_sky: AtomicBool,
+    /// the last flight data reports running motors
+    motors_on: AtomicBool,
+}
+
+/// Sends the commands to the drone, created with `Drone::split()`.
+///
+/// It is cheap to clone, the clones share the socket and the `rc_state`. The commands are send
+/// immediately, the `DronePoller` tracks their acks with its next poll. The sticks are send in
+/// the interval of the poller, see `rc_state()`.
+///
+/// # Example
+///
+/// ```no_run
+/// # use tello::Drone;
+/// # use std::time::Duration;
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let (controller, mut poller) = Drone::new("192.168.10.1:8889").split()?;
+/// poller.connect(11111);
+/// std::thread::spawn(move || loop {
+///     if let Some(msg) = poller.poll() {
+///         // ...
+///     }
+///     std::thread::sleep(Duration::from_millis(10));
+/// });
+/// controller.take_off()?;
+/// controller.rc_state().go_forward_back(0.5);
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct DroneController {
+    socket: Arc<UdpSocket>,
+    shared: Arc<Shared>,
+    sent: Sender<SentPackage>,
+}
+
+impl DroneController {
+    /// see `Drone::send()`
+    pub fn send(&self, command: UdpCommand) -> Result {
+        if command.inner.len() > MAX_PAYLOAD {
+            return Err(DroneError::InvalidParameter);
+        }
+        let cmd_id = u16::from(command.cmd);
+        self.send_packet(cmd_id, command.into())
+    }
+
+    /// see `Drone::send_raw()`
+    pub fn send_raw(&self, cmd_id: u16, pkt_type: u8, payload: &[u8]) -> Result {
+        let sq_nr = SEQ_NO.fetch_add(1, Ordering::SeqCst);
+        self.send_packet(cmd_id, encode_packet(cmd_id, pkt_type, sq_nr, payload)?)
+    }
+
+    /// send the encoded package and pass it to the poller
+    fn send_packet(&self, cmd_id: u16, data: Vec<u8>) -> Result {
+        self.socket.send(&data).map_err(|_| DroneError::Network)?;
+        // the poller is gone, if the channel is closed
+        let _ = self.sent.send(SentPackage {
+            cmd_id,
+            sq_nr: u16::from_le_bytes([data[7], data[8]]),
+            data,
+            at: Instant::now(),
+        });
+        Ok(())
+    }
+
+    /// The remote control values, send by the poller with the next stick interval. E.g.
+    /// `controller.rc_state().go_left()`.
+    ///
+    /// The poller locks it with every poll, so do not keep the guard.
+    pub fn rc_state(&self) -> MutexGuard<'_, RCState> {
+        self.shared
+            .rc_state
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+    }
+
+    pub fn take_off(&self) -> Result {
+        self.send(take_off_command())
+    }
+    /// see `Drone::throw_and_go()`, the flight data of the last poll is checked
+    pub fn throw_and_go(&self) -> Result {
+        if self.shared.in_sky.load(Ordering::SeqCst) {
+            return Err(DroneError::InFlight);
+        }
+        self.send(throw_and_go_command())
+    }
+    pub fn land(&self) -> Result {
+        self.send(land_command())
+    }
+    pub fn stop_land(&self) -> Result {
+        self.send(land_command())
+    }
+    /// see `Drone::emergency()`, **the drone falls down**. The flight data of the last poll is
+    /// checked.
+    pub fn emergency(&self) -> Result {
+        if !self.shared.motors_on.load(Ordering::SeqCst) {
+            return Err(DroneError::MotorsOff);
+        }
+        let mut rc_state = self.rc_state();
+        rc_state.stop_engines();
+        self.send(rc_state_command(&mut rc_state))?;
+        drop(rc_state);
+        self.land()
+    }
+    /// see `Drone::hover()`
+    pub fn hover(&self) -> Result {
+        let mut rc_state = self.rc_state();
+        rc_state.hover();
+        self.send(rc_state_command(&mut rc_state))
+    }
+    /// Set the axes like `RCState::set_axes()`. With `set_immediate_stick(true)` of the poller,
+    /// they are send with its next poll (at most every 10ms).
+    pub fn set_rc(&self, left_right: f32, forward_back: f32, up_down: f32, turn: f32) {
+        self.rc_state()
+            .set_axes(left_right, forward_back, up_down, turn);
+        self.shared.sticks_changed.store(true, Ordering::SeqCst);
+    }
+    /// see `Drone::palm_land()`
+    pub fn palm_land(&self) -> Result {
+        self.send(palm_land_command())
+    }
+    pub fn flip(&self, direction: Flip) -> Result {
+        self.send(flip_command(direction))
+    }
+    pub fn bounce(&self) -> Result {
+        self.send(bounce_command(true))
+    }
+    pub fn bounce_stop(&self) -> Result {
+        self.send(bounce_command(false))
+    }
+    pub fn take_picture(&self) -> Result {
+        self.send(take_picture_command())
+    }
+    pub fn set_alt_limit(&self, limit: u8) -> Result {
+        self.send(alt_limit_command(limit))
+    }
+    /// see `Drone::set_att_angle()`
+    pub fn set_att_angle(&self, degrees: f32) -> Result {
+        self.send(att_limit_command(degrees)?)
+    }
+    pub fn set_battery_threshold(&self, threshold: u8) -> Result {
+        self.send(battery_threshold_command(threshold))
+    }
+}
+
+/// Receives the messages of the drone and sends the periodic data, created with
+/// `Drone::split()`.
+///
+/// The connection, the video and the settings of the poll loop are changed with the methods of
+/// the poller. The `Drone` is readable with `Deref`, e.g. for `video_stats()` or
+/// `connection_state()`. Its `rc_state` mirrors the sticks of the
+/// `DroneController::rc_state()`, they are send by the poller. The sockets are shared with the
+/// controllers, so `Drone::bind_to()` has to be called before `split()`.
+#[derive(Debug)]
+pub struct DronePoller {
+    drone: Drone,
+    shared: Arc<Shared>,
+    sent: Receiver<SentPackage>,
+}
+
+impl DronePoller {
+    pub(crate) fn new(drone: Drone) -> std::io::Result<(DroneController, DronePoller)> {
+        let socket = Arc::new(drone.socket.try_clone()?);
+        let shared = Arc::new(Shared {
+            rc_state: Mutex::new(drone.rc_state.clone()),
+            ..Shared::default()
+        });
+        let (sender, receiver) = channel();
+        let controller = DroneController {
+            socket,
+            shared: shared.clone(),
+            sent: sender,
+        };
+        let poller = DronePoller {
+            drone,
+            shared,
+            sent: receiver,
+        };
+        Ok((controller, poller))
+    }
+
+    /// see `Drone::poll()`
+    pub fn poll(&mut self) -> Option<Message> {
+        self.with_shared(Drone::poll)
+    }
+
+    /// see `Drone::tick_receive()`
+    pub fn tick_receive(&mut self) -> Option<Message> {
+        self.with_shared(Drone::tick_receive)
+    }
+
+    /// see `Drone::tick_send()`
+    pub fn tick_send(&mut self) {
+        self.with_shared(Drone::tick_send)
+    }
+
+    /// see `Drone::drone_meta`
+    pub fn drone_meta(&self) -> &DroneMeta {
+        &self.drone.drone_meta
+    }
+
+    /// see `Drone::connect()`
+    pub fn connect(&mut self, video_port: u16) -> usize {
+        self.drone.connect(video_port)
+    }
+    /// see `Drone::ensure_connected()`
+    pub fn ensure_connected(&mut self) -> bool {
+        self.drone.ensure_connected()
+    }
+    pub fn set_connect_profile(&mut self, profile: ConnectProfile) {
+        self.drone.set_connect_profile(profile)
+    }
+    pub fn set_reconnect_timeout(&mut self, timeout: Duration) {
+        self.drone.set_reconnect_timeout(timeout)
+    }
+    pub fn set_auto_reconnect(&mut self, enabled: bool) {
+        self.drone.set_auto_reconnect(enabled)
+    }
+
+    pub fn set_stick_interval(&mut self, interval: Duration) -> Result {
+        self.drone.set_stick_interval(interval)
+    }
+    pub fn set_stick_coalescing(&mut self, enabled: bool) {
+        self.drone.set_stick_coalescing(enabled)
+    }
+    /// see `Drone::set_immediate_stick()`, the sticks of `DroneController::set_rc()` are send
+    /// with the next poll
+    pub fn set_immediate_stick(&mut self, enabled: bool) {
+        self.drone.set_immediate_stick(enabled)
+    }
+    pub fn set_ack_timeout(&mut self, timeout: Duration) {
+        self.drone.set_ack_timeout(timeout)
+    }
+    pub fn set_ack_retries(&mut self, retries: u8) {
+        self.drone.set_ack_retries(retries)
+    }
+    pub fn reset_odometry(&mut self) {
+        self.drone.reset_odometry()
+    }
+    pub fn reset_packet_counts(&mut self) {
+        self.drone.reset_packet_counts()
+    }
+
+    /// see `Drone::start_video()`
+    pub fn start_video(&mut self) -> Result {
+        self.drone.start_video()
+    }
+    /// see `Drone::stop_video()`
+    pub fn stop_video(&mut self) {
+        self.drone.stop_video()
+    }
+    /// see `Drone::video_frames_iter()`
+    pub fn video_frames_iter(&mut self, capacity: usize) -> VideoFrameIter {
+        self.drone.video_frames_iter(capacity)
+    }
+    pub fn request_key_frame(&mut self) -> Result {
+        self.drone.request_key_frame()
+    }
+    pub fn set_keyframe_interval(&mut self, interval: Option<Duration>) -> Result {
+        self.drone.set_keyframe_interval(interval)
+    }
+    pub fn set_keyframe_window(&mut self, window: Duration) -> Result {
+        self.drone.set_keyframe_window(window)
+    }
+    /// see `Drone::set_video_mode()`
+    pub fn set_video_mode(&mut self, mode: VideoMode) -> Result {
+        self.drone.set_video_mode(mode)
+    }
+    pub fn set_exposure(&mut self, level: u8) -> Result {
+        self.drone.set_exposure(level)
+    }
+    pub fn set_video_bitrate(&mut self, rate: u8) -> Result {
+        self.drone.set_video_bitrate(rate)
+    }
+    pub fn set_video_bitrate_preset(&mut self, bitrate: VideoBitrate) -> Result {
+        self.drone.set_video_bitrate_preset(bitrate)
+    }
+    pub fn set_video_recv_buffer(&mut self, bytes: usize) -> std::io::Result<Option<usize>> {
+        self.drone.set_video_recv_buffer(bytes)
+    }
+    pub fn set_frame_timestamps(&mut self, enabled: bool) {
+        self.drone.set_frame_timestamps(enabled)
+    }
+
+    /// track the packages of the controllers and run `tick` with their `rc_state`
+    fn with_shared<T>(&mut self, tick: fn(&mut Drone) -> T) -> T {
+        while let Ok(sent) = self.sent.try_recv() {
+            self.track(sent);
+        }
+        let mut rc_state = self
+            .shared
+            .rc_state
+            .lock()
+            .unwrap_or_else(|e| e.into_inner());
+        if self.shared.sticks_changed.swap(false, Ordering::SeqCst) && self.drone.immediate_stick {
+            self.drone.sticks_changed = true;
+        }
+        // the stick parameters may change the state, e.g. the `start_engines()` combination ends
+        self.drone.rc_state = rc_state.clone();
+        let result = tick(&mut self.drone);
+        *rc_state = self.drone.rc_state.clone();
+        drop(rc_state);
+
+        let flight = self.drone.drone_meta.get_flight_data();
+        let flying = matches!(&flight, Some(flight) if in_sky(flight));
+        let running = matches!(&flight, Some(flight) if motors_on(flight));
+        self.shared.in_sky.store(flying, Ordering::SeqCst);
+        self.shared.motors_on.store(running, Ordering::SeqCst);
+        result
+    }
+
+    /// wait for the ack of a package of a controller, like the packages of `Drone::send()`
+    fn track(&mut self, sent: SentPackage) {
+        let drone = &mut self.drone;
+        drone.log_packet(Direction::Sent, &sent.data);
+        drone.track_sent(sent.cmd_id, sent.sq_nr, &sent.data, sent.at);
+        match CommandIds::from(sent.cmd_id) {
+            CommandIds::ThrowAndGoCmd => drone.gestures.arm_throw(sent.at),
+            CommandIds::PalmLandCmd => drone.gestures.start_palm_land(),
+            CommandIds::StickCmd => drone.last_stick_command = sent.at,
+            _ => (),
+        }
+    }
+}
+
+impl Deref for DronePoller {
+    type Target = Drone;
+    fn deref(&self) -> &Drone {
+        &self.drone
+    }
+}
+
+#[cfg(test)]
+use crate::DRONE_PORT;
+
+#[test]
+pub fn test_split() {
+    let _port = DRONE_PORT.lock().unwrap_or_else(|e| e.into_inner());
+    let fake_drone = UdpSocket::bind("127.0.0.1:0").unwrap();
+    fake_drone
+        .set_read_timeout(Some(Duration::from_millis(100)))
+        .unwrap();
+    let (controller, mut poller) = Drone::new(&fake_drone.local_addr().unwrap().to_string())
+        .split()
+        .unwrap();
+    let received = || {
+        let mut buf = [0; 64];
+        let mut ids = vec![];
+        while let Ok(len) = fake_drone.recv(&mut buf) {
+            ids.push(CommandIds::from(u16::from_le_bytes([buf[5], buf[6]])));
+            assert!(len > 8);
+        }
+        ids
+    };
+
+    let input = controller.clone();
+    std::thread::spawn(move || input.take_off().unwrap())
+        .join()
+        .unwrap();
+    controller.throw_and_go().unwrap();
+    // the motors are off, the poller did not receive flight data yet
+    assert_eq!(controller.emergency(), Err(DroneError::MotorsOff));
+    assert_eq!(
+        controller.set_att_angle(-1.0),
+        Err(DroneError::InvalidParameter)
+    );
+    assert_eq!(
+        received(),
+        vec![CommandIds::TakeoffCmd, CommandIds::ThrowAndGoCmd]
+    );
+    // the poller waits for the acks
+    poller.set_stick_interval(Duration::from_secs(1)).unwrap();
+    poller.poll();
+    let pending: Vec<_> = poller.pending_commands().iter().map(|p| p.cmd).collect();
+    assert_eq!(
+        pending,
+        vec![CommandIds::TakeoffCmd, CommandIds::ThrowAndGoCmd]
+    );
+    assert!(poller.is_armed_for_throw());
+
+    // the sticks of the controller are send by the poller
+    poller.set_immediate_stick(true);
+    controller.set_rc(0.0, 1.0, 0.0, 0.0);
+    std::thread::sleep(Duration::from_millis(10));
+    poller.poll();
+    assert_eq!(received(), vec![CommandIds::StickCmd]);
+    assert_eq!(poller.last_sticks, Some((0.0, 1.0, 0.0, 0.0, false)));
+    controller.hover().unwrap();
+    assert_eq!(received(), vec![CommandIds::StickCmd]);
+    assert_eq!(controller.rc_state().get_stick_parameter().1, 0.0);
+}
@@ -0,0 +1,243 @@
+//! Control several drones in the station mode (SDK), e.g. Tello EDUs connected to one access
+//! point, from one process.
+use crate::command_mode::{STATUS_PORT, VIDEO_PORT};
+use crate::{CommandError, CommandMode, CommandModeState, Mission, StepReport};
+use std::future::Future;
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+#[cfg(not(feature = "tokio_async"))]
+type StateReceiver = std::sync::mpsc::Receiver<CommandModeState>;
+#[cfg(feature = "tokio_async")]
+type StateReceiver = tokio::sync::watch::Receiver<Option<CommandModeState>>;
+
+/// a command of one drone, see `AllDrones::run()`
+pub type SwarmCommand<'a> = Pin<Box<dyn Future<Output = Result<(), CommandError>> + 'a>>;
+
+#[derive(Debug)]
+struct SwarmDrone {
+    name: String,
+    drone: CommandMode,
+    /// `None` if the receiver was taken before the drone was added
+    state: Option<StateReceiver>,
+    last_state: Option<CommandModeState>,
+}
+
+impl SwarmDrone {
+    /// the latest received state
+    fn state(&mut self) -> Option<CommandModeState> {
+        #[cfg(not(feature = "tokio_async"))]
+        if let Some(state) = &self.state {
+            while let Ok(next) = state.try_recv() {
+                self.last_state = Some(next);
+            }
+        }
+        #[cfg(feature = "tokio_async")]
+        if let Some(state) = &self.state {
+            self.last_state = state.borrow().clone();
+        }
+        self.last_state.clone()
+    }
+}
+
+/// Several drones in the station mode (SDK).
+///
+/// Each drone gets its own state and video port, the first drone uses 8890 / 11111, the
+/// next one 8891 / 11112 and so on (see `with_ports()`). The ports are send to the drones with
+/// `all().enable()`.
+///
+/// The commands of `all()` and the missions of `run_missions()` run concurrently and return the
+/// results in the order of the drones. In the sync build (without `tokio_async`) the commands
+/// block, so they run one after the other.
+///
+/// # Example
+///
+/// ```no_run
+/// # use tello::{CommandError, Flip, Swarm};
+/// # async fn fly() -> Result<(), CommandError> {
+/// let mut swarm = Swarm::default();
+/// swarm.add_drone("left", "192.168.1.11:8889".parse().unwrap())?;
+/// swarm.add_drone("right", "192.168.1.12:8889".parse().unwrap())?;
+/// for result in swarm.all().enable().await {
+///     result?;
+/// }
+/// swarm.all().take_off().await;
+/// swarm.by_name("left").unwrap().flip(Flip::Left).await?;
+/// swarm.all().land().await;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct Swarm {
+    drones: Vec<SwarmDrone>,
+    status_port: u16,
+    video_port: u16,
+}
+
+impl Default for Swarm {
+    fn default() -> Self {
+        Self {
+            drones: Vec::new(),
+            status_port: STATUS_PORT,
+            video_port: VIDEO_PORT,
+        }
+    }
+}
+
+impl Swarm {
+    /// the state and video port of the first drone, the following drones use the next ports
+    pub fn with_ports(mut self, status_port: u16, video_port: u16) -> Self {
+        self.status_port = status_port;
+        self.video_port = video_port;
+        self
+    }
+
+    /// Add the drone at `addr` (`<ip>:8889`) with the next state and video port. Returns the
+    /// index of the drone.
+    ///
+    /// The state port is bound for `states()`, it fails if the port is in use.
+    pub fn add_drone(&mut self, name: &str, addr: SocketAddr) -> Result<usize, CommandError> {
+        let offset = self.drones.len() as u16;
+        let drone = CommandMode::from(addr)
+            .with_status_port(self.status_port + offset)
+            .with_video_port(self.video_port + offset);
+        self.add(name, drone)
+    }
+
+    /// Add a configured drone, e.g. with another command timeout. Returns the index of the
+    /// drone.
+    ///
+    /// Its state receiver is used for `states()`, unless it was taken before.
+    pub fn add(&mut self, name: &str, mut drone: CommandMode) -> Result<usize, CommandError> {
+        let state = drone.state_receiver()?;
+        self.drones.push(SwarmDrone {
+            name: name.to_string(),
+            drone,
+            state,
+            last_state: None,
+        });
+        Ok(self.drones.len() - 1)
+    }
+
+    pub fn len(&self) -> usize {
+        self.drones.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.drones.is_empty()
+    }
+
+    /// names of the drones, in the order they were added
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.drones.iter().map(|d| d.name.as_str())
+    }
+
+    /// the drone with the `index` of `add_drone()`
+    pub fn drone(&mut self, index: usize) -> Option<&mut CommandMode> {
+        self.drones.get_mut(index).map(|d| &mut d.drone)
+    }
+
+    /// the first drone with the `name`
+    pub fn by_name(&mut self, name: &str) -> Option<&mut CommandMode> {
+        self.drones
+            .iter_mut()
+            .find(|d| d.name == name)
+            .map(|d| &mut d.drone)
+    }
+
+    /// send the commands to all drones
+    pub fn all(&mut self) -> AllDrones<'_> {
+        AllDrones {
+            drones: &mut self.drones,
+        }
+    }
+
+    /// Run the missions concurrently, the first mission with the first drone and so on. The
+    /// reports are in the order of the drones, a drone without a mission has an empty report.
+    pub async fn run_missions(&mut self, missions: &[Mission]) -> Vec<Vec<StepReport>> {
+        let mut futures: Vec<Pin<Box<dyn Future<Output = Vec<StepReport>> + '_>>> = Vec::new();
+        for (index, d) in self.drones.iter_mut().enumerate() {
+            match missions.get(index) {
+                Some(mission) => futures.push(Box::pin(d.drone.run_mission(mission))),
+                None => futures.push(Box::pin(async { Vec::new() })),
+            }
+        }
+        join_all(futures).await
+    }
+
+    /// the latest state of each drone, `None` until a drone sends its state
+    pub fn states(&mut self) -> Vec<Option<CommandModeState>> {
+        self.drones.iter_mut().map(SwarmDrone::state).collect()
+    }
+}
+
+/// The drones of a `Swarm`, created with `Swarm::all()`.
+#[derive(Debug)]
+pub struct AllDrones<'a> {
+    drones: &'a mut [SwarmDrone],
+}
+
+impl<'a> AllDrones<'a> {
+    /// run the `command` on all drones concurrently, e.g.
+    /// `swarm.all().run(|d| Box::pin(d.up(50))).await`
+    pub async fn run(
+        self,
+        command: impl Fn(&mut CommandMode) -> SwarmCommand<'_>,
+    ) -> Vec<Result<(), CommandError>> {
+        let futures = self.drones.iter_mut().map(|d| command(&mut d.drone));
+        join_all(futures.collect()).await
+    }
+    /// see `CommandMode::enable()`, sends the ports of the drones as well
+    pub async fn enable(self) -> Vec<Result<(), CommandError>> {
+        self.run(|d| Box::pin(d.enable())).await
+    }
+    pub async fn take_off(self) -> Vec<Result<(), CommandError>> {
+        self.run(|d| Box::pin(d.take_off())).await
+    }
+    pub async fn land(self) -> Vec<Result<(), CommandError>> {
+        self.run(|d| Box::pin(d.land())).await
+    }
+    /// see `CommandMode::stop()`
+    pub async fn stop(self) -> Vec<Result<(), CommandError>> {
+        self.run(|d| Box::pin(d.stop())).await
+    }
+    /// see `CommandMode::emergency()`, **the drones fall down**
+    pub async fn emergency(self) -> Vec<Result<(), CommandError>> {
+        self.run(|d| Box::pin(d.emergency())).await
+    }
+}
+
+/// polls the futures concurrently, the outputs are in the order of the futures
+struct JoinAll<'a, T> {
+    futures: Vec<Pin<Box<dyn Future<Output = T> + 'a>>>,
+    outputs: Vec<Option<T>>,
+}
+
+fn join_all<T>(futures: Vec<Pin<Box<dyn Future<Output = T> + '_>>>) -> JoinAll<'_, T> {
+    let outputs = futures.iter().map(|_| None).collect();
+    JoinAll { futures, outputs }
+}
+
+// the futures are pinned in their boxes, the outputs are never pinned
+impl<'a, T> Unpin for JoinAll<'a, T> {}
+
+impl<'a, T> Future for JoinAll<'a, T> {
+    type Output = Vec<T>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Vec<T>> {
+        let this = &mut *self;
+        for (future, output) in this.futures.iter_mut().zip(this.outputs.iter_mut()) {
+            if output.is_none() {
+                if let Poll::Ready(value) = future.as_mut().poll(cx) {
+                    *output = Some(value);
+                }
+            }
+        }
+        if this.outputs.iter().all(Option::is_some) {
+            Poll::Ready(this.outputs.iter_mut().filter_map(Option::take).collect())
+        } else {
+            Poll::Pending
+        }
+    }
+}
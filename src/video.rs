@@ -0,0 +1,758 @@
+use crate::{lock_mut, Drone, Message};
+use bytes::Bytes;
+use std::collections::VecDeque;
+use std::convert::TryFrom;
+use std::fmt;
+use std::io::{self, Write};
+use std::sync::{Arc, Condvar, Mutex};
+use std::task::Waker;
+use std::time::Duration;
+
+/// Extends the 8 bit frame ids of the binary protocol to increasing 32 bit ids.
+///
+/// An id, that is smaller than the last one by more than half of the range (128), wrapped
+/// around, e.g. `254, 255, 0, 1` are extended to `254, 255, 256, 257`. A smaller id within
+/// half of the range is a late frame, it gets the smaller extended id and the last id is kept.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct FrameIdExtender {
+    last: Option<u32>,
+}
+
+impl FrameIdExtender {
+    /// the extended id of the frame `id`
+    pub fn extend(&mut self, id: u8) -> u32 {
+        let last = match self.last {
+            Some(last) => last,
+            None => {
+                self.last = Some(id as u32);
+                return id as u32;
+            }
+        };
+        let ahead = id.wrapping_sub(last as u8);
+        if ahead < 128 {
+            let extended = last.wrapping_add(ahead as u32);
+            self.last = Some(extended);
+            extended
+        } else {
+            last.saturating_sub(256 - ahead as u32)
+        }
+    }
+
+    /// the extended id of the last frame
+    pub fn last(&self) -> Option<u32> {
+        self.last
+    }
+}
+
+/// Reassembles the video packets of the binary protocol to complete frames.
+///
+/// Each udp packet starts with the frame id and the sequence number of the packet,
+/// followed by the h264 data. The packets of one frame are collected in one buffer, which is
+/// allocated once with the size of the largest frame so far. Receiving a packet does not
+/// allocate any memory, only the completed frame is handed out as a new `Vec<u8>`.
+#[derive(Debug, Clone)]
+pub struct VideoFrameAssembler {
+    frame_id: u8,
+    active: bool,
+    frame: Vec<u8>,
+    max_frame_size: usize,
+    frames: u64,
+    broken_frames: u64,
+    frame_ids: FrameIdExtender,
+    /// the extended id of the last completed frame
+    extended_frame_id: u32,
+}
+
+impl Default for VideoFrameAssembler {
+    fn default() -> Self {
+        Self::with_capacity(20_000)
+    }
+}
+
+impl VideoFrameAssembler {
+    /// create a new assembler with a preallocated frame buffer
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            frame_id: 0,
+            active: false,
+            frame: Vec::with_capacity(capacity),
+            max_frame_size: capacity,
+            frames: 0,
+            broken_frames: 0,
+            frame_ids: FrameIdExtender::default(),
+            extended_frame_id: 0,
+        }
+    }
+
+    /// the 32 bit id of the last completed frame, see `FrameIdExtender`. A late frame keeps
+    /// its smaller id.
+    pub fn extended_frame_id(&self) -> u32 {
+        self.extended_frame_id
+    }
+
+    /// number of the completed frames
+    pub fn frames(&self) -> u64 {
+        self.frames
+    }
+
+    /// number of the incomplete frames, dropped because of a lost packet
+    pub fn broken_frames(&self) -> u64 {
+        self.broken_frames
+    }
+
+    /// is there an incomplete frame in the buffer
+    pub fn is_active(&self) -> bool {
+        self.active
+    }
+
+    /// drop the current (incomplete) frame
+    pub fn reset(&mut self) {
+        self.active = false;
+        self.frame.clear();
+    }
+
+    /// push a received video packet `[frame_id, sqn, data...]` to the frame.
+    ///
+    /// returns the frame id and the data, when the frame is complete.
+    /// - a frame has to start with the sqn 0, otherwise the packet is dropped
+    /// - a packet of another frame drops the incomplete frame, and starts the next one with
+    ///   the sqn 0
+    pub fn push(&mut self, packet: &[u8]) -> Option<(u8, Vec<u8>)> {
+        if packet.len() < 2 {
+            return None;
+        }
+        let frame_id = packet[0];
+        let sqn = packet[1];
+
+        if self.active && frame_id != self.frame_id {
+            // the end of the frame is lost, drop it to stop data mess
+            log::debug!(target: "tello::video", "frame {} is incomplete", self.frame_id);
+            self.reset();
+            self.broken_frames += 1;
+        }
+        if !self.active {
+            // should start with 0. otherwise delete frame package
+            if sqn != 0 {
+                return None;
+            }
+            self.active = true;
+            self.frame_id = frame_id;
+        }
+
+        self.frame.extend_from_slice(&packet[2..]);
+
+        if sqn >= 120 {
+            self.active = false;
+            self.frames += 1;
+            self.extended_frame_id = self.frame_ids.extend(self.frame_id);
+            self.max_frame_size = self.max_frame_size.max(self.frame.len());
+            let frame = std::mem::replace(&mut self.frame, Vec::with_capacity(self.max_frame_size));
+            Some((self.frame_id, frame))
+        } else {
+            None
+        }
+    }
+}
+
+/// default receive buffer (SO_RCVBUF) of the video sockets. The default of the OS is too small
+/// for the bursts of a key frame, when the receiver stalls for a moment.
+pub const DEFAULT_VIDEO_RECV_BUFFER: usize = 512 * 1024;
+
+/// set the receive buffer of the socket and return the effective size. The OS may clamp it
+/// (linux: `net.core.rmem_max`) and linux reports the doubled size, including its bookkeeping.
+pub(crate) fn set_recv_buffer(socket: socket2::SockRef<'_>, bytes: usize) -> io::Result<usize> {
+    socket.set_recv_buffer_size(bytes)?;
+    socket.recv_buffer_size()
+}
+
+/// Statistics of the video receiver, see `Drone::video_stats()`.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct VideoStats {
+    /// completed frames
+    pub frames: u64,
+    /// incomplete frames, dropped because of a lost packet
+    pub broken_frames: u64,
+    /// effective receive buffer of the video socket in bytes, `None` before `connect()`
+    pub recv_buffer: Option<usize>,
+    /// key-frames requested with `start_video()`, by `tick_send()` or by hand
+    pub keyframe_requests: u64,
+    /// share of the broken frames between the last two wifi infos, see `Drone::link_quality()`
+    pub frame_loss: f32,
+}
+
+/// Video data of the SDK stream, see `CommandMode::video_receiver()`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum VideoChunk {
+    /// a complete h264 NAL unit (Annex-B) of the stream
+    FrameComplete(Vec<u8>),
+    /// the frame exceeds the maximum size and is dropped
+    FrameDropped,
+}
+
+/// size of a full udp packet of the SDK video stream
+const SDK_PACKET_SIZE: usize = 1460;
+/// larger frames are dropped, they are broken anyway
+const MAX_SDK_FRAME_SIZE: usize = 256 * 1024;
+const START_CODE: &[u8] = &[0, 0, 0, 1];
+
+/// Reassembles the video packets of the SDK stream (`streamon`).
+///
+/// The packets have no header. A frame ends with a packet shorter than 1460 bytes or when the
+/// next packet starts with a h264 start code.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct SdkFrameAssembler {
+    frame: Vec<u8>,
+    dropping: bool,
+}
+
+impl SdkFrameAssembler {
+    /// push a received packet. Returns the completed or dropped frames.
+    pub(crate) fn push(&mut self, packet: &[u8]) -> Vec<VideoChunk> {
+        let mut chunks = Vec::new();
+        if packet.starts_with(START_CODE) {
+            if !self.frame.is_empty() {
+                chunks.push(VideoChunk::FrameComplete(std::mem::take(&mut self.frame)));
+            }
+            self.dropping = false;
+        }
+
+        if !self.dropping {
+            if self.frame.len() + packet.len() > MAX_SDK_FRAME_SIZE {
+                log::debug!(target: "tello::video", "frame exceeds {} bytes", MAX_SDK_FRAME_SIZE);
+                self.frame.clear();
+                self.dropping = true;
+                chunks.push(VideoChunk::FrameDropped);
+            } else {
+                self.frame.extend_from_slice(packet);
+            }
+        }
+
+        if packet.len() < SDK_PACKET_SIZE {
+            if !self.frame.is_empty() {
+                chunks.push(VideoChunk::FrameComplete(std::mem::take(&mut self.frame)));
+            }
+            self.dropping = false;
+        }
+        chunks
+    }
+}
+
+/// Writer for the received frames, see `Drone::record_video_to()`.
+pub(crate) struct VideoRecorder {
+    /// the mutex keeps the `Drone` `Sync`, the writer only has to be `Send`
+    writer: Mutex<Box<dyn Write + Send>>,
+}
+
+impl VideoRecorder {
+    pub(crate) fn new<W: Write + Send + 'static>(writer: W) -> Self {
+        Self {
+            writer: Mutex::new(Box::new(writer)),
+        }
+    }
+
+    /// write the frame as it is. The frames are already h264 Annex-B NAL units.
+    pub(crate) fn write_frame(&mut self, frame: &[u8]) -> io::Result<()> {
+        lock_mut(&mut self.writer).write_all(frame)
+    }
+
+    pub(crate) fn flush(&mut self) -> io::Result<()> {
+        lock_mut(&mut self.writer).flush()
+    }
+}
+
+impl fmt::Debug for VideoRecorder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("VideoRecorder").finish()
+    }
+}
+
+/// Iterator of the video frames of the drone, see `Drone::video_frames()`.
+///
+/// It borrows the drone mutably and polls it, until the next frame is received. The other
+/// messages are processed by the poll (acks, meta data, ...) but not returned.
+#[derive(Debug)]
+pub struct VideoFrames<'a> {
+    drone: &'a mut Drone,
+}
+
+impl<'a> VideoFrames<'a> {
+    pub(crate) fn new(drone: &'a mut Drone) -> Self {
+        Self { drone }
+    }
+}
+
+impl<'a> Iterator for VideoFrames<'a> {
+    type Item = (u32, Vec<u8>);
+
+    /// blocks until the next frame is received. The iterator never ends.
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            match self.drone.poll() {
+                Some(Message::Frame(_, data)) => {
+                    return Some((self.drone.video.assembler.extended_frame_id(), data))
+                }
+                Some(Message::FrameTs { id, data, .. }) => return Some((id, data)),
+                Some(_) => (),
+                None => std::thread::sleep(Duration::from_millis(5)),
+            }
+        }
+    }
+}
+
+/// frames, that are not consumed yet. See `frame_queue()`.
+#[derive(Debug)]
+pub(crate) struct FrameQueue {
+    frames: VecDeque<(u32, Bytes)>,
+    capacity: usize,
+    dropped: u64,
+    closed: bool,
+    waker: Option<Waker>,
+}
+
+pub(crate) type SharedQueue = Arc<(Mutex<FrameQueue>, Condvar)>;
+
+/// create a queue of at most `capacity` frames, the producer and the consumer side.
+pub(crate) fn frame_queue(capacity: usize) -> (FrameSink, SharedQueue) {
+    let queue = Arc::new((
+        Mutex::new(FrameQueue {
+            frames: VecDeque::with_capacity(capacity),
+            capacity: capacity.max(1),
+            dropped: 0,
+            closed: false,
+            waker: None,
+        }),
+        Condvar::new(),
+    ));
+    (FrameSink(queue.clone()), queue)
+}
+
+/// Producer of a `VideoFrameStream` or a `VideoFrameIter`. The consumer ends, when it is dropped.
+#[derive(Debug)]
+pub(crate) struct FrameSink(SharedQueue);
+
+impl FrameSink {
+    fn update(&self, f: impl FnOnce(&mut FrameQueue)) {
+        let (queue, condvar) = &*self.0;
+        let mut queue = queue.lock().unwrap_or_else(|e| e.into_inner());
+        f(&mut queue);
+        if let Some(waker) = queue.waker.take() {
+            waker.wake();
+        }
+        condvar.notify_all();
+    }
+
+    /// add the frame, the oldest frame is dropped if the queue is full
+    pub(crate) fn push(&self, frame_id: u32, data: Vec<u8>) {
+        self.update(|queue| {
+            if queue.frames.len() >= queue.capacity {
+                log::trace!(target: "tello::video", "queue is full, the oldest frame is dropped");
+                queue.frames.pop_front();
+                queue.dropped += 1;
+            }
+            queue.frames.push_back((frame_id, Bytes::from(data)));
+        });
+    }
+
+    /// count a frame, that is dropped by the assembler
+    pub(crate) fn drop_frame(&self) {
+        self.update(|queue| queue.dropped += 1);
+    }
+
+    /// forward the chunks of the SDK video stream, the frames are numbered in order
+    pub(crate) fn push_chunk(&self, frame_id: &mut u32, chunk: VideoChunk) {
+        match chunk {
+            VideoChunk::FrameComplete(data) => {
+                self.push(*frame_id, data);
+                *frame_id = frame_id.wrapping_add(1);
+            }
+            VideoChunk::FrameDropped => self.drop_frame(),
+        }
+    }
+}
+
+impl Drop for FrameSink {
+    fn drop(&mut self) {
+        self.update(|queue| queue.closed = true);
+    }
+}
+
+fn dropped_frames(queue: &SharedQueue) -> u64 {
+    queue.0.lock().unwrap_or_else(|e| e.into_inner()).dropped
+}
+
+/// `Stream` of the video frames `(frame_id, data)` in the received order.
+///
+/// At most `capacity` frames are buffered. When the consumer is too slow, the oldest frame
+/// is dropped and counted in `dropped_frames()`. See `AsyncDrone::video_frames()` and
+/// `CommandMode::video_frames()`.
+#[cfg(feature = "tokio_async")]
+#[derive(Debug)]
+pub struct VideoFrameStream {
+    queue: SharedQueue,
+}
+
+#[cfg(feature = "tokio_async")]
+impl VideoFrameStream {
+    pub(crate) fn new(queue: SharedQueue) -> Self {
+        Self { queue }
+    }
+
+    /// number of the frames, that are dropped so far (slow consumer or broken frame)
+    pub fn dropped_frames(&self) -> u64 {
+        dropped_frames(&self.queue)
+    }
+}
+
+#[cfg(feature = "tokio_async")]
+impl tokio_stream::Stream for VideoFrameStream {
+    type Item = (u32, Bytes);
+
+    fn poll_next(
+        self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<Option<Self::Item>> {
+        use std::task::Poll;
+        let mut queue = self.queue.0.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(frame) = queue.frames.pop_front() {
+            Poll::Ready(Some(frame))
+        } else if queue.closed {
+            Poll::Ready(None)
+        } else {
+            queue.waker = Some(cx.waker().clone());
+            Poll::Pending
+        }
+    }
+}
+
+/// Blocking iterator of the video frames `(frame_id, data)` in the received order, like the
+/// `VideoFrameStream`. See `Drone::video_frames_iter()` and `CommandMode::video_frames_iter()`.
+#[derive(Debug)]
+pub struct VideoFrameIter {
+    queue: SharedQueue,
+}
+
+impl VideoFrameIter {
+    pub(crate) fn new(queue: SharedQueue) -> Self {
+        Self { queue }
+    }
+
+    /// number of the frames, that are dropped so far (slow consumer or broken frame)
+    pub fn dropped_frames(&self) -> u64 {
+        dropped_frames(&self.queue)
+    }
+}
+
+impl Iterator for VideoFrameIter {
+    type Item = (u32, Bytes);
+
+    /// blocks until the next frame is received. Ends when the video receiver is closed.
+    fn next(&mut self) -> Option<Self::Item> {
+        let (queue, condvar) = &*self.queue;
+        let mut queue = queue.lock().unwrap_or_else(|e| e.into_inner());
+        loop {
+            if let Some(frame) = queue.frames.pop_front() {
+                return Some(frame);
+            }
+            if queue.closed {
+                return None;
+            }
+            queue = condvar.wait(queue).unwrap_or_else(|e| e.into_inner());
+        }
+    }
+}
+
+/// h264 NAL unit type of the sequence parameter set
+const NAL_SPS: u8 = 7;
+
+/// The width and height of the first SPS (sequence parameter set) in the h264 frame
+/// (Annex-B), e.g. to detect the new geometry after `Drone::set_video_mode()`.
+pub fn sps_dimensions(frame: &[u8]) -> Option<(u32, u32)> {
+    let start = frame
+        .windows(4)
+        .position(|w| w[..3] == [0, 0, 1] && w[3] & 0x1f == NAL_SPS)?
+        + 4;
+    let end = frame[start..]
+        .windows(3)
+        .position(|w| w == [0, 0, 1])
+        .map_or(frame.len(), |end| start + end);
+    // remove the emulation prevention bytes (0x00 0x00 0x03)
+    let mut rbsp = Vec::with_capacity(end - start);
+    for &byte in &frame[start..end] {
+        if byte == 3 && rbsp.ends_with(&[0, 0]) {
+            continue;
+        }
+        rbsp.push(byte);
+    }
+    parse_sps(&mut BitReader {
+        data: &rbsp,
+        pos: 0,
+    })
+}
+
+/// the fields of the SPS up to the frame size and cropping, see ITU-T H.264 7.3.2.1.1
+fn parse_sps(r: &mut BitReader) -> Option<(u32, u32)> {
+    let profile_idc = r.bits(8)?;
+    r.bits(16)?; // constraint flags, level_idc
+    r.ue()?; // seq_parameter_set_id
+    let mut chroma_format_idc = 1;
+    if [100, 110, 122, 244, 44, 83, 86, 118, 128, 138, 139, 134, 135].contains(&profile_idc) {
+        chroma_format_idc = r.ue()?;
+        if chroma_format_idc == 3 {
+            r.bits(1)?; // separate_colour_plane_flag
+        }
+        r.ue()?; // bit_depth_luma_minus8
+        r.ue()?; // bit_depth_chroma_minus8
+        r.bits(1)?; // qpprime_y_zero_transform_bypass_flag
+        if r.bits(1)? == 1 {
+            let lists = if chroma_format_idc == 3 { 12 } else { 8 };
+            for list in 0..lists {
+                if r.bits(1)? == 1 {
+                    skip_scaling_list(r, if list < 6 { 16 } else { 64 })?;
+                }
+            }
+        }
+    }
+    r.ue()?; // log2_max_frame_num_minus4
+    match r.ue()? {
+        0 => {
+            r.ue()?; // log2_max_pic_order_cnt_lsb_minus4
+        }
+        1 => {
+            r.bits(1)?; // delta_pic_order_always_zero_flag
+            r.se()?; // offset_for_non_ref_pic
+            r.se()?; // offset_for_top_to_bottom_field
+            for _ in 0..r.ue()? {
+                r.se()?; // offset_for_ref_frame
+            }
+        }
+        _ => (),
+    }
+    r.ue()?; // max_num_ref_frames
+    r.bits(1)?; // gaps_in_frame_num_value_allowed_flag
+
+    // u64, a broken SPS must not overflow
+    let width_in_mbs = r.ue()? as u64 + 1;
+    let height_in_map_units = r.ue()? as u64 + 1;
+    let frame_mbs_only = r.bits(1)? as u64;
+    if frame_mbs_only == 0 {
+        r.bits(1)?; // mb_adaptive_frame_field_flag
+    }
+    r.bits(1)?; // direct_8x8_inference_flag
+    let (mut crop_x, mut crop_y) = (0, 0);
+    if r.bits(1)? == 1 {
+        let mut crop = || r.ue().map(u64::from);
+        let (left, right, top, bottom) = (crop()?, crop()?, crop()?, crop()?);
+        // the crop unit of 4:2:0, 4:2:2 and 4:4:4
+        let (unit_x, unit_y) = match chroma_format_idc {
+            1 => (2, 2),
+            2 => (2, 1),
+            _ => (1, 1),
+        };
+        crop_x = (left + right) * unit_x;
+        crop_y = (top + bottom) * unit_y * (2 - frame_mbs_only);
+    }
+    let width = (width_in_mbs * 16).checked_sub(crop_x)?;
+    let height = ((2 - frame_mbs_only) * height_in_map_units * 16).checked_sub(crop_y)?;
+    Some((u32::try_from(width).ok()?, u32::try_from(height).ok()?))
+}
+
+fn skip_scaling_list(r: &mut BitReader, size: usize) -> Option<()> {
+    let (mut last, mut next) = (8i64, 8i64);
+    for _ in 0..size {
+        if next != 0 {
+            next = (last + r.se()? + 256) % 256;
+        }
+        if next != 0 {
+            last = next;
+        }
+    }
+    Some(())
+}
+
+/// reads the bits and Exp-Golomb codes of the SPS
+struct BitReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> BitReader<'a> {
+    fn bits(&mut self, count: u32) -> Option<u32> {
+        let mut value = 0;
+        for _ in 0..count {
+            let byte = self.data.get(self.pos / 8)?;
+            value = (value << 1) | ((byte >> (7 - self.pos % 8)) & 1) as u32;
+            self.pos += 1;
+        }
+        Some(value)
+    }
+
+    /// unsigned Exp-Golomb code
+    fn ue(&mut self) -> Option<u32> {
+        let mut zeros = 0;
+        while self.bits(1)? == 0 {
+            zeros += 1;
+            if zeros > 31 {
+                return None;
+            }
+        }
+        Some((1 << zeros) - 1 + self.bits(zeros)?)
+    }
+
+    /// signed Exp-Golomb code
+    fn se(&mut self) -> Option<i64> {
+        let value = self.ue()? as i64;
+        Some(if value % 2 == 1 {
+            (value + 1) / 2
+        } else {
+            -value / 2
+        })
+    }
+}
+
+#[test]
+pub fn test_sps_dimensions() {
+    // the SPS of the drone (codec_data of `Drone::start_video()`)
+    let sps_960 = [
+        0, 0, 0, 1, 0x67, 0x4d, 0x40, 0x28, 0x95, 0xa0, 0x3c, 0x05, 0xb9,
+    ];
+    let pps = [0, 0, 0, 1, 0x68, 0xee, 0x38, 0x80];
+    assert_eq!(sps_dimensions(&sps_960), Some((960, 720)));
+    let frame = [&pps[..], &sps_960[..], &pps[..]].concat();
+    assert_eq!(sps_dimensions(&frame), Some((960, 720)));
+
+    let sps_1280 = [
+        0, 0, 1, 0x67, 0x4d, 0x40, 0x28, 0x95, 0xa0, 0x14, 0x01, 0x6e, 0x40,
+    ];
+    assert_eq!(sps_dimensions(&sps_1280), Some((1280, 720)));
+
+    assert_eq!(sps_dimensions(&pps), None);
+    assert_eq!(sps_dimensions(&sps_960[..8]), None);
+}
+
+#[test]
+pub fn test_assemble_frame() {
+    let mut assembler = VideoFrameAssembler::with_capacity(10);
+    assert_eq!(assembler.push(&[3, 0, 1, 2]), None);
+    assert!(assembler.is_active());
+    assert_eq!(assembler.push(&[3, 1, 3, 4]), None);
+    assert_eq!(assembler.push(&[3, 128, 5]), Some((3, vec![1, 2, 3, 4, 5])));
+    assert!(!assembler.is_active());
+    assert_eq!(assembler.extended_frame_id(), 3);
+    for id in &[100, 200, 255, 2] {
+        assembler.push(&[*id, 0]);
+        assembler.push(&[*id, 128]);
+    }
+    assert_eq!(assembler.extended_frame_id(), 258);
+
+    // a late frame gets its own id, the next frame continues after the last one
+    for (id, extended) in &[(1, 257), (3, 259)] {
+        assembler.push(&[*id, 0]);
+        assembler.push(&[*id, 128]);
+        assert_eq!(assembler.extended_frame_id(), *extended);
+    }
+}
+
+#[test]
+pub fn test_extend_frame_ids() {
+    let extend = |ids: &[u8]| {
+        let mut extender = FrameIdExtender::default();
+        ids.iter()
+            .map(|id| extender.extend(*id))
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(
+        extend(&[253, 254, 255, 0, 1, 2]),
+        [253, 254, 255, 256, 257, 258]
+    );
+    // wraps with a last id below 100, e.g. after lost frames
+    assert_eq!(extend(&[10, 90, 160, 230, 44]), [10, 90, 160, 230, 300]);
+    // three wraps, the ids keep increasing
+    let ids: Vec<u8> = (0..800u32).step_by(7).map(|id| id as u8).collect();
+    let extended = extend(&ids);
+    assert!(extended.windows(2).all(|w| w[1] > w[0]));
+    assert_eq!(*extended.last().unwrap(), 798);
+    // a late frame does not count as wrap
+    assert_eq!(extend(&[254, 1, 255, 2]), [254, 257, 255, 258]);
+    assert_eq!(extend(&[5, 3, 6]), [5, 3, 6]);
+}
+
+#[test]
+pub fn test_assemble_drops_broken_frames() {
+    let mut assembler = VideoFrameAssembler::default();
+    // does not start with sqn 0
+    assert_eq!(assembler.push(&[3, 1, 1, 2]), None);
+    assert!(!assembler.is_active());
+    // packet of another frame
+    assert_eq!(assembler.push(&[3, 0, 1, 2]), None);
+    assert_eq!(assembler.push(&[4, 1, 1, 2]), None);
+    assert!(!assembler.is_active());
+    assert_eq!(assembler.push(&[4, 0, 7]), None);
+    assert_eq!(assembler.push(&[4, 128, 8]), Some((4, vec![7, 8])));
+    // the first packet of the next frame starts it
+    assert_eq!(assembler.push(&[5, 0, 1]), None);
+    assert_eq!(assembler.push(&[6, 0, 9]), None);
+    assert_eq!(assembler.push(&[6, 128, 8]), Some((6, vec![9, 8])));
+    assert_eq!((assembler.frames(), assembler.broken_frames()), (2, 2));
+}
+
+#[test]
+pub fn test_sdk_assemble_frames() {
+    let mut assembler = SdkFrameAssembler::default();
+    let mut full = vec![7u8; SDK_PACKET_SIZE];
+    full[..4].copy_from_slice(START_CODE);
+
+    assert_eq!(assembler.push(&full), vec![]);
+    assert_eq!(
+        assembler.push(&[1, 2]),
+        vec![VideoChunk::FrameComplete([&full[..], &[1, 2]].concat())]
+    );
+    // a full packet at the end of the frame is completed by the start code of the next one
+    assert_eq!(assembler.push(&full), vec![]);
+    assert_eq!(
+        assembler.push(&full),
+        vec![VideoChunk::FrameComplete(full.clone())]
+    );
+    assert_eq!(
+        assembler.push(&[0, 0, 0, 1, 5]),
+        vec![
+            VideoChunk::FrameComplete(full.clone()),
+            VideoChunk::FrameComplete(vec![0, 0, 0, 1, 5])
+        ]
+    );
+}
+
+#[test]
+pub fn test_sdk_drop_large_frames() {
+    let mut assembler = SdkFrameAssembler::default();
+    let full = vec![7u8; SDK_PACKET_SIZE];
+    let mut chunks = vec![];
+    for _ in 0..(MAX_SDK_FRAME_SIZE / SDK_PACKET_SIZE + 10) {
+        chunks.extend(assembler.push(&full));
+    }
+    chunks.extend(assembler.push(&[1, 2]));
+    assert_eq!(chunks, vec![VideoChunk::FrameDropped]);
+    assert_eq!(
+        assembler.push(&[0, 0, 0, 1, 5]),
+        vec![VideoChunk::FrameComplete(vec![0, 0, 0, 1, 5])]
+    );
+}
+
+#[test]
+pub fn test_frame_queue_drops_oldest() {
+    let (sink, queue) = frame_queue(2);
+    let mut frame_id = 0;
+    sink.push_chunk(&mut frame_id, VideoChunk::FrameComplete(vec![1]));
+    sink.push_chunk(&mut frame_id, VideoChunk::FrameDropped);
+    sink.push_chunk(&mut frame_id, VideoChunk::FrameComplete(vec![2]));
+    sink.push_chunk(&mut frame_id, VideoChunk::FrameComplete(vec![3]));
+    drop(sink);
+
+    let mut queue = queue.0.lock().unwrap();
+    assert_eq!(queue.dropped, 2);
+    assert!(queue.closed);
+    let frames: Vec<_> = queue.frames.drain(..).collect();
+    assert_eq!(
+        frames,
+        vec![(1, Bytes::from(vec![2])), (2, Bytes::from(vec![3]))]
+    );
+}
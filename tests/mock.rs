@@ -0,0 +1,558 @@
+//! tests of the `Drone` and the `CommandMode` against the `MockDrone`
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tello::mock::{MockConfig, MockDrone};
+use tello::{
+    AltitudeController, CommandError, CommandIds, CommandMode, Drone, DroneError, MatrixColor,
+    Message, Mission, Package, PackageData, ResponseMsg, ScrollDirection, StepResult, Swarm,
+};
+
+/// the `Drone` binds the port 8889, run its tests one by one
+static DRONE_PORT: Mutex<()> = Mutex::new(());
+
+/// poll the drone until `f` returns a value, at most for 3 seconds
+fn poll_until<T>(drone: &mut Drone, mut f: impl FnMut(Message) -> Option<T>) -> Option<T> {
+    let start = Instant::now();
+    while start.elapsed() < Duration::from_secs(3) {
+        if let Some(value) = drone.poll().and_then(&mut f) {
+            return Some(value);
+        }
+        std::thread::sleep(Duration::from_millis(1));
+    }
+    None
+}
+
+/// a port, that is free right now
+fn free_port() -> u16 {
+    let socket = std::net::UdpSocket::bind(("0.0.0.0", 0)).unwrap();
+    socket.local_addr().unwrap().port()
+}
+
+/// the first of `count` consecutive ports, that are free right now
+fn free_ports(count: u16) -> u16 {
+    loop {
+        let first = free_port();
+        let sockets: Option<Vec<_>> = (0..count)
+            .map(|i| first.checked_add(i))
+            .map(|port| std::net::UdpSocket::bind(("0.0.0.0", port?)).ok())
+            .collect();
+        if sockets.is_some() {
+            return first;
+        }
+    }
+}
+
+/// run the `async fn` as test with tokio, or with a blocking executor without `tokio_async`
+macro_rules! async_test {
+    ($test:ident, $body:ident) => {
+        #[cfg(feature = "tokio_async")]
+        #[tokio::test]
+        pub async fn $test() {
+            $body().await
+        }
+        #[cfg(not(feature = "tokio_async"))]
+        #[test]
+        pub fn $test() {
+            futures::executor::block_on($body())
+        }
+    };
+}
+
+fn mock_config() -> MockConfig {
+    MockConfig::default().with_interval(Duration::from_millis(20))
+}
+
+#[test]
+pub fn test_connect_handshake() {
+    let _port = DRONE_PORT.lock().unwrap_or_else(|e| e.into_inner());
+    let mock = MockDrone::start(mock_config()).unwrap();
+    let mut drone = Drone::new(&mock.addr().to_string());
+    let video_port = free_port();
+    drone.connect(video_port);
+
+    let ack = poll_until(&mut drone, |msg| match msg {
+        Message::Response(ResponseMsg::Connected(ack)) => Some(ack),
+        _ => None,
+    });
+    // the port bytes are not always valid utf-8, the ack is converted lossy
+    let expected = [&b"conn_ack:"[..], &video_port.to_le_bytes()[..]].concat();
+    assert_eq!(ack.unwrap(), String::from_utf8_lossy(&expected));
+}
+
+#[test]
+pub fn test_poll_loop() {
+    let _port = DRONE_PORT.lock().unwrap_or_else(|e| e.into_inner());
+    let mock = MockDrone::start(
+        mock_config()
+            .with_height(12)
+            .with_battery(42)
+            .with_wifi(70, 5),
+    )
+    .unwrap();
+    let mut drone = Drone::new(&mock.addr().to_string());
+    drone.connect(free_port());
+
+    let flight = poll_until(&mut drone, |msg| match msg {
+        Message::Data(Package {
+            data: PackageData::FlightData(flight),
+            ..
+        }) => Some(flight),
+        _ => None,
+    })
+    .unwrap();
+    assert_eq!((flight.height, flight.battery_percentage), (12, 42));
+
+    let wifi = poll_until(&mut drone, |msg| match msg {
+        Message::Data(Package {
+            data: PackageData::WifiInfo(wifi),
+            ..
+        }) => Some(wifi),
+        _ => None,
+    })
+    .unwrap();
+    assert_eq!((wifi.strength(), wifi.disturb()), (70, 5));
+
+    // connect() starts the video
+    let frame = poll_until(&mut drone, |msg| match msg {
+        Message::Frame(_, data) => Some(data),
+        _ => None,
+    })
+    .unwrap();
+    assert!(frame.starts_with(&[0, 0, 0, 1]));
+
+    // the sticks are send with the poll loop
+    assert!(mock.packets().contains(&CommandIds::StickCmd));
+}
+
+#[test]
+pub fn test_video_frames_iter() {
+    let _port = DRONE_PORT.lock().unwrap_or_else(|e| e.into_inner());
+    let mock = MockDrone::start(mock_config()).unwrap();
+    let mut drone = Drone::new(&mock.addr().to_string());
+    drone.connect(free_port());
+    let mut frames = drone.video_frames_iter(4);
+
+    // the frames are passed to the iterator, instead of the poll
+    let stop = std::sync::Arc::new(std::sync::atomic::AtomicBool::new(false));
+    let stopped = stop.clone();
+    let poller = std::thread::spawn(move || {
+        let start = Instant::now();
+        let mut polled_frames = 0;
+        while !stopped.load(std::sync::atomic::Ordering::SeqCst)
+            && start.elapsed() < Duration::from_secs(3)
+        {
+            if let Some(Message::Frame(..)) = drone.poll() {
+                polled_frames += 1;
+            }
+            std::thread::sleep(Duration::from_millis(1));
+        }
+        polled_frames
+    });
+    let (_, frame) = frames.next().unwrap();
+    assert!(frame.starts_with(&[0, 0, 0, 1]));
+    stop.store(true, std::sync::atomic::Ordering::SeqCst);
+    assert_eq!(poller.join().unwrap(), 0);
+
+    // the iterator ends with the drone
+    assert_eq!(frames.next(), None);
+}
+
+#[test]
+pub fn test_command_ack() {
+    let _port = DRONE_PORT.lock().unwrap_or_else(|e| e.into_inner());
+    let mock = MockDrone::start(mock_config()).unwrap();
+    let mut drone = Drone::new(&mock.addr().to_string());
+    drone.connect(free_port());
+    drone.take_off().unwrap();
+
+    let ack = poll_until(&mut drone, |msg| match msg {
+        Message::Data(Package { cmd, .. }) if cmd == CommandIds::TakeoffCmd => Some(cmd),
+        _ => None,
+    });
+    assert_eq!(ack, Some(CommandIds::TakeoffCmd));
+    assert!(mock.packets().contains(&CommandIds::TakeoffCmd));
+}
+
+#[test]
+pub fn test_reconnect() {
+    let _port = DRONE_PORT.lock().unwrap_or_else(|e| e.into_inner());
+    let mock = MockDrone::start(mock_config()).unwrap();
+    let mut drone = Drone::new(&mock.addr().to_string());
+    drone.set_reconnect_timeout(Duration::from_millis(200));
+    drone.set_auto_reconnect(true);
+    drone.connect(free_port());
+    let flight_data = |msg| match msg {
+        Message::Data(Package {
+            data: PackageData::FlightData(_),
+            ..
+        }) => Some(()),
+        _ => None,
+    };
+    poll_until(&mut drone, flight_data).unwrap();
+    assert_eq!(mock.connections(), 1);
+
+    // the drone drops the connection, the requests are lost until it is back
+    mock.set_offline(true);
+    let start = Instant::now();
+    while start.elapsed() < Duration::from_millis(500) {
+        drone.poll();
+        std::thread::sleep(Duration::from_millis(1));
+    }
+    mock.set_offline(false);
+    assert_eq!(mock.connections(), 1);
+
+    poll_until(&mut drone, flight_data).unwrap();
+    assert_eq!(mock.connections(), 2);
+    assert!(!drone.ensure_connected());
+}
+
+#[test]
+pub fn test_take_off_and_wait() {
+    let _port = DRONE_PORT.lock().unwrap_or_else(|e| e.into_inner());
+    let mock = MockDrone::start(mock_config()).unwrap();
+    let mut drone = Drone::new(&mock.addr().to_string());
+    drone.connect(free_port());
+
+    let timeout = Duration::from_secs(2);
+    assert_eq!(drone.take_off_and_wait(timeout), Ok(()));
+    assert!(drone.drone_meta.get_flight_data().unwrap().em_sky);
+    // the messages received while waiting are kept
+    let connected = poll_until(&mut drone, |msg| match msg {
+        Message::Response(ResponseMsg::Connected(_)) => Some(()),
+        _ => None,
+    });
+    assert_eq!(connected, Some(()));
+
+    assert_eq!(drone.land_and_wait(timeout), Ok(()));
+    assert!(!drone.drone_meta.get_flight_data().unwrap().em_sky);
+}
+
+#[test]
+pub fn test_take_off_and_wait_errors() {
+    let _port = DRONE_PORT.lock().unwrap_or_else(|e| e.into_inner());
+    let mock = MockDrone::start(mock_config().with_battery_lower()).unwrap();
+    let mut drone = Drone::new(&mock.addr().to_string());
+    // no flight data without the connection
+    assert_eq!(
+        drone.take_off_and_wait(Duration::from_millis(100)),
+        Err(DroneError::Timeout)
+    );
+
+    drone.connect(free_port());
+    poll_until(&mut drone, |msg| match msg {
+        Message::Data(Package {
+            data: PackageData::FlightData(_),
+            ..
+        }) => Some(()),
+        _ => None,
+    })
+    .unwrap();
+    let packets = mock.packets().len();
+    assert_eq!(
+        drone.take_off_and_wait(Duration::from_secs(1)),
+        Err(DroneError::LowBattery)
+    );
+    assert_eq!(mock.packets().len(), packets);
+}
+
+#[cfg(feature = "tokio_async")]
+// the guard only serializes the tests on the port 8889, each test has its own runtime
+#[allow(clippy::await_holding_lock)]
+#[tokio::test]
+pub async fn test_spawned_take_off_and_wait() {
+    let _port = DRONE_PORT.lock().unwrap_or_else(|e| e.into_inner());
+    let mock = MockDrone::start(mock_config()).unwrap();
+    let (drone, _messages, _frames) = Drone::new(&mock.addr().to_string())
+        .into_async()
+        .unwrap()
+        .spawn(4);
+    drone.connect(free_port()).await.unwrap();
+
+    let timeout = Duration::from_secs(2);
+    assert_eq!(drone.take_off_and_wait(timeout).await, Ok(()));
+    assert_eq!(drone.land_and_wait(timeout).await, Ok(()));
+    assert!(mock.packets().contains(&CommandIds::LandCmd));
+}
+
+#[test]
+pub fn test_poll_truncated_frames() {
+    let _port = DRONE_PORT.lock().unwrap_or_else(|e| e.into_inner());
+    let mock = MockDrone::start(mock_config().with_truncated_frames()).unwrap();
+    let mut drone = Drone::new(&mock.addr().to_string());
+    drone.connect(free_port());
+
+    // the frames never complete, but the poll loop keeps running
+    let start = Instant::now();
+    while start.elapsed() < Duration::from_millis(500) {
+        let poll = Instant::now();
+        if let Some(Message::Frame(..)) = drone.poll() {
+            panic!("truncated frame completed");
+        }
+        assert!(poll.elapsed() < Duration::from_millis(50));
+    }
+    assert!(mock.packets().contains(&CommandIds::VideoStartCmd));
+    // the video was received, only the frames are broken
+    let stats = drone.video_stats();
+    assert_eq!(stats.frames, 0);
+    assert!(stats.broken_frames > 0);
+}
+
+async fn command_mode_happy_path() {
+    let mock = MockDrone::start(
+        mock_config()
+            .with_battery(64)
+            .with_rule("sdk?", "30")
+            .with_rule("sn?", "0TQDG44EDB4H1B"),
+    )
+    .unwrap();
+    let mut command_mode = CommandMode::from(mock.addr());
+    command_mode.set_command_timeout(Duration::from_secs(1));
+
+    assert_eq!(command_mode.enable().await, Ok(()));
+    assert_eq!(command_mode.take_off().await, Ok(()));
+    assert_eq!(command_mode.forward(100).await, Ok(()));
+    assert_eq!(command_mode.get_battery().await, Ok(64));
+    assert_eq!(command_mode.battery().await, Ok(64));
+    assert_eq!(command_mode.sdk_version().await, Ok("30".to_string()));
+    assert_eq!(
+        command_mode.serial_number().await,
+        Ok("0TQDG44EDB4H1B".to_string())
+    );
+    assert_eq!(command_mode.land().await, Ok(()));
+    assert_eq!(
+        mock.commands(),
+        [
+            "command",
+            "takeoff",
+            "forward 100",
+            "battery?",
+            "battery?",
+            "sdk?",
+            "sn?",
+            "land"
+        ]
+    );
+}
+async_test!(test_command_mode_happy_path, command_mode_happy_path);
+
+async fn command_mode_rules() {
+    let mock =
+        MockDrone::start(mock_config().with_rule("flip", "error Not enough battery")).unwrap();
+    let mut command_mode = CommandMode::from(mock.addr());
+    command_mode.set_command_timeout(Duration::from_secs(1));
+
+    assert_eq!(command_mode.enable().await, Ok(()));
+    assert_eq!(
+        command_mode.flip(tello::Flip::Back).await,
+        Err(CommandError::Drone("error Not enough battery".into()))
+    );
+    assert_eq!(command_mode.take_off().await, Ok(()));
+}
+async_test!(test_command_mode_rules, command_mode_rules);
+
+async fn command_mode_ext() {
+    let mock = MockDrone::start(
+        mock_config()
+            .with_rule("EXT led", "led ok")
+            .with_rule("EXT mled", "matrix ok")
+            .with_rule("EXT tof?", "tof 345\r\n"),
+    )
+    .unwrap();
+    let mut command_mode = CommandMode::from(mock.addr());
+    command_mode.set_command_timeout(Duration::from_secs(1));
+
+    assert_eq!(command_mode.ext_led(255, 0, 0).await, Ok(()));
+    assert_eq!(
+        command_mode
+            .ext_led_blink(2.0, (255, 0, 0), (0, 0, 255))
+            .await,
+        Ok(())
+    );
+    assert_eq!(
+        command_mode
+            .ext_display_text("TT", ScrollDirection::Left, MatrixColor::Blue, 1.5)
+            .await,
+        Ok(())
+    );
+    assert_eq!(command_mode.ext_tof().await, Ok(345));
+    assert_eq!(
+        command_mode.ext("led 0 0 0").await,
+        Ok("led ok".to_string())
+    );
+    // out of range, not send
+    assert!(matches!(
+        command_mode.ext_led_blink(20.0, (0, 0, 0), (0, 0, 0)).await,
+        Err(CommandError::InvalidParameter(_))
+    ));
+    assert_eq!(
+        mock.commands(),
+        [
+            "EXT led 255 0 0",
+            "EXT led bl 2 255 0 0 0 0 255",
+            "EXT mled l b 1.5 TT",
+            "EXT tof?",
+            "EXT led 0 0 0",
+        ]
+    );
+
+    // a plain `ok` is not the response of the expansion
+    let plain = MockDrone::start(mock_config()).unwrap();
+    let command_mode = CommandMode::from(plain.addr());
+    assert_eq!(
+        command_mode.ext_led(0, 0, 0).await,
+        Err(CommandError::UnknownResponse("ok".into()))
+    );
+}
+async_test!(test_command_mode_ext, command_mode_ext);
+
+async fn command_mode_motor() {
+    let mock = MockDrone::start(mock_config()).unwrap();
+    let mut command_mode = CommandMode::from(mock.addr());
+    command_mode.set_command_timeout(Duration::from_secs(1));
+    assert_eq!(command_mode.motor_on().await, Ok(()));
+    assert!(command_mode.motors_idling());
+    assert_eq!(command_mode.take_off().await, Ok(()));
+    assert!(!command_mode.motors_idling());
+    assert_eq!(mock.commands(), ["motoron", "takeoff"]);
+
+    // the older firmware does not know the command
+    let old = MockDrone::start(mock_config().with_rule("motor", "error Not support")).unwrap();
+    let command_mode = CommandMode::from(old.addr());
+    assert_eq!(
+        command_mode.motor_on().await,
+        Err(CommandError::UnsupportedFirmware("motoron".into()))
+    );
+    assert!(!command_mode.motors_idling());
+}
+async_test!(test_command_mode_motor, command_mode_motor);
+
+async fn command_mode_state() {
+    let mock = MockDrone::start(mock_config()).unwrap();
+    let status_port = free_port();
+    let mut command_mode = CommandMode::from(mock.addr()).with_status_port(status_port);
+    command_mode.set_command_timeout(Duration::from_secs(1));
+    let receiver = command_mode.state_receiver().unwrap().unwrap();
+    assert_eq!(command_mode.enable().await, Ok(()));
+    assert_eq!(
+        mock.commands(),
+        ["command".to_string(), format!("port {} 11111", status_port)]
+    );
+
+    #[cfg(feature = "tokio_async")]
+    let state = {
+        let mut state = receiver;
+        tokio::time::timeout(Duration::from_secs(3), state.changed())
+            .await
+            .unwrap()
+            .unwrap();
+        let value = state.borrow().clone();
+        value.unwrap()
+    };
+    #[cfg(not(feature = "tokio_async"))]
+    let state = receiver.recv_timeout(Duration::from_secs(3)).unwrap();
+    assert_eq!(state.bat, 87);
+}
+async_test!(test_command_mode_state, command_mode_state);
+
+#[test]
+pub fn test_take_picture_to_file() {
+    let _port = DRONE_PORT.lock().unwrap_or_else(|e| e.into_inner());
+    let jpeg: Vec<u8> = (0..5000).map(|i| i as u8).collect();
+    let mock = MockDrone::start(mock_config().with_picture(jpeg.clone())).unwrap();
+    let mut drone = Drone::new(&mock.addr().to_string());
+    drone.connect(free_port());
+
+    let path = std::env::temp_dir().join(format!("tello-picture-{}.jpg", std::process::id()));
+    drone
+        .take_picture_to_file(&path, Duration::from_secs(2))
+        .unwrap();
+    assert_eq!(std::fs::read(&path).unwrap(), jpeg);
+    std::fs::remove_file(&path).unwrap();
+    // the announcement is answered, the single piece is acknowledged as done
+    let packets = mock.packets();
+    let count = |cmd| packets.iter().filter(|p| **p == cmd).count();
+    assert_eq!(count(CommandIds::TelloCmdFileSize), 1);
+    assert_eq!(count(CommandIds::TelloCmdFileData), 1);
+    assert_eq!(count(CommandIds::TelloCmdFileComplete), 1);
+
+    // the picture is returned by the poll as well
+    drone.take_picture().unwrap();
+    let picture = poll_until(&mut drone, |msg| match msg {
+        Message::Picture(data) => Some(data),
+        _ => None,
+    });
+    assert_eq!(picture.map(|p| p.len()), Some(5000));
+}
+
+/// step response of the altitude controller, the mock climbs with 100 cm/s at the full stick
+async fn hold_altitude() {
+    let mock = MockDrone::start(mock_config().with_climb_rate(100.0)).unwrap();
+    let mut command_mode = CommandMode::from(mock.addr()).with_status_port(free_port());
+    command_mode.set_command_timeout(Duration::from_secs(1));
+    let mut state = command_mode.state_receiver().unwrap().unwrap();
+    assert_eq!(command_mode.enable().await, Ok(()));
+
+    let mut controller = AltitudeController::new(80);
+    let result = command_mode
+        .hold_altitude(&mut state, &mut controller, Duration::from_secs(4))
+        .await;
+    assert_eq!(result, Ok(()));
+
+    // the `rc` commands have no response, wait until the mock received the last one
+    std::thread::sleep(Duration::from_millis(50));
+    let commands = mock.commands();
+    assert_eq!(commands.last().unwrap(), "rc 0 0 0 0");
+    // climbs with the limited stick first
+    assert!(commands.contains(&"rc 0 0 60 0".to_string()));
+    let last_rc = &commands[commands.len() - 2];
+    let up_down: i8 = last_rc.split(' ').nth(3).unwrap().parse().unwrap();
+    assert!(up_down.abs() <= 5, "{}", last_rc);
+}
+async_test!(test_hold_altitude, hold_altitude);
+
+async fn swarm() {
+    let left = MockDrone::start(mock_config().with_battery(50)).unwrap();
+    let right = MockDrone::start(mock_config().with_rule("forward", "error No valid imu")).unwrap();
+    let status_port = free_ports(4);
+    let video_port = status_port + 2;
+    let mut swarm = Swarm::default().with_ports(status_port, video_port);
+    assert_eq!(swarm.add_drone("left", left.addr()), Ok(0));
+    assert_eq!(swarm.add_drone("right", right.addr()), Ok(1));
+    for index in 0..2 {
+        swarm
+            .drone(index)
+            .unwrap()
+            .set_command_timeout(Duration::from_secs(1));
+    }
+    assert_eq!(swarm.names().collect::<Vec<_>>(), ["left", "right"]);
+
+    assert_eq!(swarm.all().enable().await, [Ok(()), Ok(())]);
+    let ports = |offset| format!("port {} {}", status_port + offset, video_port + offset);
+    assert_eq!(left.commands(), ["command".to_string(), ports(0)]);
+    assert_eq!(right.commands(), ["command".to_string(), ports(1)]);
+    assert_eq!(swarm.by_name("left").unwrap().get_battery().await, Ok(50));
+
+    let mission = Mission::new().take_off().forward(100).land();
+    let reports = swarm.run_missions(&[mission.clone(), mission]).await;
+    assert!(reports[0].iter().all(|r| r.result == StepResult::Done));
+    assert_eq!(
+        reports[1].iter().map(|r| &r.result).collect::<Vec<_>>(),
+        [
+            &StepResult::Done,
+            &StepResult::Failed(CommandError::Drone("error No valid imu".into())),
+            &StepResult::Skipped
+        ]
+    );
+
+    // each drone sends its state to its own port
+    let start = Instant::now();
+    while swarm.states().iter().any(Option::is_none) {
+        assert!(start.elapsed() < Duration::from_secs(3));
+        #[cfg(feature = "tokio_async")]
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        #[cfg(not(feature = "tokio_async"))]
+        std::thread::sleep(Duration::from_millis(10));
+    }
+    assert_eq!(swarm.states().len(), 2);
+}
+async_test!(test_swarm, swarm);